@@ -0,0 +1,132 @@
+//! # Device address
+//!
+//! The I2C address of PCA9539 depends on the level of the A1 and A0 pins:
+//!
+//! | A1 | A0 | Address |
+//! |----|----|---------|
+//! | L  | L  | 0x74    |
+//! | L  | H  | 0x75    |
+//! | H  | L  | 0x76    |
+//! | H  | H  | 0x77    |
+//!
+//! [Address] is derived from the strap levels, so an invalid address can not be passed to
+//! [PCA9539](crate::expander::PCA9539) by accident.
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::address::Strap::{High, Low};
+//!
+//! const EXPANDER: Address = Address::new(Low, High);
+//! assert_eq!(0x75, EXPANDER.value());
+//! ```
+//! ## Other family members
+//! Register compatible devices with a different address range may be addressed using
+//! [Address::custom()]. The address is validated to be a non-reserved 7-bit address. In const
+//! contexts, an invalid address fails at compile time.
+//! ```
+//! use pca9539::address::Address;
+//!
+//! // PCA9535 with A2 = A1 = A0 = L
+//! const EXPANDER: Address = Address::custom(0x20);
+//! ```
+//! ```compile_fail
+//! use pca9539::address::Address;
+//!
+//! // Reserved address, fails at compile time
+//! const EXPANDER: Address = Address::custom(0x78);
+//! ```
+//! ## Runtime conversion
+//! Addresses known only at runtime (e.g. from configuration storage) are validated using [TryFrom].
+//! Only the four PCA9539 addresses are accepted.
+//! ```
+//! use pca9539::address::{Address, InvalidAddress};
+//!
+//! assert_eq!(0x76, Address::try_from(0x76).unwrap().value());
+//! assert_eq!(InvalidAddress(0x47), Address::try_from(0x47).unwrap_err());
+//! ```
+
+/// Level of an address strap pin (A0 or A1)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Strap {
+    /// Pin connected to GND
+    Low,
+    /// Pin connected to VCC
+    High,
+}
+
+/// Validated 7-bit I2C address of an expander
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Address(u8);
+
+/// The given value is not a valid PCA9539 address
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InvalidAddress(pub u8);
+
+/// Address of PCA9539 with A1 = A0 = L
+const BASE_ADDRESS: u8 = 0x74;
+
+impl Address {
+    /// All addresses selectable by A1/A0 strapping
+    pub const ALL: [Address; 4] = [
+        Address::new(Strap::Low, Strap::Low),
+        Address::new(Strap::Low, Strap::High),
+        Address::new(Strap::High, Strap::Low),
+        Address::new(Strap::High, Strap::High),
+    ];
+
+    /// Derives the address from the level of the A1 and A0 pins
+    pub const fn new(a1: Strap, a0: Strap) -> Self {
+        let mut address = BASE_ADDRESS;
+
+        if let Strap::High = a1 {
+            address |= 0b10;
+        }
+
+        if let Strap::High = a0 {
+            address |= 0b01;
+        }
+
+        Self(address)
+    }
+
+    /// Arbitrary address for register compatible family members
+    /// Panics if the given value is not a 7-bit address or a reserved address (0x00-0x07, 0x78-0x7F).
+    /// In const contexts this results in a compile time error.
+    pub const fn custom(address: u8) -> Self {
+        assert!(address <= 0x7F, "I2C address exceeds 7 bits");
+        assert!(address >= 0x08 && address <= 0x77, "Reserved I2C address");
+
+        Self(address)
+    }
+
+    /// Returns the raw 7-bit address
+    pub const fn value(self) -> u8 {
+        self.0
+    }
+}
+
+impl Default for Address {
+    /// Address with A1 = A0 = L (0x74)
+    fn default() -> Self {
+        Address::new(Strap::Low, Strap::Low)
+    }
+}
+
+impl TryFrom<u8> for Address {
+    type Error = InvalidAddress;
+
+    /// Accepts just the four PCA9539 addresses. For other family members use [Address::custom()].
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x74..=0x77 => Ok(Address(value)),
+            _ => Err(InvalidAddress(value)),
+        }
+    }
+}
+
+impl From<Address> for u8 {
+    fn from(address: Address) -> Self {
+        address.0
+    }
+}
@@ -0,0 +1,84 @@
+//! Decodes I2C logic analyzer CSV exports into PCA9539 operations
+//!
+//! Usage: `pca9539-decode [--address <address>]... <file>`
+//!
+//! Reads from stdin if the file is `-`. By default all four PCA9539 addresses are decoded.
+//! Exits with code 2 if any protocol anomaly has been detected.
+use pca9539::address::Address;
+use pca9539::decoder::{CsvParser, Decoder};
+use std::io::{BufRead, BufReader, Read};
+use std::process::ExitCode;
+
+const USAGE: &str = "Usage: pca9539-decode [--address <address>]... <file>";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(0) => ExitCode::SUCCESS,
+        Ok(anomalies) => {
+            eprintln!("{} anomalies detected", anomalies);
+            ExitCode::from(2)
+        }
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Decodes the input and returns the number of anomalies
+fn run() -> Result<usize, String> {
+    let mut addresses = Vec::new();
+    let mut path = None;
+    let mut args = std::env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--address" => {
+                let value = args.next().ok_or(USAGE)?;
+                let value = u8::from_str_radix(value.trim_start_matches("0x"), 16)
+                    .map_err(|_| format!("Invalid address {}", value))?;
+                addresses.push(Address::try_from(value).map_err(|_| format!("Invalid address {:#04x}", value))?);
+            }
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    let input: Box<dyn Read> = match path.as_deref() {
+        None => return Err(USAGE.to_string()),
+        Some("-") => Box::new(std::io::stdin()),
+        Some(path) => Box::new(std::fs::File::open(path).map_err(|error| format!("{}: {}", path, error))?),
+    };
+
+    let mut decoder = match addresses.is_empty() {
+        true => Decoder::new(),
+        false => Decoder::with_addresses(&addresses)
+            .map_err(|_| format!("More than four addresses given\n{}", USAGE))?,
+    };
+
+    let mut lines = BufReader::new(input).lines();
+    let header = lines.next().ok_or("Empty input")?.map_err(|error| error.to_string())?;
+    let mut parser = CsvParser::new(&header).map_err(|error| format!("Invalid header: {:?}", error))?;
+
+    let mut anomalies = 0;
+    let mut print = |event| {
+        decoder.feed(event, &mut |operation| {
+            if operation.is_anomaly() {
+                anomalies += 1;
+            }
+
+            println!("{:>12.6} {}", operation.time, operation);
+        })
+    };
+
+    for (number, line) in lines.enumerate() {
+        let line = line.map_err(|error| error.to_string())?;
+        parser
+            .parse_line(&line, &mut print)
+            .map_err(|error| format!("Line {}: {:?}", number + 2, error))?;
+    }
+
+    parser.finish(&mut print);
+    Ok(anomalies)
+}
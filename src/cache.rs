@@ -0,0 +1,209 @@
+//! # Atomic register cache
+//!
+//! Each [concurrency guard](crate::guard) owns a mirror of the cached register state, stored in
+//! atomics. The following pin operations are just acting on the mirror, so they proceed without
+//! taking the lock of the guard, e.g. while an interrupt handler reads a cached input and the main
+//! loop is blocked by a long I2C transfer:
+//! * `is_high()` and `is_low()` of input pins in [refresh mode](crate::pins::RefreshMode)
+//! * `set_state()`, `set_high()`, `set_low()` and `actual_level()` of output pins in refresh mode
+//! * `is_set_high()` and `is_set_low()` of output pins in both access modes
+//!
+//! I2C transfers remain serialized by the guard. Whenever an operation takes the lock, pending output
+//! changes of the mirror are applied to the expander beforehand, and register changes are stored
+//! to the mirror afterwards. So output changes in refresh mode are applied by the next locked
+//! operation at the latest, e.g. `update_bank()`.
+//!
+//! Every register bit is stored in a separate `AtomicBool`, so just atomic loads and stores are
+//! required. This also works on targets without atomic read-modify-write support, e.g. `thumbv6m`,
+//! and concurrent changes of different pins of the same bank are never lost.
+//!
+//! The mirror is owned by the guard rather than replacing the register images of
+//! [PCA9539] by atomics. The guard holds the only (mutable) reference to
+//! the expander, so pins can't read the expander without locking. Moving the whole register state
+//! into atomics would also turn all expander methods into atomic accesses, although most callers
+//! don't share the expander at all.
+//!
+//! ## Rejected changes
+//! Output changes of [locked pins](crate::lock) and changes while the [safe state](crate::safe_state)
+//! is active are ignored, the same as by the expander. [Interlocks](crate::interlock) are resolved
+//! when applying the changes to the expander. In all cases, the last rejection is reported by the
+//! next `update_bank()` or `update_all()` of a refresh mode output pin.
+//!
+//! ## Custom guards
+//! Implementations of [RefGuard](crate::guard::RefGuard) create the mirror by [AtomicCache::new()]
+//! and run all closures accessing the expander by [AtomicCache::access()].
+use crate::error::Error;
+use crate::expander::{Bank, PinID, PCA9539};
+use crate::register::Register;
+use core::sync::atomic::{AtomicBool, Ordering};
+use embedded_hal::i2c::{I2c, SevenBitAddress};
+
+/// Atomic mirror of the cached register state
+pub struct AtomicCache {
+    /// Register bits, indexed by command byte and pin
+    registers: [[AtomicBool; 8]; 8],
+
+    /// Locked pins ignoring output changes, indexed by bank
+    locked: [[AtomicBool; 8]; 2],
+
+    /// True if all output changes are ignored, as outputs are locked by the safe state
+    safe_state_locked: AtomicBool,
+
+    /// Locked pins, whose output change got ignored since the last access, indexed by bank
+    rejected: [[AtomicBool; 8]; 2],
+
+    /// True if an output change got ignored by the safe state lock since the last access
+    rejected_by_safe_state: AtomicBool,
+}
+
+impl AtomicCache {
+    /// Mirror of the cached state of the given expander
+    pub fn new<B: I2c<SevenBitAddress>>(expander: &PCA9539<B>) -> Self {
+        let cache = Self {
+            registers: Default::default(),
+            locked: Default::default(),
+            safe_state_locked: AtomicBool::new(false),
+            rejected: Default::default(),
+            rejected_by_safe_state: AtomicBool::new(false),
+        };
+
+        for register in Register::ALL {
+            cache.store(register, expander.cached_register(register), 0xFF);
+        }
+
+        cache.freeze(expander);
+        cache
+    }
+
+    /// Runs the given closure on the expander, which needs to be locked by the caller, and returns its result
+    /// Pending output changes of the mirror are applied to the expander beforehand, register changes
+    /// of the closure are stored to the mirror afterwards.
+    pub fn access<B, T, F>(&self, expander: &mut PCA9539<B>, f: F) -> T
+    where
+        B: I2c<SevenBitAddress>,
+        F: FnOnce(&mut PCA9539<B>) -> T,
+    {
+        self.defer_rejections(expander);
+
+        let output = expander.cached_banks(Register::output);
+        let mut pending = [0x0; 2];
+
+        for bank in [Bank::Bank0, Bank::Bank1] {
+            let register = Register::output(bank);
+            let mirror = self.load(register);
+            pending[bank as usize] = mirror ^ output[bank as usize];
+
+            for id in PinID::ALL {
+                if pending[bank as usize] & (1 << id as u8) != 0 {
+                    expander.set_state_deferred(bank, id, mirror & (1 << id as u8) != 0);
+                }
+            }
+        }
+
+        // Pending changes may be rejected or deactivate other pins by interlocks
+        let applied = expander.cached_banks(Register::output);
+        let corrected = [0, 1].map(|bank| pending[bank] | (output[bank] ^ applied[bank]));
+
+        let before = Register::ALL.map(|register| expander.cached_register(register));
+        let result = f(expander);
+
+        for register in Register::ALL {
+            let value = expander.cached_register(register);
+
+            // Output registers are also changed lock-free, so only bits changed by the closure are stored
+            let mask = match register.is_output() {
+                true => (value ^ before[register as usize]) | corrected[register.bank() as usize],
+                false => 0xFF,
+            };
+
+            self.store(register, value, mask);
+        }
+
+        self.freeze(expander);
+        result
+    }
+
+    /// Hands over changes ignored since the last access to the expander, reported by the next update
+    fn defer_rejections<B: I2c<SevenBitAddress>>(&self, expander: &mut PCA9539<B>) {
+        // Just loads and stores, a rejection racing with the reset is reported by the next access
+        if self.rejected_by_safe_state.load(Ordering::Relaxed) {
+            self.rejected_by_safe_state.store(false, Ordering::Relaxed);
+            expander.defer_error(Error::SafeStateLocked);
+        }
+
+        for bank in [Bank::Bank0, Bank::Bank1] {
+            for id in PinID::ALL {
+                let rejected = &self.rejected[bank as usize][id as usize];
+
+                if rejected.load(Ordering::Relaxed) {
+                    rejected.store(false, Ordering::Relaxed);
+                    expander.defer_error(Error::PinLocked(bank, id));
+                }
+            }
+        }
+    }
+
+    /// Returns the mirrored value of the given register
+    /// Bits are loaded individually, so concurrent changes may be partially included.
+    fn load(&self, register: Register) -> u8 {
+        self.registers[register as usize]
+            .iter()
+            .enumerate()
+            .fold(0x0, |value, (id, bit)| {
+                value | ((bit.load(Ordering::Relaxed) as u8) << id)
+            })
+    }
+
+    /// Returns the mirrored bit of the given pin and register
+    pub(crate) fn is_set(&self, register: Register, id: PinID) -> bool {
+        self.registers[register as usize][id as usize].load(Ordering::Relaxed)
+    }
+
+    /// Sets the mirrored output state, applied to the expander by the next locked access
+    /// Ignored if outputs are locked by the safe state or a locked pin would change. The rejection is
+    /// reported by the next update.
+    pub(crate) fn set_state(&self, bank: Bank, id: PinID, is_high: bool) {
+        let bit = &self.registers[Register::output(bank) as usize][id as usize];
+
+        if self.safe_state_locked.load(Ordering::Relaxed) {
+            self.rejected_by_safe_state.store(true, Ordering::Relaxed);
+            return;
+        }
+
+        if self.locked[bank as usize][id as usize].load(Ordering::Relaxed) {
+            if bit.load(Ordering::Relaxed) != is_high {
+                self.rejected[bank as usize][id as usize].store(true, Ordering::Relaxed);
+            }
+            return;
+        }
+
+        bit.store(is_high, Ordering::Relaxed);
+    }
+
+    /// Returns true if the actual level of the given pin is high, s. [PCA9539::is_pin_level_high()]
+    pub(crate) fn is_pin_level_high(&self, bank: Bank, id: PinID) -> bool {
+        self.is_set(Register::input(bank), id) != self.is_set(Register::polarity(bank), id)
+    }
+
+    /// Stores the bits of the given register value set in the given mask
+    fn store(&self, register: Register, value: u8, mask: u8) {
+        for (id, bit) in self.registers[register as usize].iter().enumerate() {
+            if mask & (1 << id) != 0 {
+                bit.store(value & (1 << id) != 0, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Stores which pins are ignoring output changes
+    fn freeze<B: I2c<SevenBitAddress>>(&self, expander: &PCA9539<B>) {
+        self.safe_state_locked.store(expander.is_safe_state_locked(), Ordering::Relaxed);
+
+        for bank in [Bank::Bank0, Bank::Bank1] {
+            let locked = expander.locked_pins(bank);
+
+            for (id, bit) in self.locked[bank as usize].iter().enumerate() {
+                bit.store(locked & (1 << id) != 0, Ordering::Relaxed);
+            }
+        }
+    }
+}
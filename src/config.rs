@@ -0,0 +1,357 @@
+//! # Board configuration
+//!
+//! Expanders may be described declaratively, e.g. in a board file. For each pin, a [PinConfig]
+//! defines mode, initial output level, polarity inversion, safe state and a label. Pins are grouped
+//! by [BankConfig] and [Config]. All types are const constructible.
+//!
+//! [PCA9539::with_config()] creates an expander and writes the derived register values in
+//! glitch-free order, using one auto-increment write per register pair:
+//! 1. Output registers: Pins switching to output mode are driving their initial level right away.
+//! 2. Polarity inversion registers
+//! 3. Configuration registers
+//!
+//! Once all registers are written, the [safe state](crate::safe_state) of the configuration is registered.
+//!
+//! ## Example
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::config::{BankConfig, Config, PinConfig};
+//! use pca9539::example::DummyI2CBus;
+//! use pca9539::expander::Bank::{Bank0, Bank1};
+//! use pca9539::expander::PCA9539;
+//! use pca9539::expander::PinID::{Pin0, Pin1, Pin7};
+//!
+//! const BOARD: Config = Config::new()
+//!     .bank(
+//!         Bank0,
+//!         BankConfig::new()
+//!             .pin(Pin0, PinConfig::output(false).safe_output(false).label("PYRO_ARM"))
+//!             .pin(Pin1, PinConfig::output(true).label("PWR_EN")),
+//!     )
+//!     .pin(Bank1, Pin7, PinConfig::input().inverted().label("LID_OPEN"));
+//!
+//! let expander = PCA9539::with_config(DummyI2CBus::default(), Address::default(), &BOARD).unwrap();
+//! assert!(expander.diff_config(&BOARD).is_empty());
+//! assert_eq!(Some((Bank1, Pin7)), BOARD.find("LID_OPEN"));
+//! ```
+//! ## Drift detection
+//! [PCA9539::diff_config()] compares the cached state against a configuration, e.g. for detecting
+//! pins changed at runtime. Output levels are only compared for pins configured as outputs.
+use crate::address::Address;
+use crate::error::Error;
+use crate::expander::{Bank, Mode, PinID, PCA9539};
+use crate::register::Register;
+use crate::safe_state::SafeState;
+use embedded_hal::i2c::{I2c, SevenBitAddress};
+
+/// Configuration of a single pin
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PinConfig {
+    mode: Mode,
+
+    /// Initial output level. Also set for input pins, as the output register is written anyway.
+    is_high: bool,
+
+    /// True if input polarity is inverted
+    inverted: bool,
+
+    /// Output level in safe state, None for input mode
+    safe_level: Option<bool>,
+
+    label: &'static str,
+}
+
+/// Configuration of all pins of a bank
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BankConfig {
+    pins: [PinConfig; 8],
+}
+
+/// Configuration of all pins of an expander
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    banks: [BankConfig; 2],
+}
+
+/// Pins differing from a configuration, as bitmasks indexed by bank
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ConfigDiff {
+    mode: [u8; 2],
+    output: [u8; 2],
+    polarity: [u8; 2],
+}
+
+impl PinConfig {
+    /// Input mode, which matches the power-on state of PCA9539
+    pub const fn input() -> Self {
+        Self {
+            mode: Mode::Input,
+            is_high: true,
+            inverted: false,
+            safe_level: None,
+            label: "",
+        }
+    }
+
+    /// Output mode with the given initial level
+    pub const fn output(is_high: bool) -> Self {
+        Self {
+            mode: Mode::Output,
+            is_high,
+            inverted: false,
+            safe_level: None,
+            label: "",
+        }
+    }
+
+    /// Inverts the input polarity
+    pub const fn inverted(mut self) -> Self {
+        self.inverted = true;
+        self
+    }
+
+    /// Drives the given level in safe state. By default, pins are switching to input mode.
+    pub const fn safe_output(mut self, is_high: bool) -> Self {
+        self.safe_level = Some(is_high);
+        self
+    }
+
+    /// Sets a label, e.g. the net name of the schematic
+    pub const fn label(mut self, label: &'static str) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// Returns the pin mode
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Returns the initial output level
+    pub fn is_high(&self) -> bool {
+        self.is_high
+    }
+
+    /// Returns true if the input polarity is inverted
+    pub fn is_inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// Returns the output level in safe state, None for input mode
+    pub fn safe_level(&self) -> Option<bool> {
+        self.safe_level
+    }
+
+    /// Returns the label, empty if not set
+    pub fn get_label(&self) -> &'static str {
+        self.label
+    }
+}
+
+impl BankConfig {
+    /// All pins in input mode
+    pub const fn new() -> Self {
+        Self {
+            pins: [PinConfig::input(); 8],
+        }
+    }
+
+    /// Sets the configuration of the given pin
+    pub const fn pin(mut self, id: PinID, config: PinConfig) -> Self {
+        self.pins[id as usize] = config;
+        self
+    }
+
+    /// Returns the configuration of the given pin
+    pub fn get_pin(&self, id: PinID) -> &PinConfig {
+        &self.pins[id as usize]
+    }
+
+    /// Returns the output register value
+    pub const fn output(&self) -> u8 {
+        let mut value = 0;
+        let mut index = 0;
+
+        while index < 8 {
+            value |= (self.pins[index].is_high as u8) << index;
+            index += 1;
+        }
+
+        value
+    }
+
+    /// Returns the polarity inversion register value
+    pub const fn polarity(&self) -> u8 {
+        let mut value = 0;
+        let mut index = 0;
+
+        while index < 8 {
+            value |= (self.pins[index].inverted as u8) << index;
+            index += 1;
+        }
+
+        value
+    }
+
+    /// Returns the configuration register value
+    pub const fn configuration(&self) -> u8 {
+        let mut value = 0;
+        let mut index = 0;
+
+        while index < 8 {
+            value |= (matches!(self.pins[index].mode, Mode::Input) as u8) << index;
+            index += 1;
+        }
+
+        value
+    }
+}
+
+impl Config {
+    /// All pins in input mode
+    pub const fn new() -> Self {
+        Self {
+            banks: [BankConfig::new(); 2],
+        }
+    }
+
+    /// Sets the configuration of the given bank
+    pub const fn bank(mut self, bank: Bank, config: BankConfig) -> Self {
+        self.banks[bank as usize] = config;
+        self
+    }
+
+    /// Sets the configuration of the given pin
+    pub const fn pin(mut self, bank: Bank, id: PinID, config: PinConfig) -> Self {
+        self.banks[bank as usize].pins[id as usize] = config;
+        self
+    }
+
+    /// Returns the configuration of the given bank
+    pub fn get_bank(&self, bank: Bank) -> &BankConfig {
+        &self.banks[bank as usize]
+    }
+
+    /// Returns the configuration of the given pin
+    pub fn get_pin(&self, bank: Bank, id: PinID) -> &PinConfig {
+        self.banks[bank as usize].get_pin(id)
+    }
+
+    /// Returns the first pin with the given label
+    pub fn find(&self, label: &str) -> Option<(Bank, PinID)> {
+        [Bank::Bank0, Bank::Bank1]
+            .into_iter()
+            .flat_map(|bank| PinID::ALL.into_iter().map(move |id| (bank, id)))
+            .find(|(bank, id)| self.get_pin(*bank, *id).label == label)
+    }
+
+    /// Returns the safe state of all pins
+    pub const fn safe_state(&self) -> SafeState {
+        let mut state = SafeState::new();
+        let mut index = 0;
+
+        while index < 16 {
+            let bank = if index < 8 { Bank::Bank0 } else { Bank::Bank1 };
+            let id = PinID::ALL[index % 8];
+
+            if let Some(is_high) = self.banks[bank as usize].pins[id as usize].safe_level {
+                state = state.output(bank, id, is_high);
+            }
+
+            index += 1;
+        }
+
+        state
+    }
+
+    /// Returns the register values of both banks, indexed by bank
+    pub(crate) fn registers(&self, value: fn(&BankConfig) -> u8) -> [u8; 2] {
+        [value(&self.banks[0]), value(&self.banks[1])]
+    }
+}
+
+impl Default for BankConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConfigDiff {
+    /// Returns true if the cached state matches the configuration
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns true if mode, output level or polarity of the given pin differs
+    pub fn is_pin_different(&self, bank: Bank, id: PinID) -> bool {
+        let mask = 1 << id as u8;
+        (self.mode[bank as usize] | self.output[bank as usize] | self.polarity[bank as usize]) & mask != 0
+    }
+
+    /// Returns the bitmask of pins with differing mode
+    pub fn mode(&self, bank: Bank) -> u8 {
+        self.mode[bank as usize]
+    }
+
+    /// Returns the bitmask of output pins with differing output level
+    pub fn output(&self, bank: Bank) -> u8 {
+        self.output[bank as usize]
+    }
+
+    /// Returns the bitmask of pins with differing polarity inversion
+    pub fn polarity(&self, bank: Bank) -> u8 {
+        self.polarity[bank as usize]
+    }
+}
+
+impl<B: I2c<SevenBitAddress>> PCA9539<B> {
+    /// Creates an expander and applies the given configuration, s. [apply_config()](Self::apply_config)
+    pub fn with_config(bus: B, address: Address, config: &Config) -> Result<Self, Error<B::Error>> {
+        let mut expander = Self::new(bus, address);
+        expander.apply_config(config)?;
+        Ok(expander)
+    }
+
+    /// Writes the register values of the given configuration in glitch-free order (output, polarity,
+    /// configuration) and registers its safe state
+    /// Fails before writing if outputs are locked by the safe state, a locked pin would change or an
+    /// interlock would be violated. On any error, the previously registered safe state is kept.
+    pub fn apply_config(&mut self, config: &Config) -> Result<(), Error<B::Error>> {
+        self.write_banks(
+            config.registers(BankConfig::output),
+            config.registers(BankConfig::polarity),
+            config.registers(BankConfig::configuration),
+            false,
+        )?;
+
+        self.set_safe_state(config.safe_state());
+        Ok(())
+    }
+
+    /// Compares the cached state against the given configuration
+    pub fn diff_config(&self, config: &Config) -> ConfigDiff {
+        let mut diff = ConfigDiff::default();
+
+        for bank in [Bank::Bank0, Bank::Bank1] {
+            let expected = config.get_bank(bank);
+            let outputs = !expected.configuration();
+            let index = bank as usize;
+
+            diff.mode[index] = self.cached_register(Register::configuration(bank)) ^ expected.configuration();
+            diff.output[index] = (self.cached_register(Register::output(bank)) ^ expected.output()) & outputs;
+            diff.polarity[index] = self.cached_register(Register::polarity(bank)) ^ expected.polarity();
+        }
+
+        diff
+    }
+}
@@ -0,0 +1,655 @@
+//! # Logic analyzer trace decoding
+//!
+//! Decodes I2C captures of logic analyzers into semantic PCA9539 operations, e.g.
+//! `0x74 write Bank1 configuration = 0b1111_0000 → P10-P13 output`.
+//!
+//! Decoding is done in two steps:
+//! 1. [CsvParser] parses the CSV export into bus level events ([Symbol])
+//! 2. [Decoder] reconstructs the register operations of all PCA9539 devices, tracking the register
+//!    state of each device over time. Protocol anomalies, like reads without preceding command
+//!    write, are reported as [Anomaly].
+//!
+//! The following CSV export formats are supported:
+//! * Saleae Logic 2 (columns `type`, `start_time`, `ack`, `address`, `read` and `data`)
+//! * Saleae Logic 1.x (columns `Time [s]`, `Packet ID`, `Address`, `Data`, `Read/Write` and `ACK/NAK`)
+//!
+//! Addresses above 0x7F are considered to be 8-bit addresses including the R/W bit.
+//!
+//! The `pca9539-decode` binary decodes CSV files on the command line:
+//! `cargo run --features decoder --bin pca9539-decode -- capture.csv`
+//!
+//! *Requires activation of `decoder` feature*
+//!
+//! ## Example
+//! ```
+//! use pca9539::decoder::{CsvParser, Decoder};
+//!
+//! let capture = "name,type,start_time,duration,ack,address,read,data
+//! I2C,start,0.001,0.0,,,,
+//! I2C,address,0.002,0.0,true,0x74,false,
+//! I2C,data,0.003,0.0,true,,,0x07
+//! I2C,data,0.004,0.0,true,,,0x0F
+//! I2C,stop,0.005,0.0,,,,";
+//!
+//! let mut lines = capture.lines();
+//! let mut parser = CsvParser::new(lines.next().unwrap()).unwrap();
+//! let mut decoder = Decoder::new();
+//!
+//! for line in lines {
+//!     parser.parse_line(line, &mut |event| {
+//!         decoder.feed(event, &mut |operation| {
+//!             assert_eq!(
+//!                 "0x74 write Bank1 configuration = 0b0000_1111 → P14-P17 output, P10-P13 input",
+//!                 operation.to_string()
+//!             );
+//!         });
+//!     }).unwrap();
+//! }
+//! ```
+use crate::address::Address;
+use crate::expander::Bank;
+use crate::register::Register;
+use core::fmt::{Display, Formatter};
+use heapless::Vec;
+
+/// Supported CSV export formats
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CsvFormat {
+    /// Saleae Logic 2, one row per bus symbol
+    Logic2,
+    /// Saleae Logic 1.x, one row per data byte grouped by packet ID
+    Logic1,
+}
+
+/// Bus level symbol
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Symbol {
+    /// START or repeated START condition
+    Start,
+    /// 7-bit address and R/W bit
+    Address { address: u8, read: bool, ack: bool },
+    /// Data byte
+    Data { value: u8, ack: bool },
+    /// STOP condition
+    Stop,
+}
+
+/// Bus level symbol with timestamp in seconds
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BusEvent {
+    pub time: f64,
+    pub symbol: Symbol,
+}
+
+/// Error while parsing CSV exports
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CsvError {
+    /// Header does not match any supported format
+    UnknownFormat,
+    /// Header lacks the given column
+    MissingColumn(&'static str),
+    /// Value of the given column is invalid
+    InvalidValue(&'static str),
+}
+
+/// More than four distinct addresses were given to [Decoder::with_addresses()]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TooManyAddresses;
+
+/// Parses CSV exports into bus events, one line at a time
+pub struct CsvParser {
+    format: CsvFormat,
+
+    /// Column indices of the fields, ordered as in the column names of the format
+    columns: [usize; 6],
+
+    /// Packet ID of the last Logic 1.x row
+    packet: Option<u64>,
+
+    /// Timestamp of the last row
+    time: f64,
+}
+
+/// Register state of a device as observed on the bus
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DeviceState {
+    /// Last written or read register values, None if not observed yet
+    registers: [Option<u8>; 8],
+
+    /// Register selected by the last command byte
+    pointer: Option<Register>,
+}
+
+/// Reconstructs PCA9539 operations from bus events
+pub struct Decoder {
+    /// Decoded devices with their state
+    devices: Vec<(Address, DeviceState), 4>,
+
+    transaction: Transaction,
+}
+
+/// Decoded PCA9539 operation
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Operation {
+    /// Timestamp in seconds
+    pub time: f64,
+    pub address: Address,
+    pub kind: OperationKind,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OperationKind {
+    /// Command write without data, selecting the register of subsequent reads
+    Select(Register),
+    /// Register write, including the previously known value
+    Write {
+        register: Register,
+        value: u8,
+        previous: Option<u8>,
+    },
+    /// Register read
+    Read { register: Register, value: u8 },
+    /// Protocol anomaly
+    Anomaly(Anomaly),
+}
+
+/// Protocol anomaly
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Anomaly {
+    /// Address was not acknowledged
+    AddressNack,
+    /// Data write was not acknowledged
+    DataNack(u8),
+    /// Command byte not addressing a register
+    InvalidCommand(u8),
+    /// Write to read-only input register
+    WriteToInputRegister(Register, u8),
+    /// Read without preceding command write, so the register is unknown
+    ReadWithoutCommand(u8),
+}
+
+/// State of the current transaction
+#[derive(Copy, Clone)]
+enum Transaction {
+    /// No address received since START
+    Idle,
+    /// Address is not decoded
+    Ignored,
+    /// Transaction to decoded device, with the number of transferred bytes
+    Active { device: usize, read: bool, bytes: usize },
+}
+
+/// Column names of Logic 2 format
+const LOGIC2_COLUMNS: [&str; 6] = ["type", "start_time", "ack", "address", "read", "data"];
+
+/// Column names of Logic 1.x format
+const LOGIC1_COLUMNS: [&str; 6] = ["Time [s]", "Packet ID", "Address", "Data", "Read/Write", "ACK/NAK"];
+
+impl CsvParser {
+    /// Detects the format based on the given header line
+    pub fn new(header: &str) -> Result<Self, CsvError> {
+        let (format, names) = if find_column(header, "start_time").is_some() {
+            (CsvFormat::Logic2, LOGIC2_COLUMNS)
+        } else if find_column(header, "Packet ID").is_some() {
+            (CsvFormat::Logic1, LOGIC1_COLUMNS)
+        } else {
+            return Err(CsvError::UnknownFormat);
+        };
+
+        let mut columns = [0; 6];
+        for (column, name) in columns.iter_mut().zip(names) {
+            *column = find_column(header, name).ok_or(CsvError::MissingColumn(name))?;
+        }
+
+        Ok(Self {
+            format,
+            columns,
+            packet: None,
+            time: 0.0,
+        })
+    }
+
+    /// Returns the detected format
+    pub fn format(&self) -> CsvFormat {
+        self.format
+    }
+
+    /// Parses the given data line and emits the contained bus events
+    pub fn parse_line(&mut self, line: &str, emit: &mut impl FnMut(BusEvent)) -> Result<(), CsvError> {
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+
+        match self.format {
+            CsvFormat::Logic2 => self.parse_logic2(line, emit),
+            CsvFormat::Logic1 => self.parse_logic1(line, emit),
+        }
+    }
+
+    /// Emits the STOP condition of a pending Logic 1.x packet. To be called after the last line.
+    pub fn finish(&mut self, emit: &mut impl FnMut(BusEvent)) {
+        if self.packet.take().is_some() {
+            emit(BusEvent {
+                time: self.time,
+                symbol: Symbol::Stop,
+            });
+        }
+    }
+
+    fn parse_logic2(&mut self, line: &str, emit: &mut impl FnMut(BusEvent)) -> Result<(), CsvError> {
+        let [kind, time, ack, address, read, data] = self.fields(line);
+        let time = parse_time(time, LOGIC2_COLUMNS[1])?;
+
+        let symbol = match kind {
+            "start" => Symbol::Start,
+            "stop" => Symbol::Stop,
+            "address" => Symbol::Address {
+                address: parse_address(address, LOGIC2_COLUMNS[3])?,
+                read: parse_flag(read, "true", "false", LOGIC2_COLUMNS[4])?,
+                ack: parse_flag(ack, "true", "false", LOGIC2_COLUMNS[2])?,
+            },
+            "data" => Symbol::Data {
+                value: parse_byte(data, LOGIC2_COLUMNS[5])?,
+                ack: parse_flag(ack, "true", "false", LOGIC2_COLUMNS[2])?,
+            },
+            // Other frame types are not relevant
+            _ => return Ok(()),
+        };
+
+        emit(BusEvent { time, symbol });
+        Ok(())
+    }
+
+    fn parse_logic1(&mut self, line: &str, emit: &mut impl FnMut(BusEvent)) -> Result<(), CsvError> {
+        let [time, packet, address, data, read, ack] = self.fields(line);
+        let time = parse_time(time, LOGIC1_COLUMNS[0])?;
+        self.time = time;
+        let packet = packet.parse().map_err(|_| CsvError::InvalidValue(LOGIC1_COLUMNS[1]))?;
+        let ack = parse_flag(ack, "ACK", "NAK", LOGIC1_COLUMNS[5])?;
+
+        if self.packet != Some(packet) {
+            if self.packet.is_some() {
+                emit(BusEvent {
+                    time,
+                    symbol: Symbol::Stop,
+                });
+            }
+
+            self.packet = Some(packet);
+
+            // Rows without data are address-only packets, so the acknowledge refers to the address
+            emit(BusEvent {
+                time,
+                symbol: Symbol::Start,
+            });
+            emit(BusEvent {
+                time,
+                symbol: Symbol::Address {
+                    address: parse_address(address, LOGIC1_COLUMNS[2])?,
+                    read: parse_flag(read, "Read", "Write", LOGIC1_COLUMNS[4])?,
+                    ack: ack || !data.is_empty(),
+                },
+            });
+        }
+
+        if !data.is_empty() {
+            emit(BusEvent {
+                time,
+                symbol: Symbol::Data {
+                    value: parse_byte(data, LOGIC1_COLUMNS[3])?,
+                    ack,
+                },
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the fields of the given line in column order, missing fields are empty
+    fn fields<'a>(&self, line: &'a str) -> [&'a str; 6] {
+        self.columns
+            .map(|column| line.split(',').nth(column).map(unquote).unwrap_or(""))
+    }
+}
+
+impl DeviceState {
+    /// Returns the last observed value of the given register
+    pub fn register(&self, register: Register) -> Option<u8> {
+        self.registers[register as usize]
+    }
+
+    /// Returns the register selected by the last command byte
+    pub fn pointer(&self) -> Option<Register> {
+        self.pointer
+    }
+}
+
+impl Decoder {
+    /// Decodes all four PCA9539 addresses
+    pub fn new() -> Self {
+        Self {
+            devices: Address::ALL.iter().map(|address| (*address, DeviceState::default())).collect(),
+            transaction: Transaction::Idle,
+        }
+    }
+
+    /// Decodes just the given addresses, duplicates are ignored
+    /// Fails if more than four distinct addresses are given.
+    pub fn with_addresses(addresses: &[Address]) -> Result<Self, TooManyAddresses> {
+        let mut devices: Vec<_, 4> = Vec::new();
+
+        for address in addresses {
+            if devices.iter().all(|(device, _)| device != address) {
+                devices.push((*address, DeviceState::default())).map_err(|_| TooManyAddresses)?;
+            }
+        }
+
+        Ok(Self {
+            devices,
+            transaction: Transaction::Idle,
+        })
+    }
+
+    /// Returns the observed register state of the given device
+    pub fn device(&self, address: Address) -> Option<&DeviceState> {
+        self.devices
+            .iter()
+            .find(|(device, _)| *device == address)
+            .map(|(_, state)| state)
+    }
+
+    /// Processes the given bus event and emits the decoded operations
+    pub fn feed(&mut self, event: BusEvent, emit: &mut impl FnMut(Operation)) {
+        match event.symbol {
+            Symbol::Start => self.transaction = Transaction::Idle,
+            Symbol::Address { address, read, ack } => {
+                let Some(device) = self.devices.iter().position(|(device, _)| device.value() == address) else {
+                    self.transaction = Transaction::Ignored;
+                    return;
+                };
+
+                if !ack {
+                    self.transaction = Transaction::Ignored;
+                    self.emit(event.time, device, OperationKind::Anomaly(Anomaly::AddressNack), emit);
+                    return;
+                }
+
+                self.transaction = Transaction::Active { device, read, bytes: 0 };
+            }
+            Symbol::Data { value, ack } => {
+                let Transaction::Active { device, read, bytes } = self.transaction else {
+                    return;
+                };
+
+                self.transaction = Transaction::Active {
+                    device,
+                    read,
+                    bytes: bytes + 1,
+                };
+
+                match read {
+                    true => self.decode_read(event.time, device, value, emit),
+                    false => self.decode_write(event.time, device, bytes, value, ack, emit),
+                }
+            }
+            Symbol::Stop => {
+                if let Transaction::Active {
+                    device,
+                    read: false,
+                    bytes: 1,
+                } = self.transaction
+                {
+                    if let Some(register) = self.devices[device].1.pointer {
+                        self.emit(event.time, device, OperationKind::Select(register), emit);
+                    }
+                }
+
+                self.transaction = Transaction::Idle;
+            }
+        }
+    }
+
+    fn decode_write(
+        &mut self,
+        time: f64,
+        device: usize,
+        index: usize,
+        value: u8,
+        ack: bool,
+        emit: &mut impl FnMut(Operation),
+    ) {
+        let state = &mut self.devices[device].1;
+
+        let kind = if index == 0 {
+            state.pointer = Register::try_from(value).ok();
+
+            match state.pointer {
+                Some(_) => None,
+                None => Some(OperationKind::Anomaly(Anomaly::InvalidCommand(value))),
+            }
+        } else if !ack {
+            Some(OperationKind::Anomaly(Anomaly::DataNack(value)))
+        } else {
+            match state.pointer {
+                None => None,
+                Some(register) if register.is_input() => {
+                    state.pointer = Some(register.pair());
+                    Some(OperationKind::Anomaly(Anomaly::WriteToInputRegister(register, value)))
+                }
+                Some(register) => {
+                    let previous = state.registers[register as usize].replace(value);
+                    state.pointer = Some(register.pair());
+
+                    Some(OperationKind::Write {
+                        register,
+                        value,
+                        previous,
+                    })
+                }
+            }
+        };
+
+        if let Some(kind) = kind {
+            self.emit(time, device, kind, emit);
+        }
+    }
+
+    fn decode_read(&mut self, time: f64, device: usize, value: u8, emit: &mut impl FnMut(Operation)) {
+        let state = &mut self.devices[device].1;
+
+        let kind = match state.pointer {
+            None => OperationKind::Anomaly(Anomaly::ReadWithoutCommand(value)),
+            Some(register) => {
+                state.registers[register as usize] = Some(value);
+                state.pointer = Some(register.pair());
+                OperationKind::Read { register, value }
+            }
+        };
+
+        self.emit(time, device, kind, emit);
+    }
+
+    fn emit(&self, time: f64, device: usize, kind: OperationKind, emit: &mut impl FnMut(Operation)) {
+        emit(Operation {
+            time,
+            address: self.devices[device].0,
+            kind,
+        });
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Operation {
+    /// Returns true if the operation is a protocol anomaly
+    pub fn is_anomaly(&self) -> bool {
+        matches!(self.kind, OperationKind::Anomaly(_))
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:#04x} ", self.address.value())?;
+
+        match self.kind {
+            OperationKind::Select(register) => write!(f, "select {}", register),
+            OperationKind::Read { register, value } => {
+                write!(f, "read {} = ", RegisterName(register))?;
+                fmt_binary(f, value)
+            }
+            OperationKind::Write {
+                register,
+                value,
+                previous,
+            } => {
+                write!(f, "write {} = ", RegisterName(register))?;
+                fmt_binary(f, value)?;
+
+                // Describing changed pins only, or all pins if the previous value is unknown
+                let changed = previous.map_or(0xFF, |previous| previous ^ value);
+                let (set, clear) = match register {
+                    Register::Output0 | Register::Output1 => ("high", "low"),
+                    Register::Polarity0 | Register::Polarity1 => ("inverted", "normal"),
+                    _ => ("input", "output"),
+                };
+
+                if changed == 0 {
+                    return f.write_str(" (unchanged)");
+                }
+
+                f.write_str(" →")?;
+                let bank = register.bank();
+                let mut separator = " ";
+
+                for (mask, label) in [(changed & !value, clear), (changed & value, set)] {
+                    if mask != 0 {
+                        f.write_str(separator)?;
+                        fmt_pins(f, bank, mask)?;
+                        write!(f, " {}", label)?;
+                        separator = ", ";
+                    }
+                }
+
+                Ok(())
+            }
+            OperationKind::Anomaly(anomaly) => write!(f, "ANOMALY: {}", anomaly),
+        }
+    }
+}
+
+impl Display for Anomaly {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Anomaly::AddressNack => f.write_str("address not acknowledged"),
+            Anomaly::DataNack(value) => write!(f, "data {:#04x} not acknowledged", value),
+            Anomaly::InvalidCommand(command) => write!(f, "invalid command {:#04x}", command),
+            Anomaly::WriteToInputRegister(register, value) => {
+                write!(f, "write of {:#04x} to read-only {}", value, register)
+            }
+            Anomaly::ReadWithoutCommand(value) => {
+                write!(f, "read of {:#04x} without preceding command write", value)
+            }
+        }
+    }
+}
+
+/// Register name in the form `Bank1 configuration`
+struct RegisterName(Register);
+
+impl Display for RegisterName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let kind = match self.0 {
+            Register::Input0 | Register::Input1 => "input",
+            Register::Output0 | Register::Output1 => "output",
+            Register::Polarity0 | Register::Polarity1 => "polarity",
+            Register::Configuration0 | Register::Configuration1 => "configuration",
+        };
+
+        write!(f, "Bank{} {}", self.0.bank() as u8, kind)
+    }
+}
+
+/// Formats the given value as binary with separated nibbles, e.g. 0b1111_0000
+fn fmt_binary(f: &mut Formatter<'_>, value: u8) -> core::fmt::Result {
+    write!(f, "0b{:04b}_{:04b}", value >> 4, value & 0x0F)
+}
+
+/// Formats the pins of the given mask as ranges, e.g. P10-P13, P15
+fn fmt_pins(f: &mut Formatter<'_>, bank: Bank, mask: u8) -> core::fmt::Result {
+    let bank = bank as u8;
+    let mut pin = 0;
+    let mut separator = "";
+
+    while pin < 8 {
+        if mask & (1 << pin) == 0 {
+            pin += 1;
+            continue;
+        }
+
+        let start = pin;
+        while pin < 7 && mask & (1 << (pin + 1)) != 0 {
+            pin += 1;
+        }
+
+        match start == pin {
+            true => write!(f, "{}P{}{}", separator, bank, start)?,
+            false => write!(f, "{}P{}{}-P{}{}", separator, bank, start, bank, pin)?,
+        }
+
+        separator = ", ";
+        pin += 1;
+    }
+
+    Ok(())
+}
+
+/// Returns the index of the given column within the header line
+fn find_column(header: &str, name: &str) -> Option<usize> {
+    header
+        .split(',')
+        .map(unquote)
+        .position(|column| column.eq_ignore_ascii_case(name))
+}
+
+/// Removes surrounding whitespace and quotes
+fn unquote(field: &str) -> &str {
+    field.trim().trim_matches('"')
+}
+
+fn parse_time(field: &str, column: &'static str) -> Result<f64, CsvError> {
+    field.parse().map_err(|_| CsvError::InvalidValue(column))
+}
+
+/// Parses a hex (0x..) or decimal byte
+fn parse_byte(field: &str, column: &'static str) -> Result<u8, CsvError> {
+    let result = match field.strip_prefix("0x").or_else(|| field.strip_prefix("0X")) {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => field.parse(),
+    };
+
+    result.map_err(|_| CsvError::InvalidValue(column))
+}
+
+/// Parses a 7-bit or 8-bit address, returning the 7-bit address
+fn parse_address(field: &str, column: &'static str) -> Result<u8, CsvError> {
+    let address = parse_byte(field, column)?;
+
+    match address > 0x7F {
+        true => Ok(address >> 1),
+        false => Ok(address),
+    }
+}
+
+fn parse_flag(field: &str, set: &str, clear: &str, column: &'static str) -> Result<bool, CsvError> {
+    if field.eq_ignore_ascii_case(set) {
+        Ok(true)
+    } else if field.eq_ignore_ascii_case(clear) {
+        Ok(false)
+    } else {
+        Err(CsvError::InvalidValue(column))
+    }
+}
@@ -0,0 +1,177 @@
+//! # Error handling
+//!
+//! All fallible operations of [PCA9539](crate::expander::PCA9539) and the individual pins return
+//! [Error], which carries the underlying bus error together with the operation and register involved.
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::error::{BusOperation, Error};
+//! use pca9539::expander::Bank::Bank0;
+//! use pca9539::expander::PCA9539;
+//! use pca9539::register::Register;
+//!# use embedded_hal::i2c::{ErrorKind, ErrorType, I2c, Operation, SevenBitAddress};
+//!#
+//!# struct FailingBus;
+//!#
+//!# impl ErrorType for FailingBus {
+//!#     type Error = ErrorKind;
+//!# }
+//!#
+//!# impl I2c<SevenBitAddress> for FailingBus {
+//!#     fn transaction(&mut self, _: u8, _: &mut [Operation<'_>]) -> Result<(), Self::Error> {
+//!#         Err(ErrorKind::Bus)
+//!#     }
+//!# }
+//!
+//! let mut expander = PCA9539::new(FailingBus, Address::default());
+//! let error = expander.refresh_input_state(Bank0).unwrap_err();
+//!
+//! assert_eq!(Some(Register::Input0), error.register());
+//! assert_eq!(Some(BusOperation::Write), error.operation());
+//! assert_eq!(Some(ErrorKind::Bus), error.bus_error_kind());
+//! assert_eq!("I2C write error on register Input0: Bus", format!("{}", error));
+//! ```
+use crate::expander::{Bank, PinID};
+use crate::interlock::Interlock;
+use crate::register::Register;
+use core::fmt::{Debug, Display, Formatter};
+use embedded_hal::digital;
+use embedded_hal::i2c;
+
+/// Bus operation during which an error occurred
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BusOperation {
+    Read,
+    Write,
+}
+
+/// Error of expander and pin operations
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// I2C bus error
+    Bus {
+        /// Error of the underlying bus
+        error: E,
+        /// Failed bus operation
+        operation: BusOperation,
+        /// Accessed register, None if multiple registers were accessed within one transaction
+        register: Option<Register>,
+    },
+    /// Output change rejected, as outputs are locked by the safe state, s. [safe_state module](crate::safe_state)
+    SafeStateLocked,
+    /// Output change rejected, as it violates the given interlock, s. [interlock module](crate::interlock)
+    Interlock(Interlock),
+    /// Change of mode, polarity or output state rejected, as the pin is locked, s. [lock module](crate::lock)
+    PinLocked(Bank, PinID),
+    /// Unlocking rejected, as locks are irreversible, s. [lock module](crate::lock)
+    LocksSealed,
+    /// No profile with the given name is registered, s. [profile module](crate::profile)
+    UnknownProfile,
+}
+
+impl<E> Error<E> {
+    /// Returns a closure mapping a bus error of a read operation
+    pub(crate) fn on_read(register: Option<Register>) -> impl FnOnce(E) -> Self {
+        move |error| Error::Bus {
+            error,
+            operation: BusOperation::Read,
+            register,
+        }
+    }
+
+    /// Returns a closure mapping a bus error of a write operation
+    pub(crate) fn on_write(register: Option<Register>) -> impl FnOnce(E) -> Self {
+        move |error| Error::Bus {
+            error,
+            operation: BusOperation::Write,
+            register,
+        }
+    }
+
+    /// Returns the underlying bus error
+    pub fn bus_error(&self) -> Option<&E> {
+        match self {
+            Error::Bus { error, .. } => Some(error),
+            Error::SafeStateLocked
+            | Error::Interlock(_)
+            | Error::PinLocked(..)
+            | Error::LocksSealed
+            | Error::UnknownProfile => None,
+        }
+    }
+
+    /// Returns the failed bus operation
+    pub fn operation(&self) -> Option<BusOperation> {
+        match self {
+            Error::Bus { operation, .. } => Some(*operation),
+            Error::SafeStateLocked
+            | Error::Interlock(_)
+            | Error::PinLocked(..)
+            | Error::LocksSealed
+            | Error::UnknownProfile => None,
+        }
+    }
+
+    /// Returns the accessed register
+    pub fn register(&self) -> Option<Register> {
+        match self {
+            Error::Bus { register, .. } => *register,
+            Error::SafeStateLocked
+            | Error::Interlock(_)
+            | Error::PinLocked(..)
+            | Error::LocksSealed
+            | Error::UnknownProfile => None,
+        }
+    }
+}
+
+impl<E: i2c::Error> Error<E> {
+    /// Returns the kind of the underlying bus error
+    pub fn bus_error_kind(&self) -> Option<i2c::ErrorKind> {
+        self.bus_error().map(|error| error.kind())
+    }
+}
+
+impl Display for BusOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            BusOperation::Read => f.write_str("read"),
+            BusOperation::Write => f.write_str("write"),
+        }
+    }
+}
+
+impl<E: Debug> Display for Error<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Bus {
+                error,
+                operation,
+                register,
+            } => {
+                write!(f, "I2C {} error", operation)?;
+
+                if let Some(register) = register {
+                    write!(f, " on register {}", register)?;
+                }
+
+                write!(f, ": {:?}", error)
+            }
+            Error::SafeStateLocked => f.write_str("Outputs are locked by safe state"),
+            Error::Interlock(interlock) => write!(f, "Output change violates interlock of {}", interlock),
+            Error::PinLocked(bank, id) => write!(f, "Pin{}{} is locked", *bank as u8, *id as u8),
+            Error::LocksSealed => f.write_str("Pin locks are irreversible"),
+            Error::UnknownProfile => f.write_str("Unknown profile"),
+        }
+    }
+}
+
+impl<E: Debug> core::error::Error for Error<E> {}
+
+impl<E: i2c::Error> digital::Error for Error<E> {
+    /// Digital errors are not further classified by embedded-hal, see [Error::bus_error_kind()]
+    fn kind(&self) -> digital::ErrorKind {
+        digital::ErrorKind::Other
+    }
+}
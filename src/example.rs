@@ -1,5 +1,7 @@
-//! Dummy I2C bus for examples
+//! Dummy I2C bus and pins for examples
 use core::convert::Infallible;
+use embedded_hal::digital;
+use embedded_hal::digital::InputPin;
 use embedded_hal::i2c::{ErrorType, I2c, Operation, SevenBitAddress};
 
 #[derive(Default)]
@@ -36,3 +38,28 @@ impl I2c<SevenBitAddress> for DummyI2CBus {
         Ok(())
     }
 }
+
+/// Dummy input pin for examples, which permanently reports the given level
+pub struct DummyInputPin {
+    is_high: bool,
+}
+
+impl DummyInputPin {
+    pub fn new(is_high: bool) -> Self {
+        Self { is_high }
+    }
+}
+
+impl digital::ErrorType for DummyInputPin {
+    type Error = Infallible;
+}
+
+impl InputPin for DummyInputPin {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.is_high)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.is_high)
+    }
+}
@@ -10,15 +10,18 @@
 //! [PCA9539] instance is created using a I2CBus implementing the I2C traits of
 //! [embedded-hal](https://docs.rs/embedded-hal/latest/embedded_hal/blocking/i2c/index.html).
 //!```
+//! use pca9539::address::Address;
+//! use pca9539::address::Strap::Low;
 //! use pca9539::example::DummyI2CBus;
 //! use pca9539::expander::PCA9539;
 //!
 //! let i2c_bus = DummyI2CBus::default();
-//! // Assuming I2C device address 0x74
-//! let expander = PCA9539::new(i2c_bus, 0x74);
+//! // A1 and A0 connected to GND => I2C device address 0x74
+//! let expander = PCA9539::new(i2c_bus, Address::new(Low, Low));
 //! ```
 //! ## Changing mode
 //! ```
+//!# use pca9539::address::Address;
 //!# use pca9539::example::DummyI2CBus;
 //!# use pca9539::expander::Bank::{Bank0, Bank1};
 //!# use pca9539::expander::Mode::{Input, Output};
@@ -26,7 +29,7 @@
 //!# use pca9539::expander::PinID::{Pin2, Pin4};
 //!#
 //!# let i2c_bus = DummyI2CBus::default();
-//!# let mut  expander = PCA9539::new(i2c_bus, 0x74);
+//!# let mut  expander = PCA9539::new(i2c_bus, Address::default());
 //!#
 //! // Switch Pin02 to input mode
 //! expander.set_mode(Bank0, Pin2, Input).unwrap();
@@ -36,13 +39,14 @@
 //! ```
 //! ## Reading input state
 //! ```
+//!# use pca9539::address::Address;
 //!# use pca9539::example::DummyI2CBus;
 //!# use pca9539::expander::Bank::Bank0;
 //!# use pca9539::expander::PCA9539;
 //!# use pca9539::expander::PinID::Pin1;
 //!#
 //!# let i2c_bus = DummyI2CBus::default();
-//!# let mut  expander = PCA9539::new(i2c_bus, 0x74);
+//!# let mut  expander = PCA9539::new(i2c_bus, Address::default());
 //!#
 //! expander.refresh_input_state(Bank0).unwrap();
 //! let is_high = expander.is_pin_input_high(Bank0, Pin1);
@@ -51,6 +55,7 @@
 //! ```
 //! ## Setting output state
 //! ```
+//!# use pca9539::address::Address;
 //!# use pca9539::example::DummyI2CBus;
 //!# use pca9539::expander::Bank::Bank0;
 //!# use pca9539::expander::Mode::Output;
@@ -58,11 +63,11 @@
 //!# use pca9539::expander::PinID::Pin1;
 //!#
 //!# let i2c_bus = DummyI2CBus::default();
-//!# let mut  expander = PCA9539::new(i2c_bus, 0x74);
+//!# let mut  expander = PCA9539::new(i2c_bus, Address::default());
 //!#
 //! expander.set_mode(Bank0, Pin1, Output);
 //!
-//! expander.set_state(Bank0, Pin1, true);
+//! expander.set_state(Bank0, Pin1, true).unwrap();
 //! expander.write_output_state(Bank0).unwrap();
 //!
 //! let is_high = expander.is_pin_output_high(Bank0, Pin1);
@@ -72,13 +77,14 @@
 //! PCA9539 has built-in hardware support for inverting input state. See [datasheet](<https://www.ti.com/lit/ds/symlink/pca9539.pdf?ts=1649342250975>)
 //! for more details.
 //! ```
+//!# use pca9539::address::Address;
 //!# use pca9539::example::DummyI2CBus;
 //!# use pca9539::expander::Bank::Bank0;
 //!# use pca9539::expander::PCA9539;
 //!# use pca9539::expander::PinID::{Pin1, Pin3};
 //!#
 //!# let i2c_bus = DummyI2CBus::default();
-//!# let mut  expander = PCA9539::new(i2c_bus, 0x74);
+//!# let mut  expander = PCA9539::new(i2c_bus, Address::default());
 //!#
 //! expander.reverse_polarity(Bank0, Pin3, true).unwrap();
 //! ```
@@ -86,43 +92,53 @@
 //! If needed, e.g. in case of IC reset, the complete internal state (polarity, mode, output state)
 //! may be resent.
 //! ```
+//!# use pca9539::address::Address;
 //!# use pca9539::example::DummyI2CBus;
 //!# use pca9539::expander::Bank::Bank0;
 //!# use pca9539::expander::PCA9539;
 //!# use pca9539::expander::PinID::{Pin1, Pin3};
 //!#
 //!# let i2c_bus = DummyI2CBus::default();
-//!# let mut  expander = PCA9539::new(i2c_bus, 0x74);
+//!# let mut  expander = PCA9539::new(i2c_bus, Address::default());
 //!#
 //! expander.sync_state().unwrap();
 //! ```
 
+use crate::address::Address;
+use crate::error::Error;
 #[cfg(feature = "cortex-m")]
 use crate::guard::CsMutexGuard;
 use crate::guard::LockFreeGuard;
 #[cfg(feature = "spin")]
 use crate::guard::SpinGuard;
+use crate::interlock;
+use crate::interlock::Interlock;
+use crate::link::LinkTracker;
+use crate::lock::PinLocks;
 use crate::pins::Pins;
+use crate::profile::Profiles;
+use crate::register::{Register, RegisterDump};
+use crate::safe_state::SafeState;
+#[cfg(feature = "stats")]
+use crate::stats::Statistics;
+use crate::trace;
 use bitmaps::Bitmap;
 use core::cell::RefCell;
-use core::fmt::{Debug, Formatter};
-#[cfg(feature = "cortex-m")]
-use cortex_m::interrupt::Mutex as CsMutex;
-use embedded_hal::digital::ErrorKind;
-use embedded_hal::i2c::{I2c, SevenBitAddress};
-use heapless::String;
+use embedded_hal::i2c::{I2c, Operation, SevenBitAddress};
 #[cfg(feature = "spin")]
 use spin::Mutex as SpinMutex;
 
 /// GPIO bank. PCA9539 has two with 7 pins each
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Bank {
     Bank0,
     Bank1,
 }
 
 /// GPIO pin ID. Builds together with bank an unique pin identification.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum PinID {
     Pin0 = 0,
     Pin1 = 1,
@@ -134,8 +150,23 @@ pub enum PinID {
     Pin7 = 7,
 }
 
+impl PinID {
+    /// All pins ordered by ID
+    pub(crate) const ALL: [PinID; 8] = [
+        PinID::Pin0,
+        PinID::Pin1,
+        PinID::Pin2,
+        PinID::Pin3,
+        PinID::Pin4,
+        PinID::Pin5,
+        PinID::Pin6,
+        PinID::Pin7,
+    ];
+}
+
 /// GPIO mode
-#[derive(PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Mode {
     Output,
     Input,
@@ -148,13 +179,8 @@ where
 {
     bus: B,
 
-    /// I2C slave address, dependents on A0 and A1 state.
-    /// A1 A0
-    ///  L  L => 0x74 (hexadecimal)
-    ///  L  H => 0x75 (hexadecimal)
-    ///  H  L => 0x76 (hexadecimal)
-    ///  H  H => 0x77 (hexadecimal)
-    address: u8,
+    /// I2C slave address, dependents on A0 and A1 state, s. [Address]
+    address: Address,
 
     /// First input register
     input_0: Bitmap<8>,
@@ -175,32 +201,45 @@ where
     configuration_0: Bitmap<8>,
     /// Second configuration register
     configuration_1: Bitmap<8>,
-}
 
-/// Wrapped I2C error when refreshing input state
-/// Reading input state consists of one write, followed by a read operation
-pub enum RefreshInputError<B: I2c<SevenBitAddress>> {
-    WriteError(B::Error),
-    ReadError(B::Error),
-}
+    /// Connection state, s. [link module](crate::link)
+    link: LinkTracker,
+
+    /// Safe state, s. [safe_state module](crate::safe_state)
+    safe_state: SafeState,
 
-const COMMAND_INPUT_0: u8 = 0x00;
-const COMMAND_INPUT_1: u8 = 0x01;
+    /// True if output changes are locked by the safe state
+    safe_state_locked: bool,
 
-const COMMAND_OUTPUT_0: u8 = 0x02;
-const COMMAND_OUTPUT_1: u8 = 0x03;
+    /// Interlock rules, s. [interlock module](crate::interlock)
+    interlocks: &'static [Interlock],
 
-const COMMAND_POLARITY_0: u8 = 0x04;
-const COMMAND_POLARITY_1: u8 = 0x05;
+    /// Output register values last written to the device
+    written_output: [u8; 2],
 
-const COMMAND_CONF_0: u8 = 0x06;
-const COMMAND_CONF_1: u8 = 0x07;
+    /// Rejection of the last change of a refresh mode pin, not reported yet
+    deferred_error: Option<Error<B::Error>>,
+
+    /// Locked pins, s. [lock module](crate::lock)
+    locks: PinLocks,
+
+    /// Configuration profiles, s. [profile module](crate::profile)
+    profiles: Profiles,
+
+    /// Bus statistics, s. [statistics module](crate::stats)
+    #[cfg(feature = "stats")]
+    statistics: Statistics,
+
+    /// Clock for timestamping the statistics
+    #[cfg(feature = "stats")]
+    clock: Option<fn() -> u64>,
+}
 
 impl<B> PCA9539<B>
 where
     B: I2c<SevenBitAddress>,
 {
-    pub fn new(bus: B, address: u8) -> Self {
+    pub fn new(bus: B, address: Address) -> Self {
         let mut expander = Self {
             bus,
             address,
@@ -212,6 +251,18 @@ where
             polarity_1: Bitmap::<8>::new(),
             configuration_0: Bitmap::<8>::new(),
             configuration_1: Bitmap::<8>::new(),
+            link: LinkTracker::new(),
+            safe_state: SafeState::new(),
+            safe_state_locked: false,
+            interlocks: &[],
+            written_output: [0xFF; 2],
+            deferred_error: None,
+            locks: PinLocks::new(),
+            profiles: Profiles::new(),
+            #[cfg(feature = "stats")]
+            statistics: Statistics::default(),
+            #[cfg(feature = "stats")]
+            clock: None,
         };
 
         expander.output_0.invert();
@@ -222,6 +273,107 @@ where
         expander
     }
 
+    /// Returns the I2C address of the device
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Returns the I2C bus
+    pub(crate) fn bus(&self) -> &B {
+        &self.bus
+    }
+
+    /// Returns the I2C bus
+    pub(crate) fn bus_mut(&mut self) -> &mut B {
+        &mut self.bus
+    }
+
+    /// Returns the pin locks
+    pub(crate) fn locks(&self) -> &PinLocks {
+        &self.locks
+    }
+
+    /// Returns the pin locks
+    pub(crate) fn locks_mut(&mut self) -> &mut PinLocks {
+        &mut self.locks
+    }
+
+    /// Returns the configuration profiles
+    pub(crate) fn profiles(&self) -> &Profiles {
+        &self.profiles
+    }
+
+    /// Returns the configuration profiles
+    pub(crate) fn profiles_mut(&mut self) -> &mut Profiles {
+        &mut self.profiles
+    }
+
+    /// Returns the link state tracker
+    pub(crate) fn link(&self) -> &LinkTracker {
+        &self.link
+    }
+
+    /// Returns the link state tracker
+    pub(crate) fn link_mut(&mut self) -> &mut LinkTracker {
+        &mut self.link
+    }
+
+    /// Registers the interlock rules, s. [interlock module](crate::interlock)
+    pub fn set_interlocks(&mut self, interlocks: &'static [Interlock]) {
+        self.interlocks = interlocks;
+    }
+
+    /// Registers the safe state, s. [safe_state module](crate::safe_state)
+    pub fn set_safe_state(&mut self, safe_state: SafeState) {
+        self.safe_state = safe_state;
+    }
+
+    /// Writes the safe state to the device and locks further output changes until released
+    /// Outputs are written before modes, each register pair within one transaction. The cached state
+    /// is updated and locked even if writing fails, so any later `sync_state()` writes the safe state.
+    pub fn enter_safe_state(&mut self) -> Result<(), Error<B::Error>> {
+        self.safe_state_locked = true;
+
+        for bank in [Bank::Bank0, Bank::Bank1] {
+            let current = self.cached_register(Register::output(bank));
+            let output = self.safe_state.output_register(bank, current);
+
+            self.update_cache(Register::output(bank), output);
+            self.update_cache(Register::configuration(bank), self.safe_state.configuration(bank));
+        }
+
+        self.write_cached_pair(Register::Output0)?;
+        self.write_cached_pair(Register::Configuration0)
+    }
+
+    /// Returns true if outputs are locked by the safe state
+    pub fn is_safe_state_locked(&self) -> bool {
+        self.safe_state_locked
+    }
+
+    /// Releases the lock of output changes. Outputs remain in safe state until changed.
+    pub fn release_safe_state(&mut self) {
+        self.safe_state_locked = false;
+    }
+
+    /// Returns a snapshot of the bus statistics
+    #[cfg(feature = "stats")]
+    pub fn statistics(&self) -> Statistics {
+        self.statistics.with_failure_streak(self.link.error_streak())
+    }
+
+    /// Resets all counters of the bus statistics
+    #[cfg(feature = "stats")]
+    pub fn reset_statistics(&mut self) {
+        self.statistics = Statistics::default();
+    }
+
+    /// Sets the clock used for timestamping the last successful access, s. [statistics module](crate::stats)
+    #[cfg(feature = "stats")]
+    pub fn set_clock(&mut self, clock: fn() -> u64) {
+        self.clock = Some(clock);
+    }
+
     /// Returns a pins container without using any locks
     /// This is the most efficient way of using individual pins
     /// The downside is, that these pins are neither Send or Sync, so can only be used in single-threaded
@@ -234,7 +386,7 @@ where
     /// Individual pins can be used across threads and interrupts, as long just running on a single core
     #[cfg(feature = "cortex-m")]
     pub fn pins_cs_mutex(&mut self) -> Pins<B, CsMutexGuard<'_, B>> {
-        Pins::new(CsMutexGuard::new(CsMutex::new(RefCell::new(self))))
+        Pins::new(CsMutexGuard::new(RefCell::new(self)))
     }
 
     /// Returns a pins container using a spin mutex
@@ -247,7 +399,17 @@ where
     }
 
     /// Switches the given pin to the input/output mode by adjusting the configuration register
-    pub fn set_mode(&mut self, bank: Bank, id: PinID, mode: Mode) -> Result<(), B::Error> {
+    pub fn set_mode(&mut self, bank: Bank, id: PinID, mode: Mode) -> Result<(), Error<B::Error>> {
+        self.check_safe_state_lock()?;
+
+        let mut configuration = self.cached_banks(Register::configuration);
+        match mode {
+            Mode::Output => configuration[bank as usize] &= !(1 << id as u8),
+            Mode::Input => configuration[bank as usize] |= 1 << id as u8,
+        };
+        self.check_pin_lock(Register::configuration(bank), configuration[bank as usize])?;
+        self.check_interlocks(&self.written_output, &configuration)?;
+
         match bank {
             Bank::Bank0 => self.configuration_0.set(id as usize, mode.into()),
             Bank::Bank1 => self.configuration_1.set(id as usize, mode.into()),
@@ -256,13 +418,22 @@ where
     }
 
     /// Switches all pins of the given bank to output/input mode1
-    pub fn set_mode_all(&mut self, bank: Bank, mode: Mode) -> Result<(), B::Error> {
+    /// Locked pins keep their mode.
+    pub fn set_mode_all(&mut self, bank: Bank, mode: Mode) -> Result<(), Error<B::Error>> {
+        self.check_safe_state_lock()?;
         let mut bitset = Bitmap::<8>::new();
 
         if mode == Mode::Input {
             bitset.invert();
         }
 
+        let register = Register::configuration(bank);
+        let bitset = Bitmap::from_value(self.retain_locked(register, *bitset.as_value()));
+
+        let mut configuration = self.cached_banks(Register::configuration);
+        configuration[bank as usize] = *bitset.as_value();
+        self.check_interlocks(&self.written_output, &configuration)?;
+
         match bank {
             Bank::Bank0 => self.configuration_0 = bitset,
             Bank::Bank1 => self.configuration_1 = bitset,
@@ -274,21 +445,63 @@ where
     /// Pin needs to be in OUTPUT mode for correct electrical state
     /// Note: This just updates the internal register, to make the changes effective,
     /// an additional call to `write_output_state()` is needed.
-    pub fn set_state(&mut self, bank: Bank, id: PinID, is_high: bool) {
-        match bank {
-            Bank::Bank0 => self.output_0.set(id as usize, is_high),
-            Bank::Bank1 => self.output_1.set(id as usize, is_high),
+    /// Fails if outputs are locked by the safe state, the pin is locked or the change violates an interlock,
+    /// s. [interlock module](crate::interlock). Interlocks may deactivate other pins, also of the other bank.
+    pub fn set_state(&mut self, bank: Bank, id: PinID, is_high: bool) -> Result<(), Error<B::Error>> {
+        self.check_safe_state_lock()?;
+
+        let mut output = self.cached_banks(Register::output);
+        match is_high {
+            true => output[bank as usize] |= 1 << id as u8,
+            false => output[bank as usize] &= !(1 << id as u8),
         };
+
+        let configuration = self.cached_banks(Register::configuration);
+        interlock::resolve(self.interlocks, &mut output, &configuration, bank, id).map_err(Error::Interlock)?;
+        self.check_pin_lock(Register::Output0, output[0])?;
+        self.check_pin_lock(Register::Output1, output[1])?;
+
+        self.update_cache(Register::Output0, output[0]);
+        self.update_cache(Register::Output1, output[1]);
+        Ok(())
+    }
+
+    /// Sets the cached output state of a refresh mode pin, s. [cache module](crate::cache)
+    /// Interlocks are resolved as by `set_state()`. The change is ignored if outputs are locked by the
+    /// safe state, a locked pin would change or an interlock rejects it. Rejections are reported by
+    /// the next `take_deferred_error()`.
+    pub(crate) fn set_state_deferred(&mut self, bank: Bank, id: PinID, is_high: bool) {
+        if let Err(error) = self.set_state(bank, id, is_high) {
+            self.defer_error(error);
+        }
+    }
+
+    /// Records the rejection of a refresh mode pin change, reported by the next `take_deferred_error()`
+    pub(crate) fn defer_error(&mut self, error: Error<B::Error>) {
+        self.deferred_error = Some(error);
+    }
+
+    /// Returns and resets the rejection of the last ignored change of a refresh mode pin
+    pub(crate) fn take_deferred_error(&mut self) -> Option<Error<B::Error>> {
+        self.deferred_error.take()
     }
 
     /// Sets output state for all pins of a bank
-    pub fn set_state_all(&mut self, bank: Bank, is_high: bool) -> Result<(), B::Error> {
+    /// Locked pins keep their output state.
+    pub fn set_state_all(&mut self, bank: Bank, is_high: bool) -> Result<(), Error<B::Error>> {
+        self.check_safe_state_lock()?;
         let mut bitset = Bitmap::<8>::new();
 
         if is_high {
             bitset.invert();
         }
 
+        let bitset = Bitmap::from_value(self.retain_locked(Register::output(bank), *bitset.as_value()));
+
+        let mut output = self.cached_banks(Register::output);
+        output[bank as usize] = *bitset.as_value();
+        self.check_interlocks(&output, &self.cached_banks(Register::configuration))?;
+
         match bank {
             Bank::Bank0 => self.output_0 = bitset,
             Bank::Bank1 => self.output_1 = bitset,
@@ -297,7 +510,15 @@ where
     }
 
     /// Reveres/Resets the input polarity of the given pin
-    pub fn reverse_polarity(&mut self, bank: Bank, id: PinID, reversed: bool) -> Result<(), B::Error> {
+    /// Fails if the pin is locked.
+    pub fn reverse_polarity(&mut self, bank: Bank, id: PinID, reversed: bool) -> Result<(), Error<B::Error>> {
+        let register = Register::polarity(bank);
+        let value = match reversed {
+            true => self.cached_register(register) | 1 << id as u8,
+            false => self.cached_register(register) & !(1 << id as u8),
+        };
+        self.check_pin_lock(register, value)?;
+
         match bank {
             Bank::Bank0 => self.polarity_0.set(id as usize, reversed),
             Bank::Bank1 => self.polarity_1.set(id as usize, reversed),
@@ -306,12 +527,8 @@ where
     }
 
     /// Refreshes the input state of the given bank
-    pub fn refresh_input_state(&mut self, bank: Bank) -> Result<(), RefreshInputError<B>> {
-        match bank {
-            Bank::Bank0 => self.input_0 = Bitmap::from_value(self.read_input_register(COMMAND_INPUT_0)?),
-            Bank::Bank1 => self.input_1 = Bitmap::from_value(self.read_input_register(COMMAND_INPUT_1)?),
-        };
-
+    pub fn refresh_input_state(&mut self, bank: Bank) -> Result<(), Error<B::Error>> {
+        self.read_register(Register::input(bank), true)?;
         Ok(())
     }
 
@@ -334,90 +551,421 @@ where
         }
     }
 
+    /// Returns true if the actual level of the given pin is high
+    /// In contrast to `is_pin_input_high()`, polarity inversion is taken into account, so this
+    /// reflects the physical pin level, also for pins in output mode. This method is using the cached
+    /// register, for a updated result `refresh_input_state()` needs to be called beforehand.
+    pub fn is_pin_level_high(&self, bank: Bank, id: PinID) -> bool {
+        let mask = 1 << id as u8;
+        let input = self.cached_register(Register::input(bank)) & mask != 0;
+        let inverted = self.cached_register(Register::polarity(bank)) & mask != 0;
+
+        input != inverted
+    }
+
+    /// Returns the output register values last written to the device, indexed by bank
+    pub(crate) fn written_output(&self) -> [u8; 2] {
+        self.written_output
+    }
+
+    /// Returns the cached value of the given register
+    pub fn cached_register(&self, register: Register) -> u8 {
+        let bitmap = match register {
+            Register::Input0 => &self.input_0,
+            Register::Input1 => &self.input_1,
+            Register::Output0 => &self.output_0,
+            Register::Output1 => &self.output_1,
+            Register::Polarity0 => &self.polarity_0,
+            Register::Polarity1 => &self.polarity_1,
+            Register::Configuration0 => &self.configuration_0,
+            Register::Configuration1 => &self.configuration_1,
+        };
+
+        *bitmap.as_value()
+    }
+
+    /// Reads the given register
+    /// If `update_cache` is true, the cached register state is updated with the read value.
+    pub fn read_register(&mut self, register: Register, update_cache: bool) -> Result<u8, Error<B::Error>> {
+        let mut buffer = [0x0; 1];
+        self.read(register, &mut buffer)?;
+
+        if update_cache {
+            self.update_cache(register, buffer[0]);
+        }
+
+        Ok(buffer[0])
+    }
+
+    /// Reads the given register and afterwards the other register of the same pair (auto-increment)
+    /// Values are returned in read order, so the value of the given register comes first.
+    /// If `update_cache` is true, the cached state of both registers is updated.
+    pub fn read_register_pair(
+        &mut self,
+        register: Register,
+        update_cache: bool,
+    ) -> Result<[u8; 2], Error<B::Error>> {
+        let mut buffer = [0x0; 2];
+        self.read(register, &mut buffer)?;
+
+        if update_cache {
+            self.update_cache(register, buffer[0]);
+            self.update_cache(register.pair(), buffer[1]);
+        }
+
+        Ok(buffer)
+    }
+
+    /// Writes the given register
+    /// If `update_cache` is true, the cached register state is updated as well. Input registers are
+    /// read-only, so writes to them are ignored by the device and never update the cache.
+    /// Writes to output and configuration registers fail if outputs are locked by the safe state or
+    /// the resulting device state violates an interlock.
+    /// Fails if the value differs from the cached state for any locked pin.
+    pub fn write_register(
+        &mut self,
+        register: Register,
+        value: u8,
+        update_cache: bool,
+    ) -> Result<(), Error<B::Error>> {
+        if register.is_output() || register.is_configuration() {
+            self.check_safe_state_lock()?;
+        }
+
+        if !register.is_input() {
+            self.check_pin_lock(register, value)?;
+        }
+
+        if register.is_output() || register.is_configuration() {
+            let mut output = self.written_output;
+            let mut configuration = self.cached_banks(Register::configuration);
+
+            match register.is_output() {
+                true => output[register.bank() as usize] = value,
+                false => configuration[register.bank() as usize] = value,
+            };
+            self.check_interlocks(&output, &configuration)?;
+        }
+
+        self.bus
+            .write(self.address.value(), &[register.command(), value])
+            .map_err(Error::on_write(Some(register)))
+            .inspect_err(|error| self.on_error(error))?;
+        self.on_write(register, &[value]);
+
+        if update_cache && !register.is_input() {
+            self.update_cache(register, value);
+        }
+
+        Ok(())
+    }
+
+    /// Reads all eight registers within one I2C transaction
+    /// As auto-increment just toggles between the two registers of a pair, each pair is read by a
+    /// separate command write followed by a two byte read, joined by repeated start conditions.
+    /// If `update_cache` is true, the cached state of all registers is updated.
+    pub fn dump_registers(&mut self, update_cache: bool) -> Result<RegisterDump, Error<B::Error>> {
+        let mut values = [0x0; 8];
+        let (input, rest) = values.split_at_mut(2);
+        let (output, rest) = rest.split_at_mut(2);
+        let (polarity, configuration) = rest.split_at_mut(2);
+
+        self.bus
+            .transaction(
+                self.address.value(),
+                &mut [
+                    Operation::Write(&[Register::Input0.command()]),
+                    Operation::Read(input),
+                    Operation::Write(&[Register::Output0.command()]),
+                    Operation::Read(output),
+                    Operation::Write(&[Register::Polarity0.command()]),
+                    Operation::Read(polarity),
+                    Operation::Write(&[Register::Configuration0.command()]),
+                    Operation::Read(configuration),
+                ],
+            )
+            .map_err(Error::on_read(None))
+            .inspect_err(|error| self.on_error(error))?;
+
+        for register in [
+            Register::Input0,
+            Register::Output0,
+            Register::Polarity0,
+            Register::Configuration0,
+        ] {
+            let index = register as usize;
+            self.on_read(register, &values[index..index + 2]);
+        }
+
+        if update_cache {
+            for register in Register::ALL {
+                self.update_cache(register, values[register as usize]);
+            }
+        }
+
+        Ok(RegisterDump::new(values))
+    }
+
     /// (Re)writes the internal state (mode, polarity, output state) to the configuration registers.
     /// May be useful after power resenting the expander IC to ensure the software matches the
     /// hardware state.
-    pub fn sync_state(&mut self) -> Result<(), B::Error> {
+    /// Also writes the cached safe state while outputs are locked, e.g. if entering the safe state failed.
+    pub fn sync_state(&mut self) -> Result<(), Error<B::Error>> {
         self.write_polarity(Bank::Bank0)?;
         self.write_polarity(Bank::Bank1)?;
 
-        self.write_output_state(Bank::Bank0)?;
-        self.write_output_state(Bank::Bank1)?;
+        // Rewriting the cached safe state is no state change, so it is not rejected by the lock
+        if self.safe_state_locked {
+            self.write_cached_pair(Register::Output0)?;
+        } else {
+            self.write_output_state(Bank::Bank0)?;
+            self.write_output_state(Bank::Bank1)?;
+        }
 
         self.write_conf(Bank::Bank0)?;
         self.write_conf(Bank::Bank1)
     }
 
-    /// Reads and returns the given input register
-    fn read_input_register(&mut self, command: u8) -> Result<u8, RefreshInputError<B>> {
+    /// Sends the command byte of the given register and reads the given number of bytes
+    fn read(&mut self, register: Register, buffer: &mut [u8]) -> Result<(), Error<B::Error>> {
         self.bus
-            .write(self.address, &[command])
-            .map_err(RefreshInputError::WriteError)?;
+            .write(self.address.value(), &[register.command()])
+            .map_err(Error::on_write(Some(register)))
+            .inspect_err(|error| self.on_error(error))?;
 
-        let mut buffer: [u8; 1] = [0x0; 1];
-        self.bus.read(self.address, &mut buffer).map_err(RefreshInputError::ReadError)?;
+        self.bus
+            .read(self.address.value(), buffer)
+            .map_err(Error::on_read(Some(register)))
+            .inspect_err(|error| self.on_error(error))?;
+        self.on_read(register, buffer);
 
-        Ok(buffer[0])
+        Ok(())
     }
 
-    /// Writes the configuration register of the given bank
-    fn write_conf(&mut self, bank: Bank) -> Result<(), B::Error> {
-        match bank {
-            Bank::Bank0 => self
-                .bus
-                .write(self.address, &[COMMAND_CONF_0, *self.configuration_0.as_value()]),
-            Bank::Bank1 => self
-                .bus
-                .write(self.address, &[COMMAND_CONF_1, *self.configuration_1.as_value()]),
+    /// Writes the cached state of the given register
+    fn write_cached(&mut self, register: Register) -> Result<(), Error<B::Error>> {
+        let value = self.cached_register(register);
+
+        self.bus
+            .write(self.address.value(), &[register.command(), value])
+            .map_err(Error::on_write(Some(register)))
+            .inspect_err(|error| self.on_error(error))?;
+        self.on_write(register, &[value]);
+
+        Ok(())
+    }
+
+    /// Updates the cache with the given register values, indexed by bank, and writes them to the
+    /// device in glitch-free order (output, polarity, configuration) using auto-increment writes
+    /// If `minimal` is true, only registers differing from the cache are written. On a bus error, the
+    /// cache keeps the previous values of the failed and all following registers.
+    /// Fails before writing if outputs are locked by the safe state, a locked pin would change or an
+    /// interlock would be violated, also by the intermediate state after writing the output registers.
+    pub(crate) fn write_banks(
+        &mut self,
+        output: [u8; 2],
+        polarity: [u8; 2],
+        configuration: [u8; 2],
+        minimal: bool,
+    ) -> Result<(), Error<B::Error>> {
+        self.check_safe_state_lock()?;
+
+        for bank in [Bank::Bank0, Bank::Bank1] {
+            self.check_pin_lock(Register::output(bank), output[bank as usize])?;
+            self.check_pin_lock(Register::polarity(bank), polarity[bank as usize])?;
+            self.check_pin_lock(Register::configuration(bank), configuration[bank as usize])?;
         }
+
+        self.check_interlocks(&output, &self.cached_banks(Register::configuration))?;
+        self.check_interlocks(&output, &configuration)?;
+
+        for (register, values) in [
+            (Register::Output0, output),
+            (Register::Polarity0, polarity),
+            (Register::Configuration0, configuration),
+        ] {
+            let previous = [self.cached_register(register), self.cached_register(register.pair())];
+            let changed = [values[0] != previous[0], values[1] != previous[1]];
+
+            self.update_cache(register, values[0]);
+            self.update_cache(register.pair(), values[1]);
+
+            let result = match (minimal, changed) {
+                (true, [false, false]) => Ok(()),
+                (true, [true, false]) => self.write_cached(register),
+                (true, [false, true]) => self.write_cached(register.pair()),
+                _ => self.write_cached_pair(register),
+            };
+
+            // Cache reflects the device state, unless the error triggered the safe state
+            if result.is_err() && !self.safe_state_locked {
+                self.update_cache(register, previous[0]);
+                self.update_cache(register.pair(), previous[1]);
+            }
+
+            result?;
+        }
+
+        Ok(())
     }
 
-    /// Writes the output register of the given bank
-    pub fn write_output_state(&mut self, bank: Bank) -> Result<(), B::Error> {
-        match bank {
-            Bank::Bank0 => self.bus.write(self.address, &[COMMAND_OUTPUT_0, *self.output_0.as_value()]),
-            Bank::Bank1 => self.bus.write(self.address, &[COMMAND_OUTPUT_1, *self.output_1.as_value()]),
+    /// Writes the cached state of the given register and afterwards the other register of the same
+    /// pair within one transaction (auto-increment)
+    fn write_cached_pair(&mut self, register: Register) -> Result<(), Error<B::Error>> {
+        let values = [self.cached_register(register), self.cached_register(register.pair())];
+
+        self.bus
+            .write(self.address.value(), &[register.command(), values[0], values[1]])
+            .map_err(Error::on_write(None))
+            .inspect_err(|error| self.on_error(error))?;
+        self.on_write(register, &values);
+
+        Ok(())
+    }
+
+    /// Returns the cached values of the given register of both banks
+    pub(crate) fn cached_banks(&self, register: fn(Bank) -> Register) -> [u8; 2] {
+        [
+            self.cached_register(register(Bank::Bank0)),
+            self.cached_register(register(Bank::Bank1)),
+        ]
+    }
+
+    /// Fails if the given value of the given register changes the state of a locked pin
+    fn check_pin_lock(&self, register: Register, value: u8) -> Result<(), Error<B::Error>> {
+        self.locks.check(register.bank(), self.cached_register(register), value)
+    }
+
+    /// Returns the given value of the given register with locked pins keeping their cached state
+    fn retain_locked(&self, register: Register, value: u8) -> u8 {
+        self.locks.retain(register.bank(), self.cached_register(register), value)
+    }
+
+    /// Fails if the given register values violate an interlock
+    fn check_interlocks(&self, output: &[u8; 2], configuration: &[u8; 2]) -> Result<(), Error<B::Error>> {
+        interlock::check(self.interlocks, output, configuration).map_err(Error::Interlock)
+    }
+
+    /// Fails if outputs are locked by the safe state
+    fn check_safe_state_lock(&self) -> Result<(), Error<B::Error>> {
+        match self.safe_state_locked {
+            true => Err(Error::SafeStateLocked),
+            false => Ok(()),
         }
     }
 
-    /// Writes the polarity register of the given bank
-    fn write_polarity(&mut self, bank: Bank) -> Result<(), B::Error> {
-        match bank {
-            Bank::Bank0 => self.bus.write(self.address, &[COMMAND_POLARITY_0, *self.polarity_0.as_value()]),
-            Bank::Bank1 => self.bus.write(self.address, &[COMMAND_POLARITY_1, *self.polarity_1.as_value()]),
+    /// Traces, tracks and counts a successful read of the given values, starting at the given register
+    fn on_read(&mut self, register: Register, values: &[u8]) {
+        trace::read(self.address, register, values);
+        self.link.record_success(self.address);
+
+        let mut current = register;
+        for value in values {
+            if current.is_output() {
+                self.written_output[current.bank() as usize] = *value;
+            }
+
+            current = current.pair();
         }
+
+        #[cfg(feature = "stats")]
+        self.statistics.record_read(register, values, self.clock.map(|clock| clock()));
     }
-}
 
-impl From<Mode> for bool {
-    fn from(mode: Mode) -> Self {
-        match mode {
-            Mode::Output => false,
-            Mode::Input => true,
+    /// Traces, tracks and counts a successful write of the given values, starting at the given register
+    fn on_write(&mut self, register: Register, values: &[u8]) {
+        let mut current = register;
+
+        for value in values {
+            trace::write(self.address, current, *value);
+
+            if current.is_output() {
+                self.written_output[current.bank() as usize] = *value;
+            }
+
+            current = current.pair();
         }
+
+        self.link.record_success(self.address);
+
+        #[cfg(feature = "stats")]
+        self.statistics.record_write(register, values, self.clock.map(|clock| clock()));
     }
-}
 
-impl<B: I2c> Debug for RefreshInputError<B> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
-        match self {
-            RefreshInputError::WriteError(_) => f.write_str("RefreshInputError::WriteError"),
-            RefreshInputError::ReadError(_) => f.write_str("RefreshInputError::ReadError"),
+    /// Traces, tracks and counts a failed bus operation. Enters the safe state, if triggered by the error streak.
+    fn on_error(&mut self, error: &Error<B::Error>) {
+        trace::error(self.address, error);
+        self.link.record_error(self.address);
+
+        #[cfg(feature = "stats")]
+        self.statistics.record_error(error, self.link.error_streak());
+
+        if !self.safe_state_locked && self.safe_state.is_triggered(self.link.error_streak()) {
+            // Error is already reported by the failed operation. Outputs are locked anyway.
+            let _ = self.enter_safe_state();
         }
     }
-}
 
-impl<B: I2c> RefreshInputError<B> {
-    pub fn to_string(&self) -> String<10> {
-        match self {
-            RefreshInputError::WriteError(_) => String::try_from("WriteError").unwrap(),
-            RefreshInputError::ReadError(_) => String::try_from("ReadError").unwrap(),
+    /// Updates the cached state of the given register
+    pub(crate) fn update_cache(&mut self, register: Register, value: u8) {
+        let bitmap = Bitmap::from_value(value);
+
+        match register {
+            Register::Input0 => self.input_0 = bitmap,
+            Register::Input1 => self.input_1 = bitmap,
+            Register::Output0 => self.output_0 = bitmap,
+            Register::Output1 => self.output_1 = bitmap,
+            Register::Polarity0 => self.polarity_0 = bitmap,
+            Register::Polarity1 => self.polarity_1 = bitmap,
+            Register::Configuration0 => self.configuration_0 = bitmap,
+            Register::Configuration1 => self.configuration_1 = bitmap,
+        }
+    }
+
+    /// Writes the configuration register of the given bank
+    fn write_conf(&mut self, bank: Bank) -> Result<(), Error<B::Error>> {
+        self.write_cached(Register::configuration(bank))
+    }
+
+    /// Writes the output register of the given bank
+    /// Fails if outputs are locked by the safe state or the resulting device state violates an interlock.
+    /// If needed for avoiding a violation, the cached state of the other bank is written first.
+    pub fn write_output_state(&mut self, bank: Bank) -> Result<(), Error<B::Error>> {
+        self.check_safe_state_lock()?;
+
+        let configuration = self.cached_banks(Register::configuration);
+        let cached = self.cached_banks(Register::output);
+        let mut written = self.written_output;
+        written[bank as usize] = cached[bank as usize];
+
+        if interlock::check(self.interlocks, &written, &configuration).is_err() {
+            let other = match bank {
+                Bank::Bank0 => Bank::Bank1,
+                Bank::Bank1 => Bank::Bank0,
+            };
+
+            let mut intermediate = self.written_output;
+            intermediate[other as usize] = cached[other as usize];
+            self.check_interlocks(&intermediate, &configuration)?;
+            self.check_interlocks(&cached, &configuration)?;
+
+            self.write_cached(Register::output(other))?;
         }
+
+        self.write_cached(Register::output(bank))
+    }
+
+    /// Writes the polarity register of the given bank
+    fn write_polarity(&mut self, bank: Bank) -> Result<(), Error<B::Error>> {
+        self.write_cached(Register::polarity(bank))
     }
 }
 
-impl<B: I2c> embedded_hal::digital::Error for RefreshInputError<B> {
-    fn kind(&self) -> ErrorKind {
-        ErrorKind::Other
+impl From<Mode> for bool {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Output => false,
+            Mode::Input => true,
+        }
     }
 }
@@ -0,0 +1,248 @@
+//! # Fault injection
+//!
+//! [FaultyBus] wraps any I2C bus and injects faults for testing error paths of the expander, pins
+//! and application code. The following faults are supported:
+//!
+//! | Fault                    | Effect                                     | Error kind        |
+//! |--------------------------|--------------------------------------------|-------------------|
+//! | [Fault::Nack]            | Transaction fails, bus is not accessed     | `NoAcknowledge`   |
+//! | [Fault::ArbitrationLoss] | Transaction fails, bus is not accessed     | `ArbitrationLoss` |
+//! | [Fault::Timeout]         | Transaction fails, bus is not accessed     | `Other`           |
+//! | [Fault::Bus]             | Transaction fails, bus is not accessed     | `Bus`             |
+//! | [Fault::BitFlip]         | Transaction succeeds, read data is altered | -                 |
+//!
+//! Faults are selected by a [FaultPolicy] per transaction. Each call of the I2C trait methods
+//! (e.g. `write()` or `read()`) counts as one transaction, starting at zero.
+//!
+//! *Requires activation of `fault-injection` feature*
+//!
+//! ## Scheduled faults
+//! Faults are injected at fixed transaction indices using [ScheduledFaults]. Alternatively, any
+//! closure `FnMut(usize) -> Option<Fault>` may be used as policy.
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::example::DummyI2CBus;
+//! use pca9539::expander::Bank::Bank0;
+//! use pca9539::expander::PCA9539;
+//! use pca9539::fault::{Fault, FaultyBus, ScheduledFaults};
+//!
+//! // Second transaction (read of input register) is failing
+//! const FAULTS: [(usize, Fault); 1] = [(1, Fault::Timeout)];
+//!
+//! let i2c_bus = FaultyBus::new(DummyI2CBus::default(), ScheduledFaults::new(&FAULTS));
+//! let mut expander = PCA9539::new(i2c_bus, Address::default());
+//!
+//! assert!(expander.refresh_input_state(Bank0).is_err());
+//! assert!(expander.refresh_input_state(Bank0).is_ok());
+//! ```
+//! ## Random faults
+//! [RandomFaults] injects faults with a fixed probability, based on a seeded pseudo random number
+//! generator. So a failing test run is reproducible using the same seed.
+//! ```
+//! use pca9539::example::DummyI2CBus;
+//! use pca9539::fault::{Fault, FaultyBus, RandomFaults};
+//! use embedded_hal::i2c::NoAcknowledgeSource;
+//!
+//! const FAULTS: [Fault; 2] = [Fault::Nack(NoAcknowledgeSource::Data), Fault::BitFlip(0b0000_0001)];
+//!
+//! // Injecting a fault in one of ten transactions on average
+//! let i2c_bus = FaultyBus::new(DummyI2CBus::default(), RandomFaults::new(0x1234_5678, 10, &FAULTS));
+//! ```
+use embedded_hal::i2c::{Error, ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation, SevenBitAddress};
+
+/// Fault injected into a transaction
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Fault {
+    /// Address or data byte not acknowledged
+    Nack(NoAcknowledgeSource),
+    /// Bus arbitration lost to another controller
+    ArbitrationLoss,
+    /// Bus timeout, e.g. due to clock stretching or a stuck SDA line
+    Timeout,
+    /// Misplaced START or STOP condition
+    Bus,
+    /// Transaction succeeds, but all read bytes are XORed with the given mask
+    BitFlip(u8),
+}
+
+/// Decides which fault is injected into a transaction
+pub trait FaultPolicy {
+    /// Returns the fault to inject into the transaction with the given index
+    fn fault(&mut self, transaction: usize) -> Option<Fault>;
+}
+
+/// I2C bus wrapper injecting faults based on the given policy
+pub struct FaultyBus<B: I2c<SevenBitAddress>, P: FaultPolicy> {
+    bus: B,
+    policy: P,
+
+    /// Number of transactions started
+    transactions: usize,
+
+    /// Number of injected faults
+    injected: usize,
+}
+
+/// Error of a faulty bus transaction
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FaultError<E> {
+    /// Injected fault
+    Injected(Fault),
+    /// Error of the wrapped bus
+    Bus(E),
+}
+
+/// Injects faults at fixed transaction indices
+pub struct ScheduledFaults<'a> {
+    /// Pairs of transaction index and fault
+    faults: &'a [(usize, Fault)],
+}
+
+/// Injects randomly selected faults with a fixed probability
+pub struct RandomFaults<'a> {
+    /// State of xorshift32 generator
+    state: u32,
+
+    /// A fault is injected in one of `rate` transactions on average
+    rate: u32,
+
+    faults: &'a [Fault],
+}
+
+impl<B: I2c<SevenBitAddress>, P: FaultPolicy> FaultyBus<B, P> {
+    pub fn new(bus: B, policy: P) -> Self {
+        Self {
+            bus,
+            policy,
+            transactions: 0,
+            injected: 0,
+        }
+    }
+
+    /// Returns the number of transactions started, including the failed ones
+    pub fn transactions(&self) -> usize {
+        self.transactions
+    }
+
+    /// Returns the number of injected faults
+    pub fn injected(&self) -> usize {
+        self.injected
+    }
+
+    /// Returns the wrapped bus
+    pub fn bus(&mut self) -> &mut B {
+        &mut self.bus
+    }
+
+    /// Returns the fault policy, e.g. for changing the policy between test steps
+    pub fn policy(&mut self) -> &mut P {
+        &mut self.policy
+    }
+
+    /// Releases the wrapped bus and the policy
+    pub fn release(self) -> (B, P) {
+        (self.bus, self.policy)
+    }
+}
+
+impl<B: I2c<SevenBitAddress>, P: FaultPolicy> ErrorType for FaultyBus<B, P> {
+    type Error = FaultError<B::Error>;
+}
+
+impl<B: I2c<SevenBitAddress>, P: FaultPolicy> I2c<SevenBitAddress> for FaultyBus<B, P> {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let fault = self.policy.fault(self.transactions);
+        self.transactions += 1;
+
+        if fault.is_some() {
+            self.injected += 1;
+        }
+
+        match fault {
+            None => self.bus.transaction(address, operations).map_err(FaultError::Bus),
+            Some(Fault::BitFlip(mask)) => {
+                self.bus.transaction(address, operations).map_err(FaultError::Bus)?;
+
+                for operation in operations {
+                    if let Operation::Read(buffer) = operation {
+                        buffer.iter_mut().for_each(|byte| *byte ^= mask);
+                    }
+                }
+
+                Ok(())
+            }
+            Some(fault) => Err(FaultError::Injected(fault)),
+        }
+    }
+}
+
+impl<E: Error> Error for FaultError<E> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            FaultError::Injected(Fault::Nack(source)) => ErrorKind::NoAcknowledge(*source),
+            FaultError::Injected(Fault::ArbitrationLoss) => ErrorKind::ArbitrationLoss,
+            FaultError::Injected(Fault::Bus) => ErrorKind::Bus,
+            FaultError::Injected(Fault::Timeout | Fault::BitFlip(_)) => ErrorKind::Other,
+            FaultError::Bus(error) => error.kind(),
+        }
+    }
+}
+
+impl<'a> ScheduledFaults<'a> {
+    pub fn new(faults: &'a [(usize, Fault)]) -> Self {
+        Self { faults }
+    }
+}
+
+impl FaultPolicy for ScheduledFaults<'_> {
+    fn fault(&mut self, transaction: usize) -> Option<Fault> {
+        self.faults
+            .iter()
+            .find(|(index, _)| *index == transaction)
+            .map(|(_, fault)| *fault)
+    }
+}
+
+impl<'a> RandomFaults<'a> {
+    /// A fault out of `faults` is injected in one of `rate` transactions on average
+    /// Panics if `rate` is zero or `faults` is empty.
+    pub fn new(seed: u32, rate: u32, faults: &'a [Fault]) -> Self {
+        assert!(rate > 0, "Fault rate must not be zero");
+        assert!(!faults.is_empty(), "No faults given");
+
+        Self {
+            // Xorshift gets stuck at zero
+            state: if seed == 0 { 0x9E37_79B9 } else { seed },
+            rate,
+            faults,
+        }
+    }
+
+    /// Returns the next pseudo random number (xorshift32)
+    fn next(&mut self) -> u32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state
+    }
+}
+
+impl FaultPolicy for RandomFaults<'_> {
+    fn fault(&mut self, _transaction: usize) -> Option<Fault> {
+        if !self.next().is_multiple_of(self.rate) {
+            return None;
+        }
+
+        Some(self.faults[self.next() as usize % self.faults.len()])
+    }
+}
+
+impl<F: FnMut(usize) -> Option<Fault>> FaultPolicy for F {
+    fn fault(&mut self, transaction: usize) -> Option<Fault> {
+        self(transaction)
+    }
+}
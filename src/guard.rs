@@ -1,19 +1,75 @@
 //! # Concurrency wrappers
 //!
 //! See [concurrency section](crate::pins#concurrency) for more details.
+//! Cached reads are served lock-free by the [atomic cache](crate::cache) of each guard.
+//!
+//! ## Non-blocking access
+//! [RefGuard::access()] panics on nested access (lock-free and CS mutex guard) or blocks until the
+//! expander is released (spin mutex guard). [RefGuard::try_access()] fails instead, so e.g. interrupt
+//! handlers may back off:
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::example::DummyI2CBus;
+//! use pca9539::expander::Bank::Bank0;
+//! use pca9539::expander::PCA9539;
+//! use pca9539::guard::{AccessError, RefGuard};
+//!
+//! let mut expander = PCA9539::new(DummyI2CBus::default(), Address::default());
+//! let pins = expander.pins();
+//! let guard = pins.guard();
+//!
+//! let result = guard.access(|expander| {
+//!     // Nested access is rejected
+//!     assert_eq!(Err(AccessError::Reentrant), guard.try_access(|_| ()));
+//!     expander.refresh_input_state(Bank0)
+//! });
+//! assert!(result.is_ok());
+//! ```
 
+use crate::cache::AtomicCache;
 use crate::expander::PCA9539;
 use core::cell::RefCell;
-use core::ops::DerefMut;
+use core::fmt::{Display, Formatter};
+use core::ops::{Deref, DerefMut};
 
 /// Manages the access of pins to expander reference
 pub trait RefGuard<B>
 where
     B: I2c<SevenBitAddress>,
 {
-    fn access<F>(&self, f: F)
+    /// Runs the given closure on the locked expander and returns its result
+    fn access<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut PCA9539<B>) -> T;
+
+    /// Runs the given closure on the locked expander and returns its result
+    /// Fails without running the closure, if the expander is locked elsewhere or by the caller itself.
+    fn try_access<T, F>(&self, f: F) -> Result<T, AccessError>
     where
-        F: FnMut(&mut PCA9539<B>);
+        F: FnOnce(&mut PCA9539<B>) -> T;
+
+    /// Returns the atomic register cache, which is accessible without locking
+    fn cache(&self) -> &AtomicCache;
+}
+
+/// Reason of a rejected [RefGuard::try_access()]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AccessError {
+    /// Expander is locked by another context, e.g. another core or thread
+    Contended,
+    /// Expander is already accessed by the caller, e.g. from within an access closure or an
+    /// interrupt handler preempting the access
+    Reentrant,
+}
+
+impl Display for AccessError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            AccessError::Contended => f.write_str("Expander is locked by another context"),
+            AccessError::Reentrant => f.write_str("Expander is already accessed"),
+        }
+    }
 }
 
 /// Guard which is neither Send or Sync, but is the most efficient
@@ -22,11 +78,13 @@ where
     B: I2c<SevenBitAddress>,
 {
     expander: RefCell<&'a mut PCA9539<B>>,
+    cache: AtomicCache,
 }
 
 impl<'a, B: I2c<SevenBitAddress>> LockFreeGuard<'a, B> {
     pub fn new(expander: RefCell<&'a mut PCA9539<B>>) -> Self {
-        LockFreeGuard { expander }
+        let cache = AtomicCache::new(expander.borrow().deref());
+        LockFreeGuard { expander, cache }
     }
 }
 
@@ -34,11 +92,23 @@ impl<B> RefGuard<B> for LockFreeGuard<'_, B>
 where
     B: I2c<SevenBitAddress>,
 {
-    fn access<F>(&self, mut f: F)
+    fn access<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut PCA9539<B>) -> T,
+    {
+        self.cache.access(self.expander.borrow_mut().deref_mut(), f)
+    }
+
+    fn try_access<T, F>(&self, f: F) -> Result<T, AccessError>
     where
-        F: FnMut(&mut PCA9539<B>),
+        F: FnOnce(&mut PCA9539<B>) -> T,
     {
-        f(self.expander.borrow_mut().deref_mut());
+        let mut expander = self.expander.try_borrow_mut().map_err(|_| AccessError::Reentrant)?;
+        Ok(self.cache.access(expander.deref_mut(), f))
+    }
+
+    fn cache(&self) -> &AtomicCache {
+        &self.cache
     }
 }
 
@@ -53,12 +123,19 @@ where
     B: I2c<SevenBitAddress>,
 {
     expander: CsMutex<RefCell<&'a mut PCA9539<B>>>,
+    cache: AtomicCache,
 }
 
 #[cfg(feature = "cortex-m")]
 impl<'a, B: I2c<SevenBitAddress>> CsMutexGuard<'a, B> {
-    pub fn new(expander: CsMutex<RefCell<&'a mut PCA9539<B>>>) -> Self {
-        CsMutexGuard { expander }
+    /// Wraps the given expander in a mutex
+    /// The mirror is created beforehand, so no critical section is entered.
+    pub fn new(expander: RefCell<&'a mut PCA9539<B>>) -> Self {
+        let cache = AtomicCache::new(expander.borrow().deref());
+        CsMutexGuard {
+            expander: CsMutex::new(expander),
+            cache,
+        }
     }
 }
 
@@ -67,14 +144,27 @@ impl<B> RefGuard<B> for CsMutexGuard<'_, B>
 where
     B: I2c<SevenBitAddress>,
 {
-    fn access<F>(&self, mut f: F)
+    fn access<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut PCA9539<B>) -> T,
+    {
+        cortex_m::interrupt::free(|cs| self.cache.access(self.expander.borrow(cs).borrow_mut().deref_mut(), f))
+    }
+
+    fn try_access<T, F>(&self, f: F) -> Result<T, AccessError>
     where
-        F: FnMut(&mut PCA9539<B>),
+        F: FnOnce(&mut PCA9539<B>) -> T,
     {
         cortex_m::interrupt::free(|cs| {
-            f(self.expander.borrow(cs).borrow_mut().deref_mut());
+            // Within critical sections, the expander can only be borrowed by the same context
+            let mut expander = self.expander.borrow(cs).try_borrow_mut().map_err(|_| AccessError::Reentrant)?;
+            Ok(self.cache.access(expander.deref_mut(), f))
         })
     }
+
+    fn cache(&self) -> &AtomicCache {
+        &self.cache
+    }
 }
 
 #[cfg(feature = "spin")]
@@ -86,12 +176,14 @@ where
     B: I2c<SevenBitAddress>,
 {
     expander: SpinMutex<RefCell<&'a mut PCA9539<B>>>,
+    cache: AtomicCache,
 }
 
 #[cfg(feature = "spin")]
 impl<'a, B: I2c<SevenBitAddress>> SpinGuard<'a, B> {
     pub fn new(expander: SpinMutex<RefCell<&'a mut PCA9539<B>>>) -> Self {
-        SpinGuard { expander }
+        let cache = AtomicCache::new(expander.lock().borrow().deref());
+        SpinGuard { expander, cache }
     }
 }
 
@@ -100,10 +192,24 @@ impl<B> RefGuard<B> for SpinGuard<'_, B>
 where
     B: I2c<SevenBitAddress>,
 {
-    fn access<F>(&self, mut f: F)
+    fn access<T, F>(&self, f: F) -> T
     where
-        F: FnMut(&mut PCA9539<B>),
+        F: FnOnce(&mut PCA9539<B>) -> T,
     {
-        f(self.expander.lock().borrow_mut().deref_mut());
+        self.cache.access(self.expander.lock().borrow_mut().deref_mut(), f)
+    }
+
+    /// A locked mutex is always reported as [AccessError::Contended], as the owner is unknown
+    fn try_access<T, F>(&self, f: F) -> Result<T, AccessError>
+    where
+        F: FnOnce(&mut PCA9539<B>) -> T,
+    {
+        let expander = self.expander.try_lock().ok_or(AccessError::Contended)?;
+        let result = self.cache.access(expander.borrow_mut().deref_mut(), f);
+        Ok(result)
+    }
+
+    fn cache(&self) -> &AtomicCache {
+        &self.cache
     }
 }
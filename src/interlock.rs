@@ -0,0 +1,192 @@
+//! # Output interlocks
+//!
+//! Interlocks prevent forbidden output combinations, e.g. high and low side of an H-bridge or two
+//! relays feeding the same bus being active together. A pin is considered active, if it is in
+//! output mode and driving its active level. Rules may span both banks.
+//!
+//! The following operations consult the interlocks before committing and fail with
+//! [Error::Interlock](crate::error::Error::Interlock) on violation:
+//! * [set_state()](crate::expander::PCA9539::set_state), [set_state_all()](crate::expander::PCA9539::set_state_all)
+//!   and [write_output_state()](crate::expander::PCA9539::write_output_state), including output pins
+//! * [set_mode()](crate::expander::PCA9539::set_mode) and [set_mode_all()](crate::expander::PCA9539::set_mode_all),
+//!   as a pin switching to output mode drives the level of the output register right away
+//! * [write_register()](crate::expander::PCA9539::write_register) of output and configuration registers
+//!
+//! Writes are checked against the output state of the device, so the output state of the other bank
+//! is written first, if this resolves the violation.
+//!
+//! Output changes of pins in refresh mode are checked, and resolved if configured, when applied to
+//! the expander by the next locked access, s. [cache module](crate::cache). Rejected changes are
+//! dropped and reported by the next `update_bank()` or `update_all()`.
+//!
+//! ## Resolution
+//! By default, violations are rejected. With [Resolution::DeactivateOther], setting the state of one
+//! pin of the rule deactivates the other pin instead.
+//!
+//! ## Example
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::example::DummyI2CBus;
+//! use pca9539::error::Error;
+//! use pca9539::expander::Bank::{Bank0, Bank1};
+//! use pca9539::expander::Mode::Output;
+//! use pca9539::expander::PCA9539;
+//! use pca9539::expander::PinID::{Pin0, Pin1, Pin4};
+//! use pca9539::interlock::{Interlock, InterlockPin, Resolution};
+//!
+//! const INTERLOCKS: [Interlock; 2] = [
+//!     // H-bridge: high side and low side must never be active together
+//!     Interlock::new(InterlockPin::high(Bank0, Pin0), InterlockPin::high(Bank0, Pin1)),
+//!     // Relays with active low drivers, switching over automatically
+//!     Interlock::new(InterlockPin::low(Bank0, Pin4), InterlockPin::low(Bank1, Pin4))
+//!         .resolution(Resolution::DeactivateOther),
+//! ];
+//!
+//! let mut expander = PCA9539::new(DummyI2CBus::default(), Address::default());
+//! expander.set_interlocks(&INTERLOCKS);
+//!
+//! expander.set_state_all(Bank0, false).unwrap();
+//! expander.set_mode(Bank0, Pin0, Output).unwrap();
+//! expander.set_mode(Bank0, Pin1, Output).unwrap();
+//!
+//! expander.set_state(Bank0, Pin0, true).unwrap();
+//! assert_eq!(Err(Error::Interlock(INTERLOCKS[0])), expander.set_state(Bank0, Pin1, true));
+//! ```
+use crate::expander::{Bank, PinID};
+use core::fmt::{Display, Formatter};
+
+/// Pin of an interlock rule together with its active level
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InterlockPin {
+    bank: Bank,
+    id: PinID,
+    active_high: bool,
+}
+
+/// Handling of interlock violations
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Resolution {
+    /// Output change is rejected
+    Reject,
+    /// Changing the state of one pin deactivates the other pin of the rule
+    DeactivateOther,
+}
+
+/// Rule forbidding two pins being active together
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Interlock {
+    first: InterlockPin,
+    second: InterlockPin,
+    resolution: Resolution,
+}
+
+impl InterlockPin {
+    /// Pin being active on high level
+    pub const fn high(bank: Bank, id: PinID) -> Self {
+        Self {
+            bank,
+            id,
+            active_high: true,
+        }
+    }
+
+    /// Pin being active on low level
+    pub const fn low(bank: Bank, id: PinID) -> Self {
+        Self {
+            bank,
+            id,
+            active_high: false,
+        }
+    }
+
+    /// Returns true if the pin is in output mode and driving the active level
+    fn is_active(&self, output: &[u8; 2], configuration: &[u8; 2]) -> bool {
+        let mask = 1 << self.id as u8;
+        let is_output = configuration[self.bank as usize] & mask == 0;
+        let is_high = output[self.bank as usize] & mask != 0;
+
+        is_output && is_high == self.active_high
+    }
+
+    /// Sets the output state to the inactive level
+    fn deactivate(&self, output: &mut [u8; 2]) {
+        let mask = 1 << self.id as u8;
+
+        match self.active_high {
+            true => output[self.bank as usize] &= !mask,
+            false => output[self.bank as usize] |= mask,
+        }
+    }
+
+    fn is_pin(&self, bank: Bank, id: PinID) -> bool {
+        self.bank == bank && self.id == id
+    }
+}
+
+impl Interlock {
+    /// Forbids both pins being active together, violations are rejected
+    pub const fn new(first: InterlockPin, second: InterlockPin) -> Self {
+        Self {
+            first,
+            second,
+            resolution: Resolution::Reject,
+        }
+    }
+
+    /// Sets the handling of violations
+    pub const fn resolution(mut self, resolution: Resolution) -> Self {
+        self.resolution = resolution;
+        self
+    }
+
+    fn is_violated(&self, output: &[u8; 2], configuration: &[u8; 2]) -> bool {
+        self.first.is_active(output, configuration) && self.second.is_active(output, configuration)
+    }
+}
+
+/// Returns the first violated rule for the given output and configuration register values
+pub(crate) fn check(rules: &[Interlock], output: &[u8; 2], configuration: &[u8; 2]) -> Result<(), Interlock> {
+    match rules.iter().find(|rule| rule.is_violated(output, configuration)) {
+        None => Ok(()),
+        Some(rule) => Err(*rule),
+    }
+}
+
+/// Resolves violations caused by changing the state of the given pin, afterwards checks all rules
+pub(crate) fn resolve(
+    rules: &[Interlock],
+    output: &mut [u8; 2],
+    configuration: &[u8; 2],
+    bank: Bank,
+    id: PinID,
+) -> Result<(), Interlock> {
+    for rule in rules {
+        if rule.resolution != Resolution::DeactivateOther || !rule.is_violated(output, configuration) {
+            continue;
+        }
+
+        if rule.first.is_pin(bank, id) {
+            rule.second.deactivate(output);
+        } else if rule.second.is_pin(bank, id) {
+            rule.first.deactivate(output);
+        }
+    }
+
+    check(rules, output, configuration)
+}
+
+impl Display for InterlockPin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let level = if self.active_high { "high" } else { "low" };
+        write!(f, "Pin{}{} {}", self.bank as u8, self.id as u8, level)
+    }
+}
+
+impl Display for Interlock {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{} and {}", self.first, self.second)
+    }
+}
@@ -0,0 +1,209 @@
+//! # Shared interrupt line
+//!
+//! PCA9539 signals input changes using an open-drain INT output. As the output is open-drain, the
+//! INT outputs of multiple expanders may be wired together to a single MCU pin, which is asserted
+//! (low) as long as at least one expander has pending input changes.
+//!
+//! [InterruptDispatcher] arbitrates such a shared line. When the line is asserted, the input
+//! registers of the registered expanders are read in turn, both banks of a device by one
+//! auto-increment read. Reading the input state clears the interrupt of the corresponding device.
+//! For each device with changed inputs a [InputChange] event is delivered. Once the line is
+//! deasserted, the remaining devices are skipped.
+//!
+//! Changes are detected by comparing against the cached input state of each expander. So the
+//! input state should be refreshed once before registering the expander.
+//!
+//! ## Example
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::address::Strap::{High, Low};
+//! use pca9539::example::{DummyI2CBus, DummyInputPin};
+//! use pca9539::expander::Bank::Bank0;
+//! use pca9539::expander::PCA9539;
+//! use pca9539::expander::PinID::Pin1;
+//! use pca9539::interrupt::InterruptDispatcher;
+//!
+//! let mut expander_0 = PCA9539::new(DummyI2CBus::default(), Address::new(Low, Low));
+//! let mut expander_1 = PCA9539::new(DummyI2CBus::default(), Address::new(Low, High));
+//!
+//! // Shared INT line, currently asserted
+//! let line = DummyInputPin::new(false);
+//!
+//! let mut dispatcher: InterruptDispatcher<_, _, 4> = InterruptDispatcher::new(line);
+//! dispatcher.register(&mut expander_0).unwrap();
+//! dispatcher.register(&mut expander_1).unwrap();
+//!
+//! dispatcher
+//!     .dispatch(|change| {
+//!         if change.has_changed(Bank0, Pin1) {
+//!             assert!(change.is_high(Bank0, Pin1));
+//!         }
+//!     })
+//!     .unwrap();
+//! ```
+use crate::error::Error;
+use crate::expander::{Bank, PinID, PCA9539};
+use crate::register::Register;
+use core::fmt::{Debug, Formatter};
+use embedded_hal::digital::InputPin;
+use embedded_hal::i2c::{I2c, SevenBitAddress};
+use heapless::Vec;
+
+/// Dispatches input changes of up to `N` expanders sharing one INT line
+pub struct InterruptDispatcher<'a, B, P, const N: usize>
+where
+    B: I2c<SevenBitAddress>,
+    P: InputPin,
+{
+    /// MCU pin connected to the shared INT line (active low)
+    line: P,
+
+    /// Registered expanders in polling order
+    expanders: Vec<&'a mut PCA9539<B>, N>,
+}
+
+/// Input change of a single expander
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct InputChange {
+    /// Index of the device, as returned by [InterruptDispatcher::register]
+    pub device: usize,
+
+    /// Input state before the interrupt, indexed by bank
+    previous: [u8; 2],
+
+    /// Input state after the interrupt, indexed by bank
+    current: [u8; 2],
+}
+
+/// Dispatcher capacity is exhausted, no further expanders can be registered
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CapacityError;
+
+/// Error while dispatching an interrupt
+pub enum DispatchError<B: I2c<SevenBitAddress>, E> {
+    /// Reading the level of the INT line failed
+    LineError(E),
+    /// Refreshing the input state of the device with the given index failed
+    BusError(usize, Error<B::Error>),
+}
+
+impl<'a, B, P, const N: usize> InterruptDispatcher<'a, B, P, N>
+where
+    B: I2c<SevenBitAddress>,
+    P: InputPin,
+{
+    pub fn new(line: P) -> Self {
+        Self {
+            line,
+            expanders: Vec::new(),
+        }
+    }
+
+    /// Registers the given expander and returns its device index
+    /// Expanders are polled in the order of registration.
+    pub fn register(&mut self, expander: &'a mut PCA9539<B>) -> Result<usize, CapacityError> {
+        self.expanders.push(expander).map_err(|_| CapacityError)?;
+        Ok(self.expanders.len() - 1)
+    }
+
+    /// Returns the expander with the given device index
+    pub fn device(&mut self, index: usize) -> Option<&mut PCA9539<B>> {
+        self.expanders.get_mut(index).map(|expander| &mut **expander)
+    }
+
+    /// Returns true if the shared INT line is asserted (low)
+    pub fn is_asserted(&mut self) -> Result<bool, P::Error> {
+        self.line.is_low()
+    }
+
+    /// Reads the registered expanders in turn while the INT line is asserted and calls the handler
+    /// for each device with changed inputs.
+    /// Returns the number of devices read. Zero is returned if the line was not asserted at all.
+    pub fn dispatch<F>(&mut self, mut handler: F) -> Result<usize, DispatchError<B, P::Error>>
+    where
+        F: FnMut(InputChange),
+    {
+        let mut read = 0;
+
+        for (device, expander) in self.expanders.iter_mut().enumerate() {
+            if !self.line.is_low().map_err(DispatchError::LineError)? {
+                break;
+            }
+
+            let previous = [
+                expander.cached_register(Register::Input0),
+                expander.cached_register(Register::Input1),
+            ];
+
+            // Both banks within one transaction
+            let current = expander
+                .read_register_pair(Register::Input0, true)
+                .map_err(|error| DispatchError::BusError(device, error))?;
+            read += 1;
+
+            if previous != current {
+                handler(InputChange {
+                    device,
+                    previous,
+                    current,
+                });
+            }
+        }
+
+        Ok(read)
+    }
+
+    /// Releases the INT line pin
+    pub fn release(self) -> P {
+        self.line
+    }
+}
+
+impl InputChange {
+    /// Returns true if the input state of the given pin has changed
+    pub fn has_changed(&self, bank: Bank, id: PinID) -> bool {
+        self.changed(bank) & (1 << id as u8) != 0
+    }
+
+    /// Returns true if the given pin input is high after the change
+    pub fn is_high(&self, bank: Bank, id: PinID) -> bool {
+        self.current[bank as usize] & (1 << id as u8) != 0
+    }
+
+    /// Returns the bit mask of changed pins of the given bank
+    pub fn changed(&self, bank: Bank) -> u8 {
+        self.previous[bank as usize] ^ self.current[bank as usize]
+    }
+
+    /// Returns the input register of the given bank after the change
+    pub fn state(&self, bank: Bank) -> u8 {
+        self.current[bank as usize]
+    }
+}
+
+impl<B: I2c, E: Debug> Debug for DispatchError<B, E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DispatchError::LineError(error) => write!(f, "DispatchError::LineError({:?})", error),
+            DispatchError::BusError(device, error) => {
+                write!(f, "DispatchError::BusError({}, {:?})", device, error)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<B: I2c, E> defmt::Format for DispatchError<B, E>
+where
+    B::Error: defmt::Format,
+{
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            DispatchError::LineError(_) => defmt::write!(f, "DispatchError::LineError"),
+            DispatchError::BusError(device, error) => {
+                defmt::write!(f, "DispatchError::BusError({}, {})", device, error)
+            }
+        }
+    }
+}
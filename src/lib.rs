@@ -6,10 +6,32 @@
 //! * Central I/O control, s. [PCA9539 module](crate::expander)
 //! * Two state management modes for reduced I2C overhead, s. [pins module](crate::pins)
 //! * Three concurrency models, s. [concurrency section](crate::pins#concurrency)
+//! * Lock-free cached reads, e.g. from interrupt handlers, s. [cache module](crate::cache)
+//! * Multiple expanders combined to one GPIO space, s. [multi module](crate::multi)
+//! * Interrupt dispatching for multiple expanders sharing one INT line, s. [interrupt module](crate::interrupt)
+//! * Register accurate device simulation for tests, s. `simulator` module (requires `simulator` feature)
+//! * Fault injection for testing error paths, s. `fault` module (requires `fault-injection` feature)
+//! * Expectation based I2C bus for unit tests, s. `testing` module (requires `testing` feature)
+//! * Recording and replay of I2C traffic, s. `recording` module (requires `recording` feature)
+//! * Decoding of logic analyzer captures, s. `decoder` module (requires `decoder` feature)
+//! * Connection state tracking with automatic recovery, s. [link module](crate::link)
+//! * Declarative board configuration applied at init, s. [config module](crate::config)
+//! * Typed board pin maps with named signals, s. [pin_map module](mod@crate::pin_map)
+//! * Output interlocks preventing forbidden pin combinations, s. [interlock module](crate::interlock)
+//! * Output short/overload detection by input register readback, s. [monitor module](crate::monitor)
+//! * Locking of mode, polarity and output state of single pins, s. [lock module](crate::lock)
+//! * Configuration profiles switchable at runtime, s. [profile module](crate::profile)
+//! * Versioned state snapshots for warm restarts and logging, s. [snapshot module](crate::snapshot)
+//! * Safe state applied on fault or shutdown, s. [safe_state module](crate::safe_state)
+//! * Retrying of transient bus errors, s. [retry module](crate::retry)
+//! * Bus statistics for telemetry, s. `stats` module (requires `stats` feature)
+//! * Tracing of register accesses via [defmt](https://docs.rs/defmt) or [log](https://docs.rs/log), s. [tracing section](#tracing)
 //! * no_std support
 //!
 //! ## Example
 //! ```
+//! use pca9539::address::Address;
+//! use pca9539::address::Strap::Low;
 //! use pca9539::example::DummyI2CBus;
 //! use pca9539::expander::Bank::Bank0;
 //! use pca9539::expander::PCA9539;
@@ -17,25 +39,66 @@
 //! use embedded_hal::digital::InputPin;
 //!
 //! let i2c_bus = DummyI2CBus::default();
-//! let mut  expander = PCA9539::new(i2c_bus, 0x74);
+//! let mut  expander = PCA9539::new(i2c_bus, Address::new(Low, Low));
 //! let pins = expander.pins();
 //!
 //! let mut pin01 = pins.get_pin(Bank0, Pin1);
 //! assert!(pin01.is_high().unwrap());
+//! ```
+//!
+//! ## Tracing
+//! With the `defmt` or `log` feature enabled, all register reads and writes are traced on `trace` level
+//! including the register name and value, e.g. `0x74 write Configuration0 = 0b11110111`. Bus errors are
+//! traced on `warn` level. The verbosity is chosen at compile time, by the `DEFMT_LOG` environment
+//! variable for defmt or by the `max_level_*`/`release_max_level_*` features of log. Without both
+//! features, no tracing code is compiled in.
+//!
+//! The `defmt` feature additionally implements `defmt::Format` for the public types, e.g. [Bank](crate::expander::Bank),
+//! [PinID](crate::expander::PinID), [Mode](crate::expander::Mode) and [Error](crate::error::Error).
 #![cfg_attr(not(test), no_std)]
 #![cfg_attr(feature = "strict", deny(warnings))]
 
 extern crate embedded_hal;
 
+pub mod address;
+pub mod cache;
+pub mod config;
+#[cfg(any(test, feature = "decoder"))]
+pub mod decoder;
+pub mod error;
 #[cfg(feature = "example")]
 pub mod example;
 pub mod expander;
+#[cfg(any(test, feature = "fault-injection"))]
+pub mod fault;
 pub mod guard;
+pub mod interlock;
+pub mod interrupt;
+pub mod link;
+pub mod lock;
+pub mod monitor;
+pub mod multi;
+pub mod pin_map;
 pub mod pins;
+pub mod probe;
+pub mod profile;
+#[cfg(any(test, feature = "recording"))]
+pub mod recording;
+pub mod register;
+pub mod retry;
+pub mod safe_state;
+#[cfg(any(test, feature = "simulator"))]
+pub mod simulator;
+pub mod snapshot;
+#[cfg(feature = "stats")]
+pub mod stats;
 
 pub(crate) mod pin_refreshable;
 pub(crate) mod pin_regular;
 pub mod sync_state;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub(crate) mod trace;
 
 #[cfg(test)]
 mod mocks;
@@ -0,0 +1,236 @@
+//! # Link state
+//!
+//! Each [PCA9539] tracks the state of its connection, e.g. for expanders on removable harnesses. The
+//! state is derived from the results of all register accesses:
+//!
+//! | State                    | Entered                                                    |
+//! |--------------------------|------------------------------------------------------------|
+//! | [LinkState::Online]      | Initially, on success in Degraded state or after recovery  |
+//! | [LinkState::Degraded]    | After [LinkPolicy::degraded_after] consecutive errors      |
+//! | [LinkState::Offline]     | After [LinkPolicy::offline_after] consecutive errors       |
+//! | [LinkState::Recovering]  | On success in Offline state, until the device is recovered |
+//!
+//! As the device may have lost its state while being disconnected, it is not declared Online again
+//! before being recovered. Depending on [Recovery], either the cached state is written to the device
+//! ([sync_state()](PCA9539::sync_state)) or the hardware state is adopted
+//! ([dump_registers()](PCA9539::dump_registers)).
+//!
+//! ## Supervision
+//! [poll_link()](PCA9539::poll_link) is intended to be called periodically, e.g. by a supervisor
+//! task. In Degraded and Offline state, the device is probed by reading the configuration register.
+//! Once the device responds again, the recovery is performed within the same call.
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::example::DummyI2CBus;
+//! use pca9539::expander::PCA9539;
+//! use pca9539::link::{LinkChange, LinkPolicy, LinkState, Recovery};
+//!
+//! fn on_link_change(change: LinkChange) {
+//!     // E.g. report change.current to the supervisor
+//! }
+//!
+//! let mut expander = PCA9539::new(DummyI2CBus::default(), Address::default());
+//! expander.set_link_policy(LinkPolicy::new().offline_after(5).recovery(Recovery::AdoptHardware));
+//! expander.set_link_callback(on_link_change);
+//!
+//! // Periodically
+//! assert_eq!(LinkState::Online, expander.poll_link());
+//! ```
+use crate::address::Address;
+use crate::expander::PCA9539;
+use crate::register::Register;
+use embedded_hal::i2c::{I2c, SevenBitAddress};
+
+/// State of the connection to an expander
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LinkState {
+    /// Device is responding
+    Online,
+    /// Recent accesses failed, device may be disconnected
+    Degraded,
+    /// Device is considered disconnected
+    Offline,
+    /// Device responds again, but has not been recovered yet
+    Recovering,
+}
+
+/// Recovery action after reconnection
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Recovery {
+    /// Writes the cached state (polarity, output state, mode) to the device
+    SyncState,
+    /// Reads all registers and adopts the hardware state as cached state
+    /// If outputs are locked by the [safe state](crate::safe_state), the safe state is re-applied afterwards.
+    AdoptHardware,
+}
+
+/// Thresholds and recovery action of link state tracking
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LinkPolicy {
+    degraded_after: u32,
+    offline_after: u32,
+    recovery: Recovery,
+}
+
+/// Transition of the link state
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LinkChange {
+    /// Address of the expander
+    pub address: Address,
+    pub previous: LinkState,
+    pub current: LinkState,
+}
+
+/// Tracks the link state based on the results of register accesses
+pub(crate) struct LinkTracker {
+    state: LinkState,
+    policy: LinkPolicy,
+
+    /// Number of consecutive errors, also reported by the [statistics](crate::stats) and
+    /// triggering the [safe state](crate::safe_state)
+    error_streak: u32,
+
+    /// Called on each state transition
+    callback: Option<fn(LinkChange)>,
+}
+
+impl LinkPolicy {
+    /// Degraded after one error, offline after three consecutive errors, recovery by [Recovery::SyncState]
+    pub const fn new() -> Self {
+        Self {
+            degraded_after: 1,
+            offline_after: 3,
+            recovery: Recovery::SyncState,
+        }
+    }
+
+    /// Sets the number of consecutive errors after which the link is degraded
+    pub const fn degraded_after(mut self, errors: u32) -> Self {
+        self.degraded_after = errors;
+        self
+    }
+
+    /// Sets the number of consecutive errors after which the device is considered disconnected
+    pub const fn offline_after(mut self, errors: u32) -> Self {
+        self.offline_after = errors;
+        self
+    }
+
+    /// Sets the recovery action after reconnection
+    pub const fn recovery(mut self, recovery: Recovery) -> Self {
+        self.recovery = recovery;
+        self
+    }
+}
+
+impl Default for LinkPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LinkTracker {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: LinkState::Online,
+            policy: LinkPolicy::new(),
+            error_streak: 0,
+            callback: None,
+        }
+    }
+
+    /// Returns the number of consecutive errors
+    pub(crate) fn error_streak(&self) -> u32 {
+        self.error_streak
+    }
+
+    /// Records a successful register access
+    pub(crate) fn record_success(&mut self, address: Address) {
+        self.error_streak = 0;
+
+        match self.state {
+            LinkState::Degraded => self.transition(address, LinkState::Online),
+            LinkState::Offline => self.transition(address, LinkState::Recovering),
+            LinkState::Online | LinkState::Recovering => {}
+        }
+    }
+
+    /// Records a failed register access
+    pub(crate) fn record_error(&mut self, address: Address) {
+        self.error_streak = self.error_streak.saturating_add(1);
+
+        let state = match self.state {
+            LinkState::Offline => LinkState::Offline,
+            LinkState::Recovering => LinkState::Offline,
+            _ if self.error_streak >= self.policy.offline_after => LinkState::Offline,
+            _ if self.error_streak >= self.policy.degraded_after => LinkState::Degraded,
+            state => state,
+        };
+
+        self.transition(address, state);
+    }
+
+    fn transition(&mut self, address: Address, state: LinkState) {
+        if self.state == state {
+            return;
+        }
+
+        let change = LinkChange {
+            address,
+            previous: self.state,
+            current: state,
+        };
+
+        self.state = state;
+
+        if let Some(callback) = self.callback {
+            callback(change);
+        }
+    }
+}
+
+impl<B: I2c<SevenBitAddress>> PCA9539<B> {
+    /// Returns the current link state
+    pub fn link_state(&self) -> LinkState {
+        self.link().state
+    }
+
+    /// Replaces the link policy
+    pub fn set_link_policy(&mut self, policy: LinkPolicy) {
+        self.link_mut().policy = policy;
+    }
+
+    /// Sets a callback, which is called on each link state transition
+    pub fn set_link_callback(&mut self, callback: fn(LinkChange)) {
+        self.link_mut().callback = Some(callback);
+    }
+
+    /// Probes a degraded or disconnected device and recovers it after reconnection
+    /// Returns the link state afterwards. Does not access the bus in Online state.
+    pub fn poll_link(&mut self) -> LinkState {
+        if matches!(self.link_state(), LinkState::Degraded | LinkState::Offline) {
+            // Errors are already reflected by the link state
+            let _ = self.read_register(Register::Configuration0, false);
+        }
+
+        if self.link_state() == LinkState::Recovering {
+            let result = match self.link().policy.recovery {
+                Recovery::SyncState => self.sync_state(),
+                Recovery::AdoptHardware => match self.is_safe_state_locked() {
+                    true => self.dump_registers(true).and_then(|_| self.enter_safe_state()),
+                    false => self.dump_registers(true).map(|_| ()),
+                },
+            };
+
+            if result.is_ok() {
+                let address = self.address();
+                self.link_mut().transition(address, LinkState::Online);
+            }
+        }
+
+        self.link_state()
+    }
+}
@@ -0,0 +1,128 @@
+//! # Pin locks
+//!
+//! Pins may be locked after their initial configuration, e.g. pyro or power-enable lines. Afterwards
+//! the following operations fail with [Error::PinLocked], if they would change mode, polarity or
+//! output state of a locked pin:
+//! * [set_mode()](PCA9539::set_mode), [reverse_polarity()](PCA9539::reverse_polarity) and
+//!   [set_state()](PCA9539::set_state), including output pins
+//! * [write_register()](PCA9539::write_register)
+//!
+//! [set_mode_all()](PCA9539::set_mode_all) and [set_state_all()](PCA9539::set_state_all) just
+//! ignore locked pins. Output pins in refresh mode ignore changes of locked pins as well, but report
+//! them by the next `update_bank()` or `update_all()`, s. [cache module](crate::cache).
+//! The [safe state](crate::safe_state) is applied regardless of locks.
+//!
+//! ## Irreversible locks
+//! By default, locks may be released by [unlock_pins()](PCA9539::unlock_pins). After calling
+//! [seal_locks()](PCA9539::seal_locks), this fails with [Error::LocksSealed] until the [PCA9539]
+//! instance is dropped, e.g. by a reset. Additional pins may still be locked.
+//!
+//! ## Example
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::example::DummyI2CBus;
+//! use pca9539::error::Error;
+//! use pca9539::expander::Bank::Bank0;
+//! use pca9539::expander::Mode::{Input, Output};
+//! use pca9539::expander::PCA9539;
+//! use pca9539::expander::PinID::{Pin2, Pin3};
+//!
+//! let mut expander = PCA9539::new(DummyI2CBus::default(), Address::default());
+//! expander.set_mode(Bank0, Pin2, Output).unwrap();
+//! expander.set_state(Bank0, Pin2, false).unwrap();
+//! expander.write_output_state(Bank0).unwrap();
+//!
+//! expander.lock_pin(Bank0, Pin2);
+//! expander.seal_locks();
+//!
+//! assert_eq!(Err(Error::PinLocked(Bank0, Pin2)), expander.set_mode(Bank0, Pin2, Input));
+//! assert_eq!(Err(Error::LocksSealed), expander.unlock_pins(Bank0, 0b0000_0100));
+//!
+//! // Pin02 keeps its mode
+//! expander.set_mode_all(Bank0, Input).unwrap();
+//! expander.set_mode(Bank0, Pin3, Output).unwrap();
+//! ```
+use crate::error::Error;
+use crate::expander::{Bank, PinID, PCA9539};
+use embedded_hal::i2c::{I2c, SevenBitAddress};
+
+/// Locked pins of an expander
+pub(crate) struct PinLocks {
+    /// Locked pins, indexed by bank
+    mask: [u8; 2],
+
+    /// True if locks are irreversible
+    sealed: bool,
+}
+
+impl PinLocks {
+    pub(crate) fn new() -> Self {
+        Self {
+            mask: [0; 2],
+            sealed: false,
+        }
+    }
+
+    /// Fails if the given register value of the given bank changes any locked pin compared to the current value
+    pub(crate) fn check<E>(&self, bank: Bank, current: u8, value: u8) -> Result<(), Error<E>> {
+        let changed = (current ^ value) & self.mask[bank as usize];
+
+        match changed {
+            0 => Ok(()),
+            _ => Err(Error::PinLocked(bank, PinID::ALL[changed.trailing_zeros() as usize])),
+        }
+    }
+
+    /// Returns the given register value with locked pins keeping their current value
+    pub(crate) fn retain(&self, bank: Bank, current: u8, value: u8) -> u8 {
+        let mask = self.mask[bank as usize];
+        (value & !mask) | (current & mask)
+    }
+
+    pub(crate) fn is_locked(&self, bank: Bank, id: PinID) -> bool {
+        self.mask[bank as usize] & (1 << id as u8) != 0
+    }
+}
+
+impl<B: I2c<SevenBitAddress>> PCA9539<B> {
+    /// Locks mode, polarity and output state of the given pin
+    pub fn lock_pin(&mut self, bank: Bank, id: PinID) {
+        self.lock_pins(bank, 1 << id as u8);
+    }
+
+    /// Locks all pins of the given bank set in the given bitmask
+    pub fn lock_pins(&mut self, bank: Bank, mask: u8) {
+        self.locks_mut().mask[bank as usize] |= mask;
+    }
+
+    /// Unlocks all pins of the given bank set in the given bitmask
+    /// Fails if locks are sealed.
+    pub fn unlock_pins(&mut self, bank: Bank, mask: u8) -> Result<(), Error<B::Error>> {
+        if self.locks().sealed {
+            return Err(Error::LocksSealed);
+        }
+
+        self.locks_mut().mask[bank as usize] &= !mask;
+        Ok(())
+    }
+
+    /// Makes all current and future locks irreversible for the lifetime of this instance
+    pub fn seal_locks(&mut self) {
+        self.locks_mut().sealed = true;
+    }
+
+    /// Returns true if the given pin is locked
+    pub fn is_pin_locked(&self, bank: Bank, id: PinID) -> bool {
+        self.locks().is_locked(bank, id)
+    }
+
+    /// Returns the bitmask of locked pins of the given bank
+    pub fn locked_pins(&self, bank: Bank) -> u8 {
+        self.locks().mask[bank as usize]
+    }
+
+    /// Returns true if locks are irreversible
+    pub fn are_locks_sealed(&self) -> bool {
+        self.locks().sealed
+    }
+}
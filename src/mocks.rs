@@ -1,4 +1,6 @@
-use embedded_hal::i2c::{Error, ErrorKind, ErrorType, I2c, Operation, SevenBitAddress};
+use core::convert::Infallible;
+use embedded_hal::digital::{self, InputPin};
+use embedded_hal::i2c::{Error, ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation, SevenBitAddress};
 use mockall::mock;
 
 #[derive(Debug, PartialEq)]
@@ -6,6 +8,7 @@ use mockall::mock;
 pub enum DummyError {
     ReadError,
     WriteError,
+    Nack,
 }
 
 mock! {
@@ -17,13 +20,29 @@ mock! {
     }
 }
 
+mock! {
+    pub InputLine{}
+
+    impl digital::ErrorType for InputLine {
+        type Error = Infallible;
+    }
+
+    impl InputPin for InputLine {
+        fn is_high(&mut self) -> Result<bool, Infallible>;
+        fn is_low(&mut self) -> Result<bool, Infallible>;
+    }
+}
+
 impl ErrorType for MockI2CBus {
     type Error = DummyError;
 }
 
 impl Error for DummyError {
     fn kind(&self) -> ErrorKind {
-        ErrorKind::Other
+        match self {
+            DummyError::Nack => ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+            _ => ErrorKind::Other,
+        }
     }
 }
 
@@ -88,6 +107,26 @@ impl BusMockBuilder {
         self
     }
 
+    /// Expects a read of both registers of a pair
+    pub fn expect_read_pair(mut self, times: usize, data: [u8; 2]) -> Self {
+        self.bus
+            .expect_transaction()
+            .times(times)
+            .returning(move |address, operations| {
+                assert_eq!(1, operations.len());
+                assert_eq!(0x74, address);
+
+                match &mut operations[0] {
+                    Operation::Read(buffer) => buffer.copy_from_slice(&data),
+                    Operation::Write(_) => panic!("Expected read operation"),
+                }
+
+                Ok(())
+            });
+
+        self
+    }
+
     pub fn write_error(mut self, command: u8) -> Self {
         self.bus.expect_transaction().times(1).returning(move |address, operations| {
             assert_eq!(0x74, address);
@@ -118,7 +157,32 @@ impl BusMockBuilder {
         self
     }
 
+    /// Expect the given number of transactions, which are not acknowledged
+    pub fn nack(mut self, times: usize) -> Self {
+        self.bus
+            .expect_transaction()
+            .times(times)
+            .returning(move |_, _| Err(DummyError::Nack));
+
+        self
+    }
+
     pub fn into_mock(self) -> MockI2CBus {
         self.bus
     }
 }
+
+/// Returns a mocked INT line, reporting the given levels (true = high) on subsequent calls of `is_low()`
+pub fn mock_line(levels: &[bool]) -> MockInputLine {
+    let mut line = MockInputLine::new();
+    let mut sequence = mockall::Sequence::new();
+
+    for is_high in levels.iter().copied() {
+        line.expect_is_low()
+            .times(1)
+            .in_sequence(&mut sequence)
+            .returning(move || Ok(!is_high));
+    }
+
+    line
+}
@@ -0,0 +1,146 @@
+//! # Output monitor
+//!
+//! The input registers of PCA9539 reflect the actual pin level, also for pins in output mode. So a
+//! mismatch between the output level written to the device and the read-back level indicates a
+//! short or overload of the output.
+//!
+//! [OutputMonitor] compares both for all pins in output mode, taking polarity inversion into
+//! account. As the read-back level may briefly differ, e.g. while charging a capacitive load, a pin
+//! is only reported as faulty after the given number of consecutive mismatching samples. Likewise
+//! the fault is cleared after the same number of consecutive matching samples.
+//!
+//! ## Example
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::example::DummyI2CBus;
+//! use pca9539::expander::Bank::{Bank0, Bank1};
+//! use pca9539::expander::Mode::Output;
+//! use pca9539::expander::PCA9539;
+//! use pca9539::expander::PinID::Pin3;
+//! use pca9539::monitor::OutputMonitor;
+//!
+//! let mut expander = PCA9539::new(DummyI2CBus::default(), Address::default());
+//! expander.set_mode(Bank0, Pin3, Output).unwrap();
+//!
+//! // Faults are reported after three consecutive mismatches
+//! let mut monitor = OutputMonitor::new(3);
+//!
+//! // Periodically
+//! let faults = monitor.sample(&mut expander).unwrap();
+//! assert!(!faults.is_faulty(Bank0, Pin3));
+//! assert_eq!(0b0000_0000, faults.bank(Bank1));
+//! ```
+//! ## Single pins
+//! Output pins expose the read-back level by `actual_level()`. Regular pins are reading the input
+//! register, pins in refresh mode return the cached level, which is updated by `refresh_bank()` or
+//! `refresh_all()` of input pins or by [OutputMonitor::sample].
+use crate::error::Error;
+use crate::expander::{Bank, PinID, PCA9539};
+use crate::register::Register;
+use embedded_hal::i2c::{I2c, SevenBitAddress};
+
+/// Debounced detection of output pins, whose actual level differs from the output state
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OutputMonitor {
+    /// Number of consecutive samples required for reporting or clearing a fault
+    samples: u8,
+
+    /// Consecutive samples deviating from the reported state, indexed by bank and pin
+    counters: [[u8; 8]; 2],
+
+    /// Reported faults
+    faults: OutputFaults,
+}
+
+/// Bitmask of faulty output pins
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OutputFaults {
+    /// Faulty pins, indexed by bank
+    mask: [u8; 2],
+}
+
+impl OutputMonitor {
+    /// Reports/clears faults after the given number of consecutive samples (at least one)
+    pub const fn new(samples: u8) -> Self {
+        Self {
+            samples: if samples == 0 { 1 } else { samples },
+            counters: [[0; 8]; 2],
+            faults: OutputFaults { mask: [0; 2] },
+        }
+    }
+
+    /// Reads both input registers and evaluates the result
+    pub fn sample<B: I2c<SevenBitAddress>>(
+        &mut self,
+        expander: &mut PCA9539<B>,
+    ) -> Result<OutputFaults, Error<B::Error>> {
+        expander.read_register_pair(Register::Input0, true)?;
+        Ok(self.evaluate(expander))
+    }
+
+    /// Evaluates the cached input state without accessing the bus
+    /// Intended for applications refreshing the input state anyway.
+    pub fn evaluate<B: I2c<SevenBitAddress>>(&mut self, expander: &PCA9539<B>) -> OutputFaults {
+        let written = expander.written_output();
+
+        for bank in [Bank::Bank0, Bank::Bank1] {
+            let outputs = !expander.cached_register(Register::configuration(bank));
+            let polarity = expander.cached_register(Register::polarity(bank));
+            let level = expander.cached_register(Register::input(bank)) ^ polarity;
+            let mismatch = (level ^ written[bank as usize]) & outputs;
+
+            for pin in 0..8 {
+                self.evaluate_pin(bank, pin, mismatch & (1 << pin) != 0);
+            }
+        }
+
+        self.faults
+    }
+
+    /// Returns the currently reported faults
+    pub fn faults(&self) -> OutputFaults {
+        self.faults
+    }
+
+    /// Clears all faults and samples
+    pub fn reset(&mut self) {
+        self.counters = [[0; 8]; 2];
+        self.faults = OutputFaults::default();
+    }
+
+    fn evaluate_pin(&mut self, bank: Bank, pin: u8, mismatch: bool) {
+        let mask = 1 << pin;
+        let reported = self.faults.mask[bank as usize] & mask != 0;
+        let counter = &mut self.counters[bank as usize][pin as usize];
+
+        if mismatch == reported {
+            *counter = 0;
+            return;
+        }
+
+        *counter += 1;
+
+        if *counter >= self.samples {
+            *counter = 0;
+            self.faults.mask[bank as usize] ^= mask;
+        }
+    }
+}
+
+impl OutputFaults {
+    /// Returns true if the given pin is faulty
+    pub fn is_faulty(&self, bank: Bank, id: PinID) -> bool {
+        self.mask[bank as usize] & (1 << id as u8) != 0
+    }
+
+    /// Returns the bitmask of faulty pins of the given bank
+    pub fn bank(&self, bank: Bank) -> u8 {
+        self.mask[bank as usize]
+    }
+
+    /// Returns true if no pin is faulty
+    pub fn is_empty(&self) -> bool {
+        self.mask == [0; 2]
+    }
+}
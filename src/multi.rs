@@ -0,0 +1,350 @@
+//! # Multiple expanders
+//!
+//! Up to four PCA9539 may share one I2C bus, s. [Address::ALL](crate::address::Address::ALL).
+//! [Expanders] combines several [PCA9539] instances to one logical GPIO space. Pins are addressed by
+//! a flat index, counting 16 pins per device in the order of the given devices:
+//!
+//! | Index     | Device | Bank  | Pin       |
+//! |-----------|--------|-------|-----------|
+//! | 0 - 7     | 0      | Bank0 | Pin0-Pin7 |
+//! | 8 - 15    | 0      | Bank1 | Pin0-Pin7 |
+//! | 16 - 23   | 1      | Bank0 | Pin0-Pin7 |
+//! | ...       | ...    | ...   | ...       |
+//!
+//! The container either owns the expanders or borrows them mutably. For sharing the bus,
+//! the I2C device implementations of [embedded-hal-bus](https://docs.rs/embedded-hal-bus) may be used.
+//!
+//! ## Setup
+//! ```
+//! use core::cell::RefCell;
+//! use embedded_hal_bus::i2c::RefCellDevice;
+//! use pca9539::address::Address;
+//! use pca9539::address::Strap::{High, Low};
+//! use pca9539::example::DummyI2CBus;
+//! use pca9539::expander::PCA9539;
+//! use pca9539::multi::Expanders;
+//!
+//! let i2c_bus = RefCell::new(DummyI2CBus::default());
+//!
+//! let mut expanders = Expanders::new([
+//!     PCA9539::new(RefCellDevice::new(&i2c_bus), Address::new(Low, Low)),
+//!     PCA9539::new(RefCellDevice::new(&i2c_bus), Address::new(Low, High)),
+//! ]);
+//! ```
+//! ## Individual pins
+//! Pins are fetched using the same access modes and concurrency models as for single expanders,
+//! s. [pins module](crate::pins).
+//! ```
+//!# use core::cell::RefCell;
+//!# use embedded_hal_bus::i2c::RefCellDevice;
+//!# use pca9539::address::Address;
+//!# use pca9539::address::Strap::{High, Low};
+//!# use pca9539::example::DummyI2CBus;
+//!# use pca9539::expander::PCA9539;
+//!# use pca9539::multi::Expanders;
+//!# use embedded_hal::digital::{InputPin, OutputPin, PinState};
+//!#
+//!# let i2c_bus = RefCell::new(DummyI2CBus::default());
+//!# let mut expanders = Expanders::new([
+//!#     PCA9539::new(RefCellDevice::new(&i2c_bus), Address::new(Low, Low)),
+//!#     PCA9539::new(RefCellDevice::new(&i2c_bus), Address::new(Low, High)),
+//!# ]);
+//! let pins = expanders.pins();
+//!
+//! // Device 1, Bank0, Pin1
+//! let mut pin17 = pins.get_pin(17).unwrap();
+//! assert!(pin17.is_high().unwrap());
+//!
+//! // Device 0, Bank1, Pin4
+//! let mut pin12 = pins.get_refreshable_pin(12).unwrap().into_output_pin(PinState::Low).unwrap();
+//! pin12.set_high().unwrap();
+//!
+//! // Writes the output state of all devices
+//! pins.update_all().unwrap();
+//! ```
+//! ## Bulk refresh/update
+//! ```
+//!# use core::cell::RefCell;
+//!# use embedded_hal_bus::i2c::RefCellDevice;
+//!# use pca9539::address::Address;
+//!# use pca9539::address::Strap::{High, Low};
+//!# use pca9539::example::DummyI2CBus;
+//!# use pca9539::expander::PCA9539;
+//!# use pca9539::multi::Expanders;
+//!#
+//!# let i2c_bus = RefCell::new(DummyI2CBus::default());
+//!# let mut expanders = Expanders::new([
+//!#     PCA9539::new(RefCellDevice::new(&i2c_bus), Address::new(Low, Low)),
+//!#     PCA9539::new(RefCellDevice::new(&i2c_bus), Address::new(Low, High)),
+//!# ]);
+//! // Refreshes the input state of all banks of all devices
+//! expanders.refresh_input_state_all().unwrap();
+//! assert!(expanders.is_pin_input_high(17).unwrap());
+//!
+//! // Writes the output state of all banks of all devices
+//! expanders.write_output_state_all().unwrap();
+//! ```
+use crate::error::Error;
+use crate::expander::{Bank, Mode, PinID, PCA9539};
+#[cfg(feature = "cortex-m")]
+use crate::guard::CsMutexGuard;
+#[cfg(feature = "spin")]
+use crate::guard::SpinGuard;
+use crate::guard::{LockFreeGuard, RefGuard};
+use crate::pins::{Input, Pin, RefreshMode, RegularAccessMode};
+use core::borrow::BorrowMut;
+use core::cell::RefCell;
+use core::marker::PhantomData;
+use embedded_hal::i2c::{I2c, SevenBitAddress};
+#[cfg(feature = "spin")]
+use spin::Mutex as SpinMutex;
+
+/// Number of pins per device
+pub const PINS_PER_DEVICE: usize = 16;
+
+const BANKS: [Bank; 2] = [Bank::Bank0, Bank::Bank1];
+
+/// Logical GPIO space of `N` expanders
+/// `E` is either an owned [PCA9539] or a mutable reference.
+pub struct Expanders<B, E, const N: usize>
+where
+    B: I2c<SevenBitAddress>,
+    E: BorrowMut<PCA9539<B>>,
+{
+    devices: [E; N],
+    bus: PhantomData<fn(B) -> B>,
+}
+
+/// Container for fetching individual pins of multiple expanders by flat index
+pub struct MultiPins<B: I2c<SevenBitAddress>, R: RefGuard<B>, const N: usize> {
+    guards: [R; N],
+    bus: PhantomData<fn(B) -> B>,
+}
+
+/// Error of operations on multiple expanders
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MultiError<E> {
+    /// The given flat pin index exceeds the number of pins
+    InvalidIndex(usize),
+    /// Operation failed on the device with the given index
+    DeviceError(usize, E),
+}
+
+/// Splits the given flat pin index into device index, bank and pin ID
+pub fn locate(index: usize) -> (usize, Bank, PinID) {
+    let device = index / PINS_PER_DEVICE;
+    let bank = BANKS[(index % PINS_PER_DEVICE) / 8];
+
+    (device, bank, PinID::ALL[index % 8])
+}
+
+impl<B, E, const N: usize> Expanders<B, E, N>
+where
+    B: I2c<SevenBitAddress>,
+    E: BorrowMut<PCA9539<B>>,
+{
+    /// Total number of pins
+    pub const PIN_COUNT: usize = N * PINS_PER_DEVICE;
+
+    pub fn new(devices: [E; N]) -> Self {
+        Self {
+            devices,
+            bus: PhantomData,
+        }
+    }
+
+    /// Returns the expander with the given device index
+    pub fn device(&mut self, index: usize) -> Option<&mut PCA9539<B>> {
+        self.devices.get_mut(index).map(|device| device.borrow_mut())
+    }
+
+    /// Releases the expanders
+    pub fn release(self) -> [E; N] {
+        self.devices
+    }
+
+    /// Returns a pins container without using any locks
+    /// s. [PCA9539::pins()] for more details
+    pub fn pins(&mut self) -> MultiPins<B, LockFreeGuard<'_, B>, N> {
+        MultiPins::new(
+            self.devices
+                .each_mut()
+                .map(|device| LockFreeGuard::new(RefCell::new(device.borrow_mut()))),
+        )
+    }
+
+    /// Returns a pins container using Mutex based on critical sections
+    /// s. [PCA9539::pins_cs_mutex()] for more details
+    #[cfg(feature = "cortex-m")]
+    pub fn pins_cs_mutex(&mut self) -> MultiPins<B, CsMutexGuard<'_, B>, N> {
+        MultiPins::new(
+            self.devices
+                .each_mut()
+                .map(|device| CsMutexGuard::new(RefCell::new(device.borrow_mut()))),
+        )
+    }
+
+    /// Returns a pins container using a spin mutex
+    /// s. [PCA9539::pins_spin_mutex()] for more details
+    #[cfg(feature = "spin")]
+    pub fn pins_spin_mutex(&mut self) -> MultiPins<B, SpinGuard<'_, B>, N> {
+        MultiPins::new(
+            self.devices
+                .each_mut()
+                .map(|device| SpinGuard::new(SpinMutex::new(RefCell::new(device.borrow_mut())))),
+        )
+    }
+
+    /// Switches the given pin to the input/output mode
+    pub fn set_mode(&mut self, index: usize, mode: Mode) -> Result<(), MultiError<Error<B::Error>>> {
+        let (device, bank, id) = self.locate(index)?;
+
+        self.devices[device]
+            .borrow_mut()
+            .set_mode(bank, id, mode)
+            .map_err(|error| MultiError::DeviceError(device, error))
+    }
+
+    /// Sets the cached output state of the given pin
+    /// Note: This just updates the internal register, to make the changes effective,
+    /// an additional call to `write_output_state_all()` is needed.
+    pub fn set_state(&mut self, index: usize, is_high: bool) -> Result<(), MultiError<Error<B::Error>>> {
+        let (device, bank, id) = self.locate(index)?;
+
+        self.devices[device]
+            .borrow_mut()
+            .set_state(bank, id, is_high)
+            .map_err(|error| MultiError::DeviceError(device, error))
+    }
+
+    /// Returns true if the cached input state of the given pin is high
+    pub fn is_pin_input_high(&self, index: usize) -> Result<bool, MultiError<Error<B::Error>>> {
+        let (device, bank, id) = self.locate(index)?;
+
+        Ok(self.devices[device].borrow().is_pin_input_high(bank, id))
+    }
+
+    /// Returns true if the cached output state of the given pin is high
+    pub fn is_pin_output_high(&self, index: usize) -> Result<bool, MultiError<Error<B::Error>>> {
+        let (device, bank, id) = self.locate(index)?;
+
+        Ok(self.devices[device].borrow().is_pin_output_high(bank, id))
+    }
+
+    /// Refreshes the input state of all banks of all devices
+    pub fn refresh_input_state_all(&mut self) -> Result<(), MultiError<Error<B::Error>>> {
+        for (index, device) in self.devices.iter_mut().enumerate() {
+            for bank in BANKS {
+                device
+                    .borrow_mut()
+                    .refresh_input_state(bank)
+                    .map_err(|error| MultiError::DeviceError(index, error))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the output state of all banks of all devices
+    pub fn write_output_state_all(&mut self) -> Result<(), MultiError<Error<B::Error>>> {
+        for (index, device) in self.devices.iter_mut().enumerate() {
+            for bank in BANKS {
+                device
+                    .borrow_mut()
+                    .write_output_state(bank)
+                    .map_err(|error| MultiError::DeviceError(index, error))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// (Re)writes the internal state of all devices, s. [PCA9539::sync_state()]
+    pub fn sync_state(&mut self) -> Result<(), MultiError<Error<B::Error>>> {
+        for (index, device) in self.devices.iter_mut().enumerate() {
+            device
+                .borrow_mut()
+                .sync_state()
+                .map_err(|error| MultiError::DeviceError(index, error))?;
+        }
+
+        Ok(())
+    }
+
+    fn locate<T>(&self, index: usize) -> Result<(usize, Bank, PinID), MultiError<T>> {
+        if index >= Self::PIN_COUNT {
+            return Err(MultiError::InvalidIndex(index));
+        }
+
+        Ok(locate(index))
+    }
+}
+
+impl<B: I2c<SevenBitAddress>, R: RefGuard<B>, const N: usize> MultiPins<B, R, N> {
+    pub fn new(guards: [R; N]) -> Self {
+        Self {
+            guards,
+            bus: PhantomData,
+        }
+    }
+
+    /// Returns an individual pin, which state gets updated synchronously
+    /// Returns None if the index exceeds the number of pins.
+    /// **The library does not prevent multiple parallel instances of the same pin.**
+    pub fn get_pin(&self, index: usize) -> Option<Pin<'_, B, R, Input, RegularAccessMode>> {
+        let (device, bank, id) = self.locate(index)?;
+        Some(Pin::regular(&self.guards[device], bank, id))
+    }
+
+    /// Returns an individual pin, which is using a cached state
+    /// Returns None if the index exceeds the number of pins.
+    /// **The library does not prevent multiple parallel instances of the same pin.**
+    pub fn get_refreshable_pin(&self, index: usize) -> Option<Pin<'_, B, R, Input, RefreshMode>> {
+        let (device, bank, id) = self.locate(index)?;
+        Some(Pin::refreshable(&self.guards[device], bank, id))
+    }
+
+    /// Refreshes the input state of all pins of all devices
+    pub fn refresh_all(&self) -> Result<(), MultiError<Error<B::Error>>> {
+        for (index, guard) in self.guards.iter().enumerate() {
+            guard
+                .access(|expander| {
+                    expander.refresh_input_state(Bank::Bank0)?;
+                    expander.refresh_input_state(Bank::Bank1)
+                })
+                .map_err(|error| MultiError::DeviceError(index, error))?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the output state of all pins of all devices
+    /// Also reports the last change of a refresh mode pin rejected since the previous update, e.g. by
+    /// an [interlock](crate::interlock), s. [cache module](crate::cache)
+    pub fn update_all(&self) -> Result<(), MultiError<Error<B::Error>>> {
+        for (index, guard) in self.guards.iter().enumerate() {
+            guard
+                .access(|expander| {
+                    expander.write_output_state(Bank::Bank0)?;
+                    expander.write_output_state(Bank::Bank1)?;
+
+                    match expander.take_deferred_error() {
+                        Some(error) => Err(error),
+                        None => Ok(()),
+                    }
+                })
+                .map_err(|error| MultiError::DeviceError(index, error))?;
+        }
+
+        Ok(())
+    }
+
+    fn locate(&self, index: usize) -> Option<(usize, Bank, PinID)> {
+        if index >= N * PINS_PER_DEVICE {
+            return None;
+        }
+
+        Some(locate(index))
+    }
+}
@@ -0,0 +1,203 @@
+//! # Board pin maps
+//!
+//! The [pin_map!](crate::pin_map!) macro generates a struct of named, correctly typed [pins](crate::pins::Pin)
+//! for a board, e.g. `led_status` instead of `(Bank1, Pin3)`. Each signal is declared by name, bank,
+//! pin, direction and, for outputs, the initial state:
+//!
+//! | Declaration                                   | Type of field                   |
+//! |-----------------------------------------------|---------------------------------|
+//! | `name: input(Bank0, Pin1)`                    | Input pin, regular access mode  |
+//! | `name: output(Bank0, Pin1, Low)`              | Output pin, regular access mode |
+//! | `name: refreshable input(Bank0, Pin1)`        | Input pin, refresh access mode  |
+//! | `name: refreshable output(Bank0, Pin1, High)` | Output pin, refresh access mode |
+//!
+//! The generated `new()` function fetches all pins of a [Pins](crate::pins::Pins) container and
+//! switches them to the declared mode and initial state. Besides that, a [Config] constant with
+//! the matching initial configuration is generated, using the signal names as labels.
+//!
+//! Mis-wiring is caught at compile time: Using an input as output (or vice versa) fails type
+//! checking, assigning the same pin twice fails constant evaluation.
+//!
+//! ## Example
+//! ```
+//! use embedded_hal::digital::{InputPin, OutputPin};
+//! use pca9539::address::Address;
+//! use pca9539::example::DummyI2CBus;
+//! use pca9539::expander::PCA9539;
+//! use pca9539::pin_map;
+//!
+//! pin_map! {
+//!     /// Pins of the main board
+//!     pub struct BoardPins {
+//!         led_status: output(Bank1, Pin3, Low),
+//!         relay_main: refreshable output(Bank0, Pin0, High),
+//!         sw_arm: input(Bank0, Pin7),
+//!     }
+//!
+//!     /// Initial configuration of the main board
+//!     pub const BOARD_CONFIG: Config;
+//! }
+//!
+//! let mut expander = PCA9539::new(DummyI2CBus::default(), Address::default());
+//! let pins = expander.pins();
+//! let mut board = BoardPins::new(&pins).unwrap();
+//!
+//! board.led_status.set_high().unwrap();
+//! let is_armed = board.sw_arm.is_high().unwrap();
+//!
+//! assert!(BOARD_CONFIG.find("sw_arm").is_some());
+//! ```
+//! Assigning a pin twice does not compile:
+//! ```compile_fail
+//! pca9539::pin_map! {
+//!     pub struct BoardPins {
+//!         led_status: output(Bank1, Pin3, Low),
+//!         led_error: output(Bank1, Pin3, Low),
+//!     }
+//!
+//!     pub const BOARD_CONFIG: Config;
+//! }
+//! ```
+#[cfg(doc)]
+use crate::config::Config;
+
+/// Re-exports used by the expansion of [pin_map!](crate::pin_map!)
+#[doc(hidden)]
+pub mod private {
+    pub use embedded_hal::digital::PinState;
+    pub use embedded_hal::i2c::{I2c, SevenBitAddress};
+}
+
+/// Panics during constant evaluation if any pin is assigned twice
+/// Pins are identified by bank * 8 + pin ID.
+#[doc(hidden)]
+pub const fn assert_unique(pins: &[u8]) {
+    let mut index = 0;
+
+    while index < pins.len() {
+        let mut other = index + 1;
+
+        while other < pins.len() {
+            if pins[index] == pins[other] {
+                panic!("pin_map!: pin assigned twice");
+            }
+
+            other += 1;
+        }
+
+        index += 1;
+    }
+}
+
+/// Generates a struct of named, typed pins and the matching initial configuration, s. [pin_map module](mod@crate::pin_map)
+#[macro_export]
+macro_rules! pin_map {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $field:ident : $($kind:ident)+ ($bank:ident, $pin:ident $(, $state:ident)?)
+            ),* $(,)?
+        }
+
+        $(#[$config_meta:meta])*
+        $config_vis:vis const $config:ident : Config;
+    ) => {
+        $(#[$meta])*
+        $vis struct $name<'a, B, R>
+        where
+            B: $crate::pin_map::private::I2c<$crate::pin_map::private::SevenBitAddress>,
+            R: $crate::guard::RefGuard<B>,
+        {
+            $(
+                $(#[$field_meta])*
+                pub $field: $crate::pin_map!(@type 'a, B, R, $($kind)+),
+            )*
+        }
+
+        impl<'a, B, R> $name<'a, B, R>
+        where
+            B: $crate::pin_map::private::I2c<$crate::pin_map::private::SevenBitAddress>,
+            R: $crate::guard::RefGuard<B>,
+        {
+            /// Fetches all pins and switches them to the declared mode and initial state
+            pub fn new(pins: &'a $crate::pins::Pins<B, R>) -> Result<Self, $crate::error::Error<B::Error>> {
+                Ok(Self {
+                    $(
+                        $field: $crate::pin_map!(
+                            @init pins,
+                            $crate::expander::Bank::$bank,
+                            $crate::expander::PinID::$pin,
+                            $($kind)+ $(, $state)?
+                        ),
+                    )*
+                })
+            }
+        }
+
+        const _: () = $crate::pin_map::assert_unique(&[
+            $(($crate::expander::Bank::$bank as u8) * 8 + $crate::expander::PinID::$pin as u8),*
+        ]);
+
+        $(#[$config_meta])*
+        $config_vis const $config: $crate::config::Config = $crate::config::Config::new()
+            $(
+                .pin(
+                    $crate::expander::Bank::$bank,
+                    $crate::expander::PinID::$pin,
+                    $crate::pin_map!(@config $($kind)+ $(, $state)?).label(stringify!($field)),
+                )
+            )*;
+    };
+
+    (@type $lt:lifetime, $bus:ident, $guard:ident, input) => {
+        $crate::pins::Pin<$lt, $bus, $guard, $crate::pins::Input, $crate::pins::RegularAccessMode>
+    };
+    (@type $lt:lifetime, $bus:ident, $guard:ident, output) => {
+        $crate::pins::Pin<$lt, $bus, $guard, $crate::pins::Output, $crate::pins::RegularAccessMode>
+    };
+    (@type $lt:lifetime, $bus:ident, $guard:ident, refreshable input) => {
+        $crate::pins::Pin<$lt, $bus, $guard, $crate::pins::Input, $crate::pins::RefreshMode>
+    };
+    (@type $lt:lifetime, $bus:ident, $guard:ident, refreshable output) => {
+        $crate::pins::Pin<$lt, $bus, $guard, $crate::pins::Output, $crate::pins::RefreshMode>
+    };
+
+    (@init $pins:ident, $bank:expr, $pin:expr, input) => {
+        $pins.get_pin($bank, $pin).into_input_pin()?
+    };
+    (@init $pins:ident, $bank:expr, $pin:expr, output, $state:ident) => {
+        $pins
+            .get_pin($bank, $pin)
+            .into_output_pin($crate::pin_map::private::PinState::$state)?
+    };
+    (@init $pins:ident, $bank:expr, $pin:expr, refreshable input) => {
+        $pins.get_refreshable_pin($bank, $pin).into_input_pin()?
+    };
+    (@init $pins:ident, $bank:expr, $pin:expr, refreshable output, $state:ident) => {
+        $pins
+            .get_refreshable_pin($bank, $pin)
+            .into_output_pin($crate::pin_map::private::PinState::$state)?
+    };
+
+    (@config input) => {
+        $crate::config::PinConfig::input()
+    };
+    (@config refreshable input) => {
+        $crate::config::PinConfig::input()
+    };
+    (@config output, $state:ident) => {
+        $crate::config::PinConfig::output($crate::pin_map!(@level $state))
+    };
+    (@config refreshable output, $state:ident) => {
+        $crate::config::PinConfig::output($crate::pin_map!(@level $state))
+    };
+
+    (@level High) => {
+        true
+    };
+    (@level Low) => {
+        false
+    };
+}
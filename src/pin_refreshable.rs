@@ -1,6 +1,8 @@
-use crate::expander::{Bank, Mode, PinID, RefreshInputError};
+use crate::error::Error;
+use crate::expander::{Bank, Mode, PinID};
 use crate::guard::RefGuard;
 use crate::pins::{Input, Output, Pin, PinMode, RefreshMode};
+use crate::register::Register;
 use core::convert::Infallible;
 use core::marker::PhantomData;
 use embedded_hal::digital::{ErrorType, InputPin, OutputPin, PinState, StatefulOutputPin};
@@ -45,14 +47,8 @@ where
     }
 
     /// Refreshes the input state of the given bank
-    fn refresh(&self, bank: Bank) -> Result<(), RefreshInputError<B>> {
-        let mut result = Ok(());
-
-        self.expander.access(|expander| {
-            result = expander.refresh_input_state(bank);
-        });
-
-        result
+    fn refresh(&self, bank: Bank) -> Result<(), Error<B::Error>> {
+        self.expander.access(|expander| expander.refresh_input_state(bank))
     }
 }
 
@@ -61,7 +57,7 @@ where
     B: I2c<SevenBitAddress>,
     R: RefGuard<B>,
 {
-    type Error = RefreshInputError<B>;
+    type Error = Error<B::Error>;
 
     /// Refreshes the input state of all pins of the same bank
     fn refresh_bank(&self) -> Result<(), Self::Error> {
@@ -80,7 +76,7 @@ where
     B: I2c<SevenBitAddress>,
     R: RefGuard<B>,
 {
-    type Error = B::Error;
+    type Error = Error<B::Error>;
 
     /// Updates the output state of all pins of the same bank
     fn update_bank(&self) -> Result<(), Self::Error> {
@@ -100,14 +96,17 @@ where
     R: RefGuard<B>,
 {
     /// Writes the output state of the given bank
-    fn update(&self, bank: Bank) -> Result<(), B::Error> {
-        let mut result = Ok(());
-
+    /// Also reports the last change rejected since the previous update, e.g. by a [pin lock](crate::lock),
+    /// the [safe state](crate::safe_state) or an [interlock](crate::interlock)
+    fn update(&self, bank: Bank) -> Result<(), Error<B::Error>> {
         self.expander.access(|expander| {
-            result = expander.write_output_state(bank);
-        });
+            expander.write_output_state(bank)?;
 
-        result
+            match expander.take_deferred_error() {
+                Some(error) => Err(error),
+                None => Ok(()),
+            }
+        })
     }
 }
 
@@ -125,13 +124,7 @@ where
     R: RefGuard<B>,
 {
     fn is_high(&mut self) -> Result<bool, Self::Error> {
-        let mut state = false;
-
-        self.expander.access(|expander| {
-            state = expander.is_pin_input_high(self.bank, self.id);
-        });
-
-        Ok(state)
+        Ok(self.expander.cache().is_set(Register::input(self.bank), self.id))
     }
 
     fn is_low(&mut self) -> Result<bool, Self::Error> {
@@ -161,10 +154,8 @@ where
     }
 
     fn set_state(&mut self, state: PinState) -> Result<(), Self::Error> {
-        self.expander.access(|expander| {
-            expander.set_state(self.bank, self.id, state == PinState::High);
-        });
-
+        // Rejections by safe state lock, pin locks and interlocks are reported by the next update
+        self.expander.cache().set_state(self.bank, self.id, state == PinState::High);
         Ok(())
     }
 }
@@ -183,13 +174,25 @@ where
     }
 }
 
+impl<B, R> Pin<'_, B, R, Output, RefreshMode>
+where
+    B: I2c<SevenBitAddress>,
+    R: RefGuard<B>,
+{
+    /// Returns the actual pin level based on the cached input register
+    /// The cache is updated by refreshing input pins of the same bank or by the [output monitor](crate::monitor).
+    pub fn actual_level(&self) -> PinState {
+        PinState::from(self.expander.cache().is_pin_level_high(self.bank, self.id))
+    }
+}
+
 impl<'a, B, M, R> Pin<'a, B, R, M, RefreshMode>
 where
     B: I2c<SevenBitAddress>,
     R: RefGuard<B>,
     M: PinMode,
 {
-    pub fn into_input_pin(self) -> Result<Pin<'a, B, R, Input, RefreshMode>, B::Error> {
+    pub fn into_input_pin(self) -> Result<Pin<'a, B, R, Input, RefreshMode>, Error<B::Error>> {
         self.change_mode(Mode::Input)?;
 
         Ok(Pin {
@@ -202,7 +205,7 @@ where
         })
     }
 
-    pub fn into_output_pin(self, state: PinState) -> Result<Pin<'a, B, R, Output, RefreshMode>, B::Error> {
+    pub fn into_output_pin(self, state: PinState) -> Result<Pin<'a, B, R, Output, RefreshMode>, Error<B::Error>> {
         self.change_mode(Mode::Output)?;
 
         let mut pin = Pin {
@@ -1,4 +1,5 @@
-use crate::expander::{Bank, Mode, PinID, RefreshInputError};
+use crate::error::Error;
+use crate::expander::{Bank, Mode, PinID};
 use crate::guard::RefGuard;
 use crate::pins::{Input, Output, Pin, PinMode, RegularAccessMode};
 use core::marker::PhantomData;
@@ -27,7 +28,7 @@ where
     B: I2c<SevenBitAddress>,
     R: RefGuard<B>,
 {
-    type Error = RefreshInputError<B>;
+    type Error = Error<B::Error>;
 }
 
 impl<B, R> InputPin for Pin<'_, B, R, Input, RegularAccessMode>
@@ -36,16 +37,10 @@ where
     R: RefGuard<B>,
 {
     fn is_high(&mut self) -> Result<bool, Self::Error> {
-        let mut result = Ok(false);
-
         self.expander.access(|expander| {
-            result = match expander.refresh_input_state(self.bank) {
-                Ok(_) => Ok(expander.is_pin_input_high(self.bank, self.id)),
-                Err(error) => Err(error),
-            }
-        });
-
-        result
+            expander.refresh_input_state(self.bank)?;
+            Ok(expander.is_pin_input_high(self.bank, self.id))
+        })
     }
 
     fn is_low(&mut self) -> Result<bool, Self::Error> {
@@ -58,7 +53,7 @@ where
     B: I2c<SevenBitAddress>,
     R: RefGuard<B>,
 {
-    type Error = RefreshInputError<B>;
+    type Error = Error<B::Error>;
 }
 
 impl<B, R> OutputPin for Pin<'_, B, R, Output, RegularAccessMode>
@@ -75,14 +70,10 @@ where
     }
 
     fn set_state(&mut self, state: PinState) -> Result<(), Self::Error> {
-        let mut result = Ok(());
-
         self.expander.access(|expander| {
-            expander.set_state(self.bank, self.id, state == PinState::High);
-            result = expander.write_output_state(self.bank).map_err(RefreshInputError::WriteError);
-        });
-
-        result
+            expander.set_state(self.bank, self.id, state == PinState::High)?;
+            expander.write_output_state(self.bank)
+        })
     }
 }
 
@@ -102,13 +93,28 @@ where
     }
 }
 
+impl<B, R> Pin<'_, B, R, Output, RegularAccessMode>
+where
+    B: I2c<SevenBitAddress>,
+    R: RefGuard<B>,
+{
+    /// Reads back the actual pin level from the input register
+    /// Differing from the output state indicates a short or overload, s. [monitor module](crate::monitor)
+    pub fn actual_level(&self) -> Result<PinState, Error<B::Error>> {
+        self.expander.access(|expander| {
+            expander.refresh_input_state(self.bank)?;
+            Ok(PinState::from(expander.is_pin_level_high(self.bank, self.id)))
+        })
+    }
+}
+
 impl<'a, B, M, R> Pin<'a, B, R, M, RegularAccessMode>
 where
     B: I2c<SevenBitAddress>,
     R: RefGuard<B>,
     M: PinMode,
 {
-    pub fn into_input_pin(self) -> Result<Pin<'a, B, R, Input, RegularAccessMode>, B::Error> {
+    pub fn into_input_pin(self) -> Result<Pin<'a, B, R, Input, RegularAccessMode>, Error<B::Error>> {
         self.change_mode(Mode::Input)?;
 
         Ok(Pin {
@@ -124,8 +130,8 @@ where
     pub fn into_output_pin(
         self,
         state: PinState,
-    ) -> Result<Pin<'a, B, R, Output, RegularAccessMode>, RefreshInputError<B>> {
-        self.change_mode(Mode::Output).map_err(RefreshInputError::WriteError)?;
+    ) -> Result<Pin<'a, B, R, Output, RegularAccessMode>, Error<B::Error>> {
+        self.change_mode(Mode::Output)?;
 
         let mut pin = Pin {
             expander: self.expander,
@@ -13,13 +13,15 @@
 //! Individual pins can be fetched using [PCA9539](crate::expander::PCA9539) instance.
 //! Different concurrency models are supported, see [Concurrency](#Concurrency) section for more details.
 //! ```
+//! use pca9539::address::Address;
+//! use pca9539::address::Strap::Low;
 //! use pca9539::example::DummyI2CBus;
 //! use pca9539::expander::Bank::Bank0;
 //! use pca9539::expander::PCA9539;
 //! use pca9539::expander::PinID::Pin1;
 //!
 //! let i2c_bus = DummyI2CBus::default();
-//! let mut  expander = PCA9539::new(i2c_bus, 0x74);
+//! let mut  expander = PCA9539::new(i2c_bus, Address::new(Low, Low));
 //! let pins = expander.pins();
 //! ```
 //! ## State management modes
@@ -27,6 +29,7 @@
 //! The following examples demonstrate using the synchronous regular access mode.
 //! Regular access mode is used when calling `get_pin()` method.
 //! ```
+//!# use pca9539::address::Address;
 //!# use pca9539::example::DummyI2CBus;
 //!# use pca9539::expander::Bank::{Bank0, Bank1};
 //!# use pca9539::expander::PCA9539;
@@ -34,7 +37,7 @@
 //!# use embedded_hal::digital::{InputPin, PinState, OutputPin};
 //!#
 //!# let i2c_bus = DummyI2CBus::default();
-//!# let mut  expander = PCA9539::new(i2c_bus, 0x74);
+//!# let mut  expander = PCA9539::new(i2c_bus, Address::default());
 //! let pins = expander.pins();
 //! let mut pin12 = pins.get_pin(Bank1, Pin2);
 //! let mut  pin04 = pins.get_pin(Bank0, Pin4).into_output_pin(PinState::Low).unwrap();
@@ -54,8 +57,10 @@
 //! The state is always updated for all pins or pins of the same bank.
 //!
 //! As `is_high()` and `is_low()` are just acting on cached state, calls of this method can not fail.
+//! Besides that, they do not lock the concurrency guard, s. [cache module](crate::cache).
 //! #### Input example
 //! ```
+//!# use pca9539::address::Address;
 //!# use pca9539::example::DummyI2CBus;
 //!# use pca9539::expander::Bank::{Bank0, Bank1};
 //!# use pca9539::expander::PCA9539;
@@ -64,7 +69,7 @@
 //!# use pca9539::pins::RefreshableInputPin;
 //!#
 //!# let i2c_bus = DummyI2CBus::default();
-//!# let mut  expander = PCA9539::new(i2c_bus, 0x74);
+//!# let mut  expander = PCA9539::new(i2c_bus, Address::default());
 //! let pins = expander.pins();
 //! let mut pin00 = pins.get_refreshable_pin(Bank0, Pin0);
 //! let mut pin10 = pins.get_refreshable_pin(Bank1, Pin0);
@@ -81,6 +86,7 @@
 //! ```
 //! #### Output example
 //! ```
+//!# use pca9539::address::Address;
 //!# use pca9539::example::DummyI2CBus;
 //!# use pca9539::expander::Bank::{Bank0, Bank1};
 //!# use pca9539::expander::PCA9539;
@@ -89,7 +95,7 @@
 //!# use pca9539::pins::RefreshableOutputPin;
 //!#
 //!# let i2c_bus = DummyI2CBus::default();
-//!# let mut  expander = PCA9539::new(i2c_bus, 0x74);
+//!# let mut  expander = PCA9539::new(i2c_bus, Address::default());
 //! let pins = expander.pins();
 //! let mut pin00 = pins.get_refreshable_pin(Bank0, Pin0).into_output_pin(PinState::Low).unwrap();
 //! let mut pin10 = pins.get_refreshable_pin(Bank1, Pin0).into_output_pin(PinState::Low).unwrap();
@@ -117,11 +123,12 @@
 //! The downside is, that these pins are neither Send or Sync, so can only be used in single-threaded
 //! and interrupt-free applications
 //! ```
+//!# use pca9539::address::Address;
 //!# use pca9539::example::DummyI2CBus;
 //!# use pca9539::expander::PCA9539;
 //!#
 //!# let i2c_bus = DummyI2CBus::default();
-//!# let mut  expander = PCA9539::new(i2c_bus, 0x74);
+//!# let mut  expander = PCA9539::new(i2c_bus, Address::default());
 //! let pins = expander.pins();
 //! ```
 //!
@@ -132,11 +139,12 @@
 //! *Requires activation of `cortex-m` feature*
 //!
 //! ```
+//!# use pca9539::address::Address;
 //!# use pca9539::example::DummyI2CBus;
 //!# use pca9539::expander::PCA9539;
 //!#
 //!# let i2c_bus = DummyI2CBus::default();
-//!# let mut  expander = PCA9539::new(i2c_bus, 0x74);
+//!# let mut  expander = PCA9539::new(i2c_bus, Address::default());
 //!# #[cfg(feature = "cortex-m")]
 //! let pins = expander.pins_cs_mutex();
 //! ```
@@ -150,11 +158,12 @@
 //! *Requires activation of `spin` feature*
 //!
 //! ```
+//!# use pca9539::address::Address;
 //!# use pca9539::example::DummyI2CBus;
 //!# use pca9539::expander::PCA9539;
 //!#
 //!# let i2c_bus = DummyI2CBus::default();
-//!# let mut  expander = PCA9539::new(i2c_bus, 0x74);
+//!# let mut  expander = PCA9539::new(i2c_bus, Address::default());
 //!# #[cfg(feature = "spin")]
 //! let pins = expander.pins_spin_mutex();
 //! ```
@@ -164,6 +173,7 @@
 //!
 //! If called the method sends the state of **all banks**, not just the bank of the given pin.
 //! ```
+//!# use pca9539::address::Address;
 //!# use pca9539::example::DummyI2CBus;
 //!# use pca9539::expander::Bank::{Bank0, Bank1};
 //!# use pca9539::expander::PCA9539;
@@ -173,14 +183,16 @@
 //!# use crate::pca9539::sync_state::SyncState;
 //!#
 //!# let i2c_bus = DummyI2CBus::default();
-//!# let mut  expander = PCA9539::new(i2c_bus, 0x74);
+//!# let mut  expander = PCA9539::new(i2c_bus, Address::default());
 //!# let pins = expander.pins();
 //! let pin = pins.get_refreshable_pin(Bank0, Pin0);
 //! pin.sync_state().unwrap();
 //! ```
+use crate::error::Error;
 use crate::expander::{Bank, Mode, PinID};
 use crate::guard::RefGuard;
 pub use crate::pin_refreshable::{RefreshableInputPin, RefreshableOutputPin};
+use crate::register::Register;
 use crate::sync_state::SyncState;
 use core::marker::PhantomData;
 use embedded_hal::i2c::{I2c, SevenBitAddress};
@@ -199,6 +211,11 @@ impl<B: I2c<SevenBitAddress>, R: RefGuard<B>> Pins<B, R> {
         }
     }
 
+    /// Returns the concurrency guard, e.g. for accessing the expander by [try_access()](RefGuard::try_access)
+    pub fn guard(&self) -> &R {
+        &self.guard
+    }
+
     /// Returns an individual pin, which state gets updated synchronously
     /// **The library does not prevent multiple parallel instances of the same pin.**
     pub fn get_pin(&self, bank: Bank, id: PinID) -> Pin<'_, B, R, Input, RegularAccessMode> {
@@ -265,14 +282,9 @@ where
     A: AccessMode,
 {
     /// Reverses/Resets the input polarity
-    pub fn invert_polarity(&self, invert: bool) -> Result<(), B::Error> {
-        let mut result = Ok(());
-
-        self.expander.access(|expander| {
-            result = expander.reverse_polarity(self.bank, self.id, invert);
-        });
-
-        result
+    pub fn invert_polarity(&self, invert: bool) -> Result<(), Error<B::Error>> {
+        self.expander
+            .access(|expander| expander.reverse_polarity(self.bank, self.id, invert))
     }
 }
 
@@ -283,13 +295,9 @@ where
     A: AccessMode,
 {
     /// Returns the current output state, this logic is independent from access mode, as it acts in both
-    /// cases on cached register state
+    /// cases on cached register state. Does not lock the guard, s. [cache module](crate::cache).
     pub(crate) fn is_pin_output_high(&self) -> bool {
-        let mut is_high = false;
-        self.expander
-            .access(|expander| is_high = expander.is_pin_output_high(self.bank, self.id));
-
-        is_high
+        self.expander.cache().is_set(Register::output(self.bank), self.id)
     }
 }
 
@@ -301,14 +309,8 @@ where
     A: AccessMode,
 {
     /// Switches the pin to the given mode
-    pub(crate) fn change_mode(&self, mode: Mode) -> Result<(), B::Error> {
-        let mut result = Ok(());
-
-        self.expander.access(|expander| {
-            result = expander.set_mode(self.bank, self.id, mode);
-        });
-
-        result
+    pub(crate) fn change_mode(&self, mode: Mode) -> Result<(), Error<B::Error>> {
+        self.expander.access(|expander| expander.set_mode(self.bank, self.id, mode))
     }
 }
 
@@ -319,15 +321,9 @@ where
     M: PinMode,
     A: AccessMode,
 {
-    type Error = B::Error;
-
-    fn sync_state(&self) -> Result<(), B::Error> {
-        let mut result = Ok(());
-
-        self.expander.access(|expander| {
-            result = expander.sync_state();
-        });
+    type Error = Error<B::Error>;
 
-        result
+    fn sync_state(&self) -> Result<(), Error<B::Error>> {
+        self.expander.access(|expander| expander.sync_state())
     }
 }
@@ -0,0 +1,102 @@
+//! # Bus probing
+//!
+//! Detects which expanders are present on the bus, e.g. during board bring-up or as part of a
+//! self-test.
+//!
+//! Each of the four PCA9539 addresses is checked. A device is considered a PCA9539, if it
+//! acknowledges its address and the polarity inversion register of Bank0 reads back a written test
+//! pattern. Afterwards the original register value is restored.
+//!
+//! If a bus error occurs after writing the test pattern, restoring the original value is still
+//! attempted before returning the error. If restoring fails as well, the polarity of Bank0 may
+//! remain inverted, so its input levels are read inverted until the polarity inversion register is
+//! written again, e.g. by applying a [configuration](crate::config).
+//!
+//! The polarity inversion register just affects the input register value, so probing does not
+//! disturb any outputs. In contrast, writing a test pattern to the configuration register would
+//! temporarily change the pin directions.
+//!
+//! ## Interrupt side effect
+//! Every register affects either the pins or the input register, so there is no side-effect-free
+//! test pattern. Inverting the polarity flips the input register value of all Bank0 pins, which the
+//! device may signal as input change by asserting INT. So interrupt handlers on the (possibly
+//! shared) INT line may be woken up while probing. After restoring the original polarity, the input
+//! register matches its previous value again and INT is released. Disable these interrupts while
+//! probing or make sure handlers tolerate spurious interrupts, e.g. the
+//! [InterruptDispatcher](crate::interrupt::InterruptDispatcher) reports no change then.
+//!
+//! ## Example
+//! ```
+//! use pca9539::example::DummyI2CBus;
+//! use pca9539::probe::probe;
+//!
+//! let mut i2c_bus = DummyI2CBus::default();
+//!
+//! for address in probe(&mut i2c_bus).unwrap() {
+//!     // Device found at address.value()
+//! }
+//! ```
+use crate::address::Address;
+use crate::register::Register;
+use embedded_hal::i2c::{Error, ErrorKind, I2c, SevenBitAddress};
+use heapless::Vec;
+
+/// Probes all PCA9539 addresses and returns the addresses of the detected devices
+/// Addresses not acknowledged are skipped, any other bus error is returned.
+/// Detected devices may assert INT while probing, s. [module documentation](crate::probe#interrupt-side-effect).
+pub fn probe<B: I2c<SevenBitAddress>>(bus: &mut B) -> Result<Vec<Address, 4>, B::Error> {
+    let mut devices = Vec::new();
+
+    for address in Address::ALL {
+        if probe_address(bus, address)? {
+            // Capacity matches the number of addresses
+            let _ = devices.push(address);
+        }
+    }
+
+    Ok(devices)
+}
+
+/// Returns true if a PCA9539 responds at the given address
+/// Returns false if the address is not acknowledged or the responder does not behave like PCA9539.
+/// On bus errors after writing the test pattern, the original polarity is restored on a best-effort basis.
+pub fn probe_address<B: I2c<SevenBitAddress>>(bus: &mut B, address: Address) -> Result<bool, B::Error> {
+    let original = match read_polarity(bus, address) {
+        Ok(value) => value,
+        Err(error) => {
+            return match error.kind() {
+                ErrorKind::NoAcknowledge(_) => Ok(false),
+                _ => Err(error),
+            }
+        }
+    };
+
+    let result = check_pattern(bus, address, original);
+
+    if result.is_err() {
+        // Best effort, the original error is more relevant than a failed restore
+        let _ = bus.write(address.value(), &[Register::Polarity0.command(), original]);
+    }
+
+    result
+}
+
+/// Writes the inverted original polarity, reads it back and restores the original value
+fn check_pattern<B: I2c<SevenBitAddress>>(bus: &mut B, address: Address, original: u8) -> Result<bool, B::Error> {
+    let pattern = !original;
+    bus.write(address.value(), &[Register::Polarity0.command(), pattern])?;
+    let is_matching = read_polarity(bus, address)? == pattern;
+
+    bus.write(address.value(), &[Register::Polarity0.command(), original])?;
+    Ok(is_matching && read_polarity(bus, address)? == original)
+}
+
+/// Reads the polarity inversion register of Bank0
+fn read_polarity<B: I2c<SevenBitAddress>>(bus: &mut B, address: Address) -> Result<u8, B::Error> {
+    bus.write(address.value(), &[Register::Polarity0.command()])?;
+
+    let mut buffer = [0x0; 1];
+    bus.read(address.value(), &mut buffer)?;
+
+    Ok(buffer[0])
+}
@@ -0,0 +1,152 @@
+//! # Configuration profiles
+//!
+//! I/O usage may change between operating phases, e.g. ground test, flight and recovery. Each phase
+//! is described by a named [Profile], wrapping a [board configuration](crate::config). Profiles are
+//! registered by [set_profiles()](PCA9539::set_profiles) and switched at runtime by
+//! [switch_profile()](PCA9539::switch_profile).
+//!
+//! Switching computes the difference against the cached state and only writes registers that
+//! changed, in glitch-free order (output, polarity, configuration). Output levels of pins being
+//! inputs in the target profile are kept, so no register is written just for them.
+//! The switch is rejected before writing anything, if outputs are locked by the [safe state](crate::safe_state),
+//! a [locked pin](crate::lock) would change or an [interlock](crate::interlock) would be violated,
+//! including the intermediate state after writing the output registers.
+//!
+//! On success, the safe state of the target profile is registered and the changed pins are returned.
+//! A bus error may leave the device partially switched, s. [switch_profile()](PCA9539::switch_profile).
+//!
+//! ## Example
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::config::{Config, PinConfig};
+//! use pca9539::example::DummyI2CBus;
+//! use pca9539::expander::Bank::Bank0;
+//! use pca9539::expander::PCA9539;
+//! use pca9539::expander::PinID::{Pin0, Pin1};
+//! use pca9539::profile::Profile;
+//!
+//! static PROFILES: [Profile; 2] = [
+//!     Profile::new("ground", Config::new().pin(Bank0, Pin0, PinConfig::output(false))),
+//!     Profile::new(
+//!         "flight",
+//!         Config::new()
+//!             .pin(Bank0, Pin0, PinConfig::output(true))
+//!             .pin(Bank0, Pin1, PinConfig::output(false)),
+//!     ),
+//! ];
+//!
+//! let mut expander = PCA9539::new(DummyI2CBus::default(), Address::default());
+//! expander.set_profiles(&PROFILES);
+//! expander.switch_profile("ground").unwrap();
+//!
+//! let diff = expander.switch_profile("flight").unwrap();
+//! assert_eq!(0b0000_0010, diff.mode(Bank0));
+//! assert_eq!(0b0000_0011, diff.output(Bank0));
+//! assert_eq!(Some("flight"), expander.active_profile());
+//! ```
+use crate::config::{BankConfig, Config, ConfigDiff};
+use crate::error::Error;
+use crate::expander::PCA9539;
+use crate::register::Register;
+use embedded_hal::i2c::{I2c, SevenBitAddress};
+
+/// Named configuration of an operating phase
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Profile {
+    name: &'static str,
+    config: Config,
+}
+
+/// Registered profiles of an expander
+pub(crate) struct Profiles {
+    /// All registered profiles
+    profiles: &'static [Profile],
+
+    /// Index of the last applied profile
+    active: Option<usize>,
+}
+
+impl Profile {
+    /// Profile with the given name and configuration
+    pub const fn new(name: &'static str, config: Config) -> Self {
+        Self { name, config }
+    }
+
+    /// Returns the name of the profile
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the configuration of the profile
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+}
+
+impl Profiles {
+    pub(crate) fn new() -> Self {
+        Self {
+            profiles: &[],
+            active: None,
+        }
+    }
+}
+
+impl<B: I2c<SevenBitAddress>> PCA9539<B> {
+    /// Registers the given profiles. Resets the active profile.
+    pub fn set_profiles(&mut self, profiles: &'static [Profile]) {
+        *self.profiles_mut() = Profiles { profiles, active: None };
+    }
+
+    /// Returns the name of the last applied profile, None if no profile was switched to yet
+    pub fn active_profile(&self) -> Option<&'static str> {
+        let profiles = self.profiles();
+        profiles.active.map(|index| profiles.profiles[index].name)
+    }
+
+    /// Switches to the profile with the given name, only writing changed registers
+    /// Returns the pins differing from the profile before switching.
+    /// Fails with [Error::UnknownProfile] if no profile with the given name is registered.
+    ///
+    /// # Errors
+    /// Rejections (unknown profile, safe state lock, pin locks and interlocks) happen before writing,
+    /// so device, active profile and safe state are unchanged.
+    ///
+    /// On a bus error, the registers written before the failed one already hold the values of the
+    /// target profile, so the device is left with a mix of both profiles. The cache reflects this
+    /// state, s. [diff_config()](PCA9539::diff_config). The active profile is reset to None, while the
+    /// safe state of the previous profile is kept. Switching again writes just the missing registers.
+    pub fn switch_profile(&mut self, name: &str) -> Result<ConfigDiff, Error<B::Error>> {
+        let index = self
+            .profiles()
+            .profiles
+            .iter()
+            .position(|profile| profile.name == name)
+            .ok_or(Error::UnknownProfile)?;
+
+        let config = self.profiles().profiles[index].config;
+        let diff = self.diff_config(&config);
+
+        let configuration = config.registers(BankConfig::configuration);
+        let cached = self.cached_banks(Register::output);
+        let target = config.registers(BankConfig::output);
+        let output =
+            [0, 1].map(|bank| (cached[bank] & configuration[bank]) | (target[bank] & !configuration[bank]));
+
+        let result = self.write_banks(output, config.registers(BankConfig::polarity), configuration, true);
+
+        if let Err(error) = result {
+            // Device matches neither the previous nor the target profile
+            if error.bus_error().is_some() {
+                self.profiles_mut().active = None;
+            }
+
+            return Err(error);
+        }
+
+        self.set_safe_state(config.safe_state());
+        self.profiles_mut().active = Some(index);
+        Ok(diff)
+    }
+}
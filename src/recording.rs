@@ -0,0 +1,592 @@
+//! # Traffic recording and replay
+//!
+//! [RecordingBus] wraps any I2C bus and records all transactions including their result into a
+//! bounded [Trace]. If the trace is full, the oldest transaction is dropped, so the trace always
+//! contains the most recent traffic. [ReplayBus] replays a recorded trace in a test: Reads are
+//! served with the recorded data, writes are asserted to match the recorded data.
+//!
+//! *Requires activation of `recording` feature*
+//!
+//! ## Trace format
+//! Traces are serialized as text, one transaction per line. Each line consists of the address,
+//! the operations with hex data and the result:
+//! ```text
+//! 0x74 W:06,F7 OK
+//! 0x74 W:00 OK
+//! 0x74 R:26 OK
+//! 0x75 W:00 ERR:NackAddress
+//! ```
+//! Error kinds are encoded as `Bus`, `ArbitrationLoss`, `NackAddress`, `NackData`, `Nack`,
+//! `Overrun` and `Other`. Empty lines and lines starting with `#` are ignored when parsing.
+//!
+//! Transactions with more than [MAX_OPERATIONS] operations or more than [MAX_DATA] bytes per
+//! operation are not recorded, but counted as dropped. This limit is never reached by the traffic
+//! of [PCA9539](crate::expander::PCA9539).
+//!
+//! ## Recording
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::example::DummyI2CBus;
+//! use pca9539::expander::Bank::Bank0;
+//! use pca9539::expander::Mode::Output;
+//! use pca9539::expander::PCA9539;
+//! use pca9539::expander::PinID::Pin3;
+//! use pca9539::recording::RecordingBus;
+//!
+//! let mut i2c_bus: RecordingBus<_, 16> = RecordingBus::new(DummyI2CBus::default());
+//!
+//! let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+//! expander.set_mode(Bank0, Pin3, Output).unwrap();
+//! expander.refresh_input_state(Bank0).unwrap();
+//!
+//! let trace = i2c_bus.trace();
+//! assert_eq!("0x74 W:06,F7 OK\n0x74 W:00 OK\n0x74 R:26 OK\n", trace.to_string());
+//!
+//! // Human readable form, decoding PCA9539 registers
+//! assert_eq!(
+//!     "0x74 write Configuration0 = 0b11110111\n0x74 select Input0\n0x74 read Input0 = 0b00100110\n",
+//!     trace.decoded().to_string()
+//! );
+//! ```
+//! ## Replay
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::expander::Bank::Bank0;
+//! use pca9539::expander::PCA9539;
+//! use pca9539::expander::PinID::Pin1;
+//! use pca9539::recording::{ReplayBus, Trace};
+//!
+//! let trace: Trace<8> = Trace::parse("0x74 W:00 OK\n0x74 R:26 OK").unwrap();
+//! let mut i2c_bus = ReplayBus::new(trace);
+//!
+//! let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+//! expander.refresh_input_state(Bank0).unwrap();
+//! assert!(expander.is_pin_input_high(Bank0, Pin1));
+//!
+//! i2c_bus.done();
+//! ```
+use crate::register::{Register, RegisterValues};
+use core::fmt::{Display, Formatter};
+use core::str::FromStr;
+use embedded_hal::i2c::{Error, ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation, SevenBitAddress};
+use heapless::{Deque, Vec};
+
+/// Maximum number of operations per recorded transaction
+pub const MAX_OPERATIONS: usize = 8;
+
+/// Maximum number of bytes per recorded operation
+pub const MAX_DATA: usize = 8;
+
+/// Recorded I2C operation
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordedOperation {
+    /// Written data
+    Write(Vec<u8, MAX_DATA>),
+    /// Read data
+    Read(Vec<u8, MAX_DATA>),
+}
+
+/// Recorded I2C transaction
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedTransaction {
+    pub address: SevenBitAddress,
+    pub operations: Vec<RecordedOperation, MAX_OPERATIONS>,
+    pub result: Result<(), ErrorKind>,
+}
+
+/// Bounded sequence of recorded transactions
+#[derive(Clone, Debug)]
+pub struct Trace<const N: usize> {
+    transactions: Deque<RecordedTransaction, N>,
+
+    /// Number of dropped transactions
+    dropped: usize,
+}
+
+/// Human readable form of a trace, decoding PCA9539 registers
+pub struct DecodedTrace<'a, const N: usize> {
+    trace: &'a Trace<N>,
+}
+
+/// I2C bus wrapper recording all transactions
+pub struct RecordingBus<B: I2c<SevenBitAddress>, const N: usize> {
+    bus: B,
+    trace: Trace<N>,
+}
+
+/// I2C bus replaying a recorded trace
+pub struct ReplayBus<const N: usize> {
+    trace: Trace<N>,
+
+    /// Index of the next transaction to replay
+    position: usize,
+}
+
+/// Error of a replayed transaction, which failed during recording
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ReplayError(pub ErrorKind);
+
+/// The given text is not a valid transaction
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct InvalidTransaction;
+
+/// Error while parsing a trace
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ParseError {
+    /// Invalid transaction at the given line number (starting at 1)
+    InvalidLine(usize),
+    /// Trace contains more transactions than the capacity
+    CapacityExceeded,
+}
+
+/// Actual transaction of replay, read data is unknown
+struct ActualTransaction<'a, 'b> {
+    address: SevenBitAddress,
+    operations: &'a [Operation<'b>],
+}
+
+impl RecordedTransaction {
+    /// Records the given transaction after execution
+    /// Returns None if the transaction exceeds the limits.
+    fn record(
+        address: SevenBitAddress,
+        operations: &[Operation<'_>],
+        result: Result<(), ErrorKind>,
+    ) -> Option<Self> {
+        let mut recorded = Vec::new();
+
+        for operation in operations {
+            let operation = match operation {
+                Operation::Write(data) => RecordedOperation::Write(Vec::from_slice(data).ok()?),
+                Operation::Read(buffer) => RecordedOperation::Read(Vec::from_slice(buffer).ok()?),
+            };
+
+            recorded.push(operation).ok()?;
+        }
+
+        Some(Self {
+            address,
+            operations: recorded,
+            result,
+        })
+    }
+}
+
+impl<const N: usize> Trace<N> {
+    pub fn new() -> Self {
+        Self {
+            transactions: Deque::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Parses a trace in text format
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        let mut trace = Self::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let transaction = line.parse().map_err(|_| ParseError::InvalidLine(number + 1))?;
+            trace
+                .transactions
+                .push_back(transaction)
+                .map_err(|_| ParseError::CapacityExceeded)?;
+        }
+
+        Ok(trace)
+    }
+
+    /// Appends the given transaction. If the trace is full, the oldest transaction is dropped.
+    pub fn push(&mut self, transaction: RecordedTransaction) {
+        if self.transactions.is_full() {
+            self.transactions.pop_front();
+            self.dropped += 1;
+        }
+
+        // Space is ensured above
+        let _ = self.transactions.push_back(transaction);
+    }
+
+    /// Returns the recorded transactions, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &RecordedTransaction> {
+        self.transactions.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    /// Returns the number of transactions dropped due to capacity or limits
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Removes all transactions and resets the dropped counter
+    pub fn clear(&mut self) {
+        self.transactions.clear();
+        self.dropped = 0;
+    }
+
+    /// Returns the human readable form of the trace
+    pub fn decoded(&self) -> DecodedTrace<'_, N> {
+        DecodedTrace { trace: self }
+    }
+}
+
+impl<const N: usize> Default for Trace<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<B: I2c<SevenBitAddress>, const N: usize> RecordingBus<B, N> {
+    pub fn new(bus: B) -> Self {
+        Self {
+            bus,
+            trace: Trace::new(),
+        }
+    }
+
+    /// Returns the wrapped bus
+    pub fn bus(&mut self) -> &mut B {
+        &mut self.bus
+    }
+
+    /// Returns the recorded trace
+    pub fn trace(&self) -> &Trace<N> {
+        &self.trace
+    }
+
+    /// Returns the recorded trace, e.g. for clearing
+    pub fn trace_mut(&mut self) -> &mut Trace<N> {
+        &mut self.trace
+    }
+
+    /// Releases the wrapped bus and the recorded trace
+    pub fn release(self) -> (B, Trace<N>) {
+        (self.bus, self.trace)
+    }
+}
+
+impl<B: I2c<SevenBitAddress>, const N: usize> ErrorType for RecordingBus<B, N> {
+    type Error = B::Error;
+}
+
+impl<B: I2c<SevenBitAddress>, const N: usize> I2c<SevenBitAddress> for RecordingBus<B, N> {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let result = self.bus.transaction(address, operations);
+
+        match RecordedTransaction::record(
+            address,
+            operations,
+            result.as_ref().map_err(|error| error.kind()).copied(),
+        ) {
+            Some(transaction) => self.trace.push(transaction),
+            None => self.trace.dropped += 1,
+        }
+
+        result
+    }
+}
+
+impl<const N: usize> ReplayBus<N> {
+    pub fn new(trace: Trace<N>) -> Self {
+        Self { trace, position: 0 }
+    }
+
+    /// Returns the number of transactions not replayed yet
+    pub fn remaining(&self) -> usize {
+        self.trace.len() - self.position
+    }
+
+    /// Panics if any transaction has not been replayed
+    pub fn done(&self) {
+        if let Some(transaction) = self.trace.iter().nth(self.position) {
+            panic!(
+                "{} transaction(s) not replayed, next: {}",
+                self.remaining(),
+                transaction
+            );
+        }
+    }
+}
+
+impl<const N: usize> ErrorType for ReplayBus<N> {
+    type Error = ReplayError;
+}
+
+impl<const N: usize> I2c<SevenBitAddress> for ReplayBus<N> {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let actual = ActualTransaction { address, operations };
+
+        let Some(recorded) = self.trace.iter().nth(self.position) else {
+            panic!("Unexpected transaction {}, trace is exhausted", actual);
+        };
+
+        let is_matching = recorded.address == address
+            && recorded.operations.len() == operations.len()
+            && recorded.operations.iter().zip(operations.iter()).all(|(recorded, actual)| {
+                match (recorded, actual) {
+                    (RecordedOperation::Write(expected), Operation::Write(data)) => expected.as_slice() == *data,
+                    (RecordedOperation::Read(expected), Operation::Read(buffer)) => expected.len() == buffer.len(),
+                    _ => false,
+                }
+            });
+
+        if !is_matching {
+            panic!(
+                "Replay mismatch at transaction #{}: expected {}, actual {}",
+                self.position, recorded, actual
+            );
+        }
+
+        for (recorded, actual) in recorded.operations.iter().zip(operations.iter_mut()) {
+            if let (RecordedOperation::Read(data), Operation::Read(buffer)) = (recorded, actual) {
+                buffer.copy_from_slice(data);
+            }
+        }
+
+        self.position += 1;
+        recorded.result.map_err(ReplayError)
+    }
+}
+
+impl Error for ReplayError {
+    fn kind(&self) -> ErrorKind {
+        self.0
+    }
+}
+
+impl Display for RecordedTransaction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:#04x}", self.address)?;
+
+        for operation in &self.operations {
+            let (prefix, data) = match operation {
+                RecordedOperation::Write(data) => ("W", data),
+                RecordedOperation::Read(data) => ("R", data),
+            };
+
+            write!(f, " {}:", prefix)?;
+            fmt_hex(f, data)?;
+        }
+
+        match self.result {
+            Ok(()) => f.write_str(" OK"),
+            Err(kind) => write!(f, " ERR:{}", kind_name(kind)),
+        }
+    }
+}
+
+impl FromStr for RecordedTransaction {
+    type Err = InvalidTransaction;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut tokens = text.split_whitespace();
+
+        let address = tokens
+            .next()
+            .and_then(|token| token.strip_prefix("0x"))
+            .ok_or(InvalidTransaction)?;
+        let address = u8::from_str_radix(address, 16).map_err(|_| InvalidTransaction)?;
+
+        let mut operations = Vec::new();
+        let mut result = None;
+
+        for token in tokens {
+            if result.is_some() {
+                return Err(InvalidTransaction);
+            }
+
+            if token == "OK" {
+                result = Some(Ok(()));
+                continue;
+            }
+
+            if let Some(kind) = token.strip_prefix("ERR:") {
+                result = Some(Err(parse_kind(kind).ok_or(InvalidTransaction)?));
+                continue;
+            }
+
+            let operation = match token.split_once(':') {
+                Some(("W", data)) => RecordedOperation::Write(parse_hex(data)?),
+                Some(("R", data)) => RecordedOperation::Read(parse_hex(data)?),
+                _ => return Err(InvalidTransaction),
+            };
+
+            operations.push(operation).map_err(|_| InvalidTransaction)?;
+        }
+
+        Ok(Self {
+            address,
+            operations,
+            result: result.ok_or(InvalidTransaction)?,
+        })
+    }
+}
+
+impl<const N: usize> Display for Trace<N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        for transaction in self.iter() {
+            writeln!(f, "{}", transaction)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<const N: usize> Display for DecodedTrace<'_, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        // Register selected by the last command byte, per address
+        let mut pointers: [Option<Register>; 128] = [None; 128];
+
+        for transaction in self.trace.iter() {
+            let pointer = &mut pointers[(transaction.address & 0x7F) as usize];
+            write!(f, "{:#04x} ", transaction.address)?;
+
+            for (i, operation) in transaction.operations.iter().enumerate() {
+                if i > 0 {
+                    f.write_str("; ")?;
+                }
+
+                let values = match operation {
+                    RecordedOperation::Write(data) => {
+                        let Some((command, values)) = data.split_first() else {
+                            f.write_str("write without data")?;
+                            continue;
+                        };
+
+                        *pointer = Register::try_from(*command).ok();
+
+                        match pointer {
+                            None => {
+                                write!(f, "write invalid command {:#04x}", command)?;
+                                continue;
+                            }
+                            Some(register) if values.is_empty() => {
+                                write!(f, "select {}", register)?;
+                                continue;
+                            }
+                            Some(_) => {
+                                f.write_str("write ")?;
+                                values
+                            }
+                        }
+                    }
+                    RecordedOperation::Read(data) => {
+                        f.write_str("read ")?;
+                        data.as_slice()
+                    }
+                };
+
+                match pointer {
+                    Some(register) => write!(f, "{}", RegisterValues::new(*register, values))?,
+                    None => fmt_hex(f, values)?,
+                }
+
+                if values.len() % 2 == 1 {
+                    *pointer = pointer.map(Register::pair);
+                }
+            }
+
+            if let Err(kind) = transaction.result {
+                write!(f, " (failed: {})", kind_name(kind))?;
+            }
+
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for ActualTransaction<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:#04x}", self.address)?;
+
+        for operation in self.operations {
+            match operation {
+                Operation::Write(data) => {
+                    f.write_str(" W:")?;
+                    fmt_hex(f, data)?;
+                }
+                Operation::Read(buffer) => write!(f, " R:<{} byte(s)>", buffer.len())?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats the given data as comma separated hex bytes
+fn fmt_hex(f: &mut Formatter<'_>, data: &[u8]) -> core::fmt::Result {
+    for (i, byte) in data.iter().enumerate() {
+        if i > 0 {
+            f.write_str(",")?;
+        }
+
+        write!(f, "{:02X}", byte)?;
+    }
+
+    Ok(())
+}
+
+/// Parses comma separated hex bytes
+fn parse_hex(text: &str) -> Result<Vec<u8, MAX_DATA>, InvalidTransaction> {
+    let mut data = Vec::new();
+
+    if text.is_empty() {
+        return Ok(data);
+    }
+
+    for byte in text.split(',') {
+        let byte = u8::from_str_radix(byte, 16).map_err(|_| InvalidTransaction)?;
+        data.push(byte).map_err(|_| InvalidTransaction)?;
+    }
+
+    Ok(data)
+}
+
+/// Returns the trace format name of the given error kind
+fn kind_name(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::Bus => "Bus",
+        ErrorKind::ArbitrationLoss => "ArbitrationLoss",
+        ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address) => "NackAddress",
+        ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data) => "NackData",
+        ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown) => "Nack",
+        ErrorKind::Overrun => "Overrun",
+        _ => "Other",
+    }
+}
+
+/// Parses the trace format name of an error kind
+fn parse_kind(name: &str) -> Option<ErrorKind> {
+    match name {
+        "Bus" => Some(ErrorKind::Bus),
+        "ArbitrationLoss" => Some(ErrorKind::ArbitrationLoss),
+        "NackAddress" => Some(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)),
+        "NackData" => Some(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data)),
+        "Nack" => Some(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown)),
+        "Overrun" => Some(ErrorKind::Overrun),
+        "Other" => Some(ErrorKind::Other),
+        _ => None,
+    }
+}
@@ -0,0 +1,235 @@
+//! # Registers
+//!
+//! PCA9539 has eight registers, organized in pairs of one register per bank. The register is
+//! selected by the command byte, which is sent as first byte of each write operation. Subsequent
+//! reads or writes are toggling between the two registers of the pair (auto-increment).
+//!
+//! | Command | Register        |
+//! |---------|-----------------|
+//! | 0x00    | Input0          |
+//! | 0x01    | Input1          |
+//! | 0x02    | Output0         |
+//! | 0x03    | Output1         |
+//! | 0x04    | Polarity0       |
+//! | 0x05    | Polarity1       |
+//! | 0x06    | Configuration0  |
+//! | 0x07    | Configuration1  |
+//!
+//! ## Raw register access
+//! For diagnostics and features not modeled by this crate, registers may be accessed directly.
+//! Optionally the cached register state is updated as well.
+//! ```
+//!# use pca9539::address::Address;
+//!# use pca9539::example::DummyI2CBus;
+//!# use pca9539::expander::PCA9539;
+//! use pca9539::register::Register;
+//!#
+//!# let i2c_bus = DummyI2CBus::default();
+//!# let mut  expander = PCA9539::new(i2c_bus, Address::default());
+//!
+//! // Reading without updating the cache
+//! let input = expander.read_register(Register::Input1, false).unwrap();
+//! assert_eq!(0b1110_0101, input);
+//!
+//! // Writing and updating the cache
+//! expander.write_register(Register::Polarity0, 0b0000_0001, true).unwrap();
+//! assert_eq!(0b0000_0001, expander.cached_register(Register::Polarity0));
+//!
+//! // Reading both registers of a pair
+//! let [output_0, output_1] = expander.read_register_pair(Register::Output0, false).unwrap();
+//! ```
+//! ## Register dump
+//! All eight registers are read within one I2C transaction.
+//! ```
+//!# use pca9539::address::Address;
+//!# use pca9539::example::DummyI2CBus;
+//!# use pca9539::expander::PCA9539;
+//! use pca9539::register::Register;
+//!#
+//!# let i2c_bus = DummyI2CBus::default();
+//!# let mut  expander = PCA9539::new(i2c_bus, Address::default());
+//!
+//! let dump = expander.dump_registers(false).unwrap();
+//! assert_eq!(0b0010_0110, dump.get(Register::Input0));
+//! ```
+use crate::expander::Bank;
+use core::fmt::{Display, Formatter};
+
+/// PCA9539 register. The discriminant matches the command byte.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Register {
+    Input0 = 0x00,
+    Input1 = 0x01,
+    Output0 = 0x02,
+    Output1 = 0x03,
+    Polarity0 = 0x04,
+    Polarity1 = 0x05,
+    Configuration0 = 0x06,
+    Configuration1 = 0x07,
+}
+
+/// The given command byte does not address a register
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InvalidCommand(pub u8);
+
+/// Values of all eight registers
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RegisterDump([u8; 8]);
+
+/// Values of consecutive registers, displayed with register names (e.g. `Output1 = 0b00000001, Output0 = 0b00000010`)
+/// The registers of subsequent values are toggling within the pair (auto-increment).
+#[derive(Copy, Clone, Debug)]
+pub struct RegisterValues<'a> {
+    register: Register,
+    values: &'a [u8],
+}
+
+impl Register {
+    /// All registers ordered by command byte
+    pub const ALL: [Register; 8] = [
+        Register::Input0,
+        Register::Input1,
+        Register::Output0,
+        Register::Output1,
+        Register::Polarity0,
+        Register::Polarity1,
+        Register::Configuration0,
+        Register::Configuration1,
+    ];
+
+    /// Returns the command byte selecting this register
+    pub const fn command(self) -> u8 {
+        self as u8
+    }
+
+    /// Returns the input register of the given bank
+    pub const fn input(bank: Bank) -> Self {
+        match bank {
+            Bank::Bank0 => Register::Input0,
+            Bank::Bank1 => Register::Input1,
+        }
+    }
+
+    /// Returns the output register of the given bank
+    pub const fn output(bank: Bank) -> Self {
+        match bank {
+            Bank::Bank0 => Register::Output0,
+            Bank::Bank1 => Register::Output1,
+        }
+    }
+
+    /// Returns the polarity inversion register of the given bank
+    pub const fn polarity(bank: Bank) -> Self {
+        match bank {
+            Bank::Bank0 => Register::Polarity0,
+            Bank::Bank1 => Register::Polarity1,
+        }
+    }
+
+    /// Returns the configuration register of the given bank
+    pub const fn configuration(bank: Bank) -> Self {
+        match bank {
+            Bank::Bank0 => Register::Configuration0,
+            Bank::Bank1 => Register::Configuration1,
+        }
+    }
+
+    /// Returns the bank this register belongs to
+    pub const fn bank(self) -> Bank {
+        match self.command() & 0x1 {
+            0 => Bank::Bank0,
+            _ => Bank::Bank1,
+        }
+    }
+
+    /// Returns the other register of the pair, which is accessed next due to auto-increment
+    pub const fn pair(self) -> Self {
+        Register::ALL[(self.command() ^ 0x1) as usize]
+    }
+
+    /// Returns true for the read-only input registers
+    pub const fn is_input(self) -> bool {
+        matches!(self, Register::Input0 | Register::Input1)
+    }
+
+    /// Returns true for the output registers
+    pub const fn is_output(self) -> bool {
+        matches!(self, Register::Output0 | Register::Output1)
+    }
+
+    /// Returns true for the configuration registers
+    pub const fn is_configuration(self) -> bool {
+        matches!(self, Register::Configuration0 | Register::Configuration1)
+    }
+
+    /// Returns the name of the register
+    pub const fn name(self) -> &'static str {
+        match self {
+            Register::Input0 => "Input0",
+            Register::Input1 => "Input1",
+            Register::Output0 => "Output0",
+            Register::Output1 => "Output1",
+            Register::Polarity0 => "Polarity0",
+            Register::Polarity1 => "Polarity1",
+            Register::Configuration0 => "Configuration0",
+            Register::Configuration1 => "Configuration1",
+        }
+    }
+}
+
+impl TryFrom<u8> for Register {
+    type Error = InvalidCommand;
+
+    fn try_from(command: u8) -> Result<Self, Self::Error> {
+        Register::ALL.get(command as usize).copied().ok_or(InvalidCommand(command))
+    }
+}
+
+impl Display for Register {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl RegisterDump {
+    pub fn new(values: [u8; 8]) -> Self {
+        Self(values)
+    }
+
+    /// Returns the value of the given register
+    pub fn get(&self, register: Register) -> u8 {
+        self.0[register as usize]
+    }
+
+    /// Returns the values of all registers, ordered by command byte
+    pub fn as_bytes(&self) -> &[u8; 8] {
+        &self.0
+    }
+}
+
+impl<'a> RegisterValues<'a> {
+    /// Values starting at the given register
+    pub fn new(register: Register, values: &'a [u8]) -> Self {
+        Self { register, values }
+    }
+}
+
+impl Display for RegisterValues<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let mut register = self.register;
+
+        for (i, value) in self.values.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+
+            write!(f, "{} = {:#010b}", register, value)?;
+            register = register.pair();
+        }
+
+        Ok(())
+    }
+}
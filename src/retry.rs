@@ -0,0 +1,329 @@
+//! # Retrying transient bus errors
+//!
+//! [RetryBus] wraps an I2C bus and retries failed transactions according to a [RetryPolicy], e.g.
+//! in case of a single NACK caused by EMI. As all register accesses of [PCA9539] pass the bus, the
+//! policy applies to the expander as well as to all pins.
+//!
+//! Reads of PCA9539 are split into a command write selecting the register and a subsequent read. So
+//! before retrying a read, the register pointer of the device is restored by resending the command
+//! byte. Otherwise, a partially completed read would advance the pointer to the other register of
+//! the pair.
+//!
+//! Register pointers are tracked for up to four device addresses, including
+//! [custom addresses](Address::custom), in the order of their first access. On buses with more
+//! devices, the oldest entry is replaced. So a retried read of a device, which is not tracked
+//! anymore, is not preceded by restoring the pointer.
+//!
+//! ## Example
+//! Retries are delayed by the given [DelayNs] implementation. The backoff is doubled on every retry.
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::example::DummyI2CBus;
+//! use pca9539::expander::Bank::Bank0;
+//! use pca9539::expander::PCA9539;
+//! use pca9539::retry::{NoDelay, RetryPolicy};
+//! use embedded_hal::i2c::ErrorKind;
+//! use embedded_hal::i2c::NoAcknowledgeSource::Unknown;
+//!
+//! // Up to four attempts, retries delayed by 100 µs, 200 µs and 400 µs
+//! const POLICY: RetryPolicy = RetryPolicy::new(4)
+//!     .backoff_us(100)
+//!     .retryable(&[ErrorKind::NoAcknowledge(Unknown), ErrorKind::ArbitrationLoss]);
+//!
+//!# let delay = NoDelay;
+//! let i2c_bus = DummyI2CBus::default();
+//! let mut expander = PCA9539::with_retry(i2c_bus, Address::default(), POLICY, delay);
+//!
+//! expander.refresh_input_state(Bank0).unwrap();
+//! ```
+//! ## Statistics
+//! The retry statistics allow distinguishing flaky devices (recovered transactions) from dead
+//! devices (failed transactions).
+//! ```
+//!# use pca9539::address::Address;
+//!# use pca9539::example::DummyI2CBus;
+//!# use pca9539::expander::Bank::Bank0;
+//!# use pca9539::expander::PCA9539;
+//!# use pca9539::retry::{NoDelay, RetryPolicy};
+//!#
+//!# let mut expander = PCA9539::with_retry(DummyI2CBus::default(), Address::default(), RetryPolicy::default(), NoDelay);
+//!# expander.refresh_input_state(Bank0).unwrap();
+//! let statistics = expander.retry_statistics();
+//!
+//! assert_eq!(0, statistics.retries);
+//! assert_eq!(0, statistics.recovered);
+//! assert_eq!(0, statistics.failed);
+//! ```
+use crate::address::Address;
+use crate::expander::PCA9539;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::{Error, ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation, SevenBitAddress};
+
+/// Number of device addresses, whose register pointer is tracked
+const TRACKED_DEVICES: usize = 4;
+
+/// Error kinds retried by default
+const DEFAULT_RETRYABLE: [ErrorKind; 3] = [
+    ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown),
+    ErrorKind::ArbitrationLoss,
+    ErrorKind::Bus,
+];
+
+/// Decides which errors are retried and how often
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts per transaction, including the first one
+    max_attempts: u8,
+
+    /// Delay before the first retry in microseconds, doubled on every further retry
+    backoff_us: u32,
+
+    /// Error kinds which are retried
+    retryable: &'static [ErrorKind],
+}
+
+/// Retry counters of a bus
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RetryStatistics {
+    /// Number of retries, i.e. attempts besides the first one
+    pub retries: u32,
+
+    /// Number of transactions succeeding after at least one retry
+    pub recovered: u32,
+
+    /// Number of transactions failing finally, either due to a non-retryable error or after all attempts
+    pub failed: u32,
+}
+
+/// I2C bus wrapper retrying failed transactions
+pub struct RetryBus<B: I2c<SevenBitAddress>, D: DelayNs> {
+    bus: B,
+    delay: D,
+    policy: RetryPolicy,
+    statistics: RetryStatistics,
+
+    /// Tracked device addresses together with their register pointer, as far as known
+    pointers: [Option<(SevenBitAddress, Option<u8>)>; TRACKED_DEVICES],
+
+    /// Entry replaced next, if all entries are in use
+    next_replaced: usize,
+}
+
+/// Delay without any effect, for retrying without backoff
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NoDelay;
+
+impl RetryPolicy {
+    /// Creates a policy with the given maximum number of attempts (including the first one),
+    /// without backoff and retrying NACKs, arbitration loss and bus errors.
+    /// A maximum of zero attempts is treated as one attempt.
+    pub const fn new(max_attempts: u8) -> Self {
+        Self {
+            max_attempts,
+            backoff_us: 0,
+            retryable: &DEFAULT_RETRYABLE,
+        }
+    }
+
+    /// Sets the delay before the first retry in microseconds, which gets doubled on every further retry
+    pub const fn backoff_us(mut self, backoff_us: u32) -> Self {
+        self.backoff_us = backoff_us;
+        self
+    }
+
+    /// Sets the retried error kinds
+    /// [NoAcknowledgeSource::Unknown] matches NACKs of any source.
+    pub const fn retryable(mut self, kinds: &'static [ErrorKind]) -> Self {
+        self.retryable = kinds;
+        self
+    }
+
+    /// Returns the maximum number of attempts per transaction
+    pub fn max_attempts(&self) -> u8 {
+        self.max_attempts.max(1)
+    }
+
+    /// Returns true if errors of the given kind are retried
+    pub fn is_retryable(&self, kind: ErrorKind) -> bool {
+        self.retryable.iter().any(|retryable| match (retryable, kind) {
+            (ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown), ErrorKind::NoAcknowledge(_)) => true,
+            (retryable, kind) => *retryable == kind,
+        })
+    }
+}
+
+impl Default for RetryPolicy {
+    /// Up to three attempts without backoff
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+impl<B: I2c<SevenBitAddress>, D: DelayNs> RetryBus<B, D> {
+    pub fn new(bus: B, policy: RetryPolicy, delay: D) -> Self {
+        Self {
+            bus,
+            delay,
+            policy,
+            statistics: RetryStatistics::default(),
+            pointers: [None; TRACKED_DEVICES],
+            next_replaced: 0,
+        }
+    }
+
+    /// Returns the retry policy
+    pub fn policy(&self) -> RetryPolicy {
+        self.policy
+    }
+
+    /// Replaces the retry policy
+    pub fn set_policy(&mut self, policy: RetryPolicy) {
+        self.policy = policy;
+    }
+
+    /// Returns the retry statistics
+    pub fn statistics(&self) -> RetryStatistics {
+        self.statistics
+    }
+
+    /// Resets the retry statistics
+    pub fn reset_statistics(&mut self) {
+        self.statistics = RetryStatistics::default();
+    }
+
+    /// Releases the wrapped bus and delay
+    pub fn release(self) -> (B, D) {
+        (self.bus, self.delay)
+    }
+
+    /// Executes a retry of the given transaction. Transactions starting with a read are preceded by
+    /// restoring the register pointer, if known.
+    fn retry(&mut self, address: SevenBitAddress, operations: &mut [Operation<'_>]) -> Result<(), B::Error> {
+        if let (Some(Operation::Read(_)), Some(pointer)) = (operations.first(), self.pointer(address)) {
+            self.bus.write(address, &[pointer])?;
+        }
+
+        self.bus.transaction(address, operations)
+    }
+
+    /// Returns the known register pointer of the device with the given address
+    fn pointer(&self, address: SevenBitAddress) -> Option<u8> {
+        self.index(address).and_then(|index| self.pointers[index]?.1)
+    }
+
+    /// Tracks the register pointer after a transaction
+    /// Auto-increment toggles the pointer within the register pair after each data byte.
+    fn update_pointer(&mut self, address: SevenBitAddress, operations: &[Operation<'_>], success: bool) {
+        let index = match self.index(address) {
+            Some(index) => index,
+            None if success => self.track(address),
+            None => return,
+        };
+
+        if !success {
+            self.pointers[index] = Some((address, None));
+            return;
+        }
+
+        let mut pointer = self.pointers[index].and_then(|(_, pointer)| pointer);
+
+        for operation in operations {
+            pointer = match operation {
+                Operation::Write([]) => pointer,
+                Operation::Write([command, values @ ..]) => Some(command ^ (values.len() % 2) as u8),
+                Operation::Read(buffer) => pointer.map(|pointer| pointer ^ (buffer.len() % 2) as u8),
+            };
+        }
+
+        self.pointers[index] = Some((address, pointer));
+    }
+
+    /// Returns the entry of the given address, if tracked
+    fn index(&self, address: SevenBitAddress) -> Option<usize> {
+        self.pointers
+            .iter()
+            .position(|entry| matches!(entry, Some((tracked, _)) if *tracked == address))
+    }
+
+    /// Starts tracking the given address, replacing the oldest entry if all entries are in use
+    fn track(&mut self, address: SevenBitAddress) -> usize {
+        let index = match self.pointers.iter().position(Option::is_none) {
+            Some(index) => index,
+            None => {
+                let index = self.next_replaced;
+                self.next_replaced = (index + 1) % TRACKED_DEVICES;
+                index
+            }
+        };
+
+        self.pointers[index] = Some((address, None));
+        index
+    }
+}
+
+impl<B: I2c<SevenBitAddress>, D: DelayNs> ErrorType for RetryBus<B, D> {
+    type Error = B::Error;
+}
+
+impl<B: I2c<SevenBitAddress>, D: DelayNs> I2c<SevenBitAddress> for RetryBus<B, D> {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let mut result = self.bus.transaction(address, operations);
+        let mut attempts = 1;
+        let mut backoff = self.policy.backoff_us;
+
+        while let Err(error) = &result {
+            if attempts >= self.policy.max_attempts() || !self.policy.is_retryable(error.kind()) {
+                break;
+            }
+
+            if backoff > 0 {
+                self.delay.delay_us(backoff);
+                backoff = backoff.saturating_mul(2);
+            }
+
+            self.statistics.retries = self.statistics.retries.saturating_add(1);
+            attempts += 1;
+            result = self.retry(address, operations);
+        }
+
+        match result {
+            Ok(()) if attempts > 1 => self.statistics.recovered = self.statistics.recovered.saturating_add(1),
+            Ok(()) => {}
+            Err(_) => self.statistics.failed = self.statistics.failed.saturating_add(1),
+        }
+
+        self.update_pointer(address, operations, result.is_ok());
+        result
+    }
+}
+
+impl DelayNs for NoDelay {
+    fn delay_ns(&mut self, _ns: u32) {}
+}
+
+impl<B: I2c<SevenBitAddress>, D: DelayNs> PCA9539<RetryBus<B, D>> {
+    /// Creates an expander retrying failed register accesses according to the given policy
+    pub fn with_retry(bus: B, address: Address, policy: RetryPolicy, delay: D) -> Self {
+        Self::new(RetryBus::new(bus, policy, delay), address)
+    }
+
+    /// Returns the retry statistics
+    pub fn retry_statistics(&self) -> RetryStatistics {
+        self.bus().statistics()
+    }
+
+    /// Resets the retry statistics
+    pub fn reset_retry_statistics(&mut self) {
+        self.bus_mut().reset_statistics();
+    }
+
+    /// Replaces the retry policy
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.bus_mut().set_policy(policy);
+    }
+}
@@ -0,0 +1,139 @@
+//! # Safe state
+//!
+//! The safe state defines mode and output level of each pin, which is taken when the system enters
+//! a fault state or shuts down. It is registered using [set_safe_state()](crate::expander::PCA9539::set_safe_state)
+//! and applied by [enter_safe_state()](crate::expander::PCA9539::enter_safe_state).
+//!
+//! Afterwards all output changes (output state as well as mode) are rejected with
+//! [Error::SafeStateLocked](crate::error::Error::SafeStateLocked), until the lock is explicitly
+//! released by [release_safe_state()](crate::expander::PCA9539::release_safe_state).
+//!
+//! ## Glitch-free ordering
+//! The safe state is written within two transactions, each using auto-increment for writing both
+//! banks at once:
+//! 1. Output registers: Pins switching to output mode are driving their safe level right away.
+//!    Pins switching to input mode keep their current level until being released.
+//! 2. Configuration registers
+//!
+//! ## Example
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::example::DummyI2CBus;
+//! use pca9539::error::Error;
+//! use pca9539::expander::Bank::{Bank0, Bank1};
+//! use pca9539::expander::PCA9539;
+//! use pca9539::expander::PinID::{Pin2, Pin5};
+//! use pca9539::safe_state::SafeState;
+//!
+//! // Pin02 drives low, Pin15 drives high, all other pins are inputs
+//! const SAFE_STATE: SafeState = SafeState::new().output(Bank0, Pin2, false).output(Bank1, Pin5, true);
+//!
+//! let mut expander = PCA9539::new(DummyI2CBus::default(), Address::default());
+//! expander.set_safe_state(SAFE_STATE);
+//!
+//! expander.enter_safe_state().unwrap();
+//! assert_eq!(Err(Error::SafeStateLocked), expander.set_state(Bank0, Pin2, true));
+//!
+//! expander.release_safe_state();
+//! assert!(expander.set_state(Bank0, Pin2, true).is_ok());
+//! ```
+//! ## Automatic triggering
+//! The safe state may be entered automatically after the given number of consecutive bus errors.
+//! As the bus is failing at this point, writing the safe state may fail as well. However, outputs
+//! are locked anyway and the safe state is cached, so it is written by any later
+//! [sync_state()](crate::expander::PCA9539::sync_state), e.g. by the [link recovery](crate::link).
+//! ```
+//!# use pca9539::expander::Bank::Bank0;
+//!# use pca9539::expander::PinID::Pin2;
+//! use pca9539::safe_state::SafeState;
+//!
+//! const SAFE_STATE: SafeState = SafeState::new().output(Bank0, Pin2, false).enter_after_errors(3);
+//! ```
+use crate::expander::{Bank, Mode, PinID};
+
+/// Mode and output level of each pin in safe state
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SafeState {
+    /// Configuration register values, indexed by bank
+    configuration: [u8; 2],
+
+    /// Output register values, indexed by bank
+    output: [u8; 2],
+
+    /// Number of consecutive bus errors triggering the safe state
+    trigger: Option<u32>,
+}
+
+impl SafeState {
+    /// All pins in input mode, which matches the power-on state of PCA9539
+    pub const fn new() -> Self {
+        Self {
+            configuration: [0xFF; 2],
+            output: [0xFF; 2],
+            trigger: None,
+        }
+    }
+
+    /// Sets the given pin to output mode with the given level
+    pub const fn output(mut self, bank: Bank, id: PinID, is_high: bool) -> Self {
+        let mask = 1 << id as u8;
+        self.configuration[bank as usize] &= !mask;
+
+        if is_high {
+            self.output[bank as usize] |= mask;
+        } else {
+            self.output[bank as usize] &= !mask;
+        }
+
+        self
+    }
+
+    /// Sets the given pin to input mode
+    pub const fn input(mut self, bank: Bank, id: PinID) -> Self {
+        self.configuration[bank as usize] |= 1 << id as u8;
+        self
+    }
+
+    /// Enters the safe state automatically after the given number of consecutive bus errors
+    pub const fn enter_after_errors(mut self, errors: u32) -> Self {
+        self.trigger = Some(errors);
+        self
+    }
+
+    /// Returns the mode of the given pin
+    pub fn mode(&self, bank: Bank, id: PinID) -> Mode {
+        match self.configuration[bank as usize] & (1 << id as u8) {
+            0 => Mode::Output,
+            _ => Mode::Input,
+        }
+    }
+
+    /// Returns true if the given pin is driving high level in safe state
+    /// Always false for input pins.
+    pub fn is_output_high(&self, bank: Bank, id: PinID) -> bool {
+        self.mode(bank, id) == Mode::Output && self.output[bank as usize] & (1 << id as u8) != 0
+    }
+
+    /// Returns the configuration register value of the given bank
+    pub(crate) fn configuration(&self, bank: Bank) -> u8 {
+        self.configuration[bank as usize]
+    }
+
+    /// Returns the output register value of the given bank, keeping the current level of input pins
+    pub(crate) fn output_register(&self, bank: Bank, current: u8) -> u8 {
+        let inputs = self.configuration[bank as usize];
+        (self.output[bank as usize] & !inputs) | (current & inputs)
+    }
+
+    /// Returns true if the given number of consecutive errors triggers the safe state
+    pub(crate) fn is_triggered(&self, errors: u32) -> bool {
+        matches!(self.trigger, Some(trigger) if errors >= trigger)
+    }
+}
+
+impl Default for SafeState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
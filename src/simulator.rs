@@ -0,0 +1,332 @@
+//! # Simulated PCA9539
+//!
+//! Register accurate simulation of PCA9539 for integration tests, implementing the I2C traits of
+//! embedded-hal. The following device behaviour is modeled:
+//! * All eight registers, selected by the command byte
+//! * Auto-increment toggling between the two registers of a pair
+//! * Pin levels of outputs reflected on the input registers
+//! * Polarity inversion applied to input register reads
+//! * INT assertion on input changes, cleared by reading the input register or by restoring the original level
+//! * Power-on reset state
+//!
+//! The level of input pins is driven by the test using [SimulatedPCA9539::set_pin_level()].
+//!
+//! *Requires activation of `simulator` feature*
+//!
+//! ## Single device
+//! As the expander takes ownership of the bus, the simulated device is shared using a RefCell, e.g. by
+//! using [embedded-hal-bus](https://docs.rs/embedded-hal-bus).
+//! ```
+//! use core::cell::RefCell;
+//! use embedded_hal_bus::i2c::RefCellDevice;
+//! use pca9539::address::Address;
+//! use pca9539::expander::Bank::{Bank0, Bank1};
+//! use pca9539::expander::Mode::Output;
+//! use pca9539::expander::PCA9539;
+//! use pca9539::expander::PinID::{Pin2, Pin5};
+//! use pca9539::simulator::SimulatedPCA9539;
+//!
+//! let device = RefCell::new(SimulatedPCA9539::new(Address::default()));
+//! let mut expander = PCA9539::new(RefCellDevice::new(&device), Address::default());
+//!
+//! // Driving input level
+//! device.borrow_mut().set_pin_level(Bank0, Pin2, false);
+//! assert!(device.borrow().is_interrupt_active());
+//!
+//! expander.refresh_input_state(Bank0).unwrap();
+//! assert!(!expander.is_pin_input_high(Bank0, Pin2));
+//! assert!(!device.borrow().is_interrupt_active());
+//!
+//! // Checking output level
+//! expander.set_mode(Bank1, Pin5, Output).unwrap();
+//! expander.set_state(Bank1, Pin5, false).unwrap();
+//! expander.write_output_state(Bank1).unwrap();
+//! assert!(!device.borrow().pin_level(Bank1, Pin5));
+//! ```
+//! ## Multiple devices
+//! [SimulatedBus] routes transactions to the device with the matching address. Transactions to
+//! addresses without device are not acknowledged. The wired-OR INT line of all devices is available
+//! as input pin using [SimulatedInterruptLine].
+//! ```
+//! use core::cell::RefCell;
+//! use embedded_hal_bus::i2c::RefCellDevice;
+//! use pca9539::address::Address;
+//! use pca9539::address::Strap::{High, Low};
+//! use pca9539::expander::Bank::Bank1;
+//! use pca9539::expander::PinID::Pin0;
+//! use pca9539::simulator::{SimulatedBus, SimulatedInterruptLine, SimulatedPCA9539};
+//! use pca9539::probe::probe;
+//! use embedded_hal::digital::InputPin;
+//!
+//! let mut bus: SimulatedBus<2> = SimulatedBus::new();
+//! bus.attach(SimulatedPCA9539::new(Address::new(Low, High))).unwrap();
+//! bus.attach(SimulatedPCA9539::new(Address::new(High, High))).unwrap();
+//!
+//! let bus = RefCell::new(bus);
+//! let mut line = SimulatedInterruptLine::new(&bus);
+//!
+//! let devices = probe(&mut RefCellDevice::new(&bus)).unwrap();
+//! assert_eq!(2, devices.len());
+//!
+//! bus.borrow_mut().device_mut(Address::new(High, High)).unwrap().set_pin_level(Bank1, Pin0, false);
+//! assert!(line.is_low().unwrap());
+//! ```
+use crate::address::Address;
+use crate::expander::{Bank, PinID};
+use crate::register::Register;
+use core::cell::RefCell;
+use core::convert::Infallible;
+use embedded_hal::digital::{self, InputPin};
+use embedded_hal::i2c::{Error, ErrorKind, ErrorType, I2c, NoAcknowledgeSource, Operation, SevenBitAddress};
+use heapless::Vec;
+
+/// Simulated PCA9539 device
+#[derive(Clone, Debug)]
+pub struct SimulatedPCA9539 {
+    address: Address,
+
+    /// Output, polarity inversion and configuration registers. Input registers are derived from pin levels.
+    registers: [u8; 8],
+
+    /// Externally driven pin levels, indexed by bank
+    levels: [u8; 2],
+
+    /// Register selected by the last command byte
+    pointer: Register,
+
+    /// Pin levels at the last read of the input register, indexed by bank
+    latched: [u8; 2],
+}
+
+/// Multiple simulated devices sharing one bus
+#[derive(Debug, Default)]
+pub struct SimulatedBus<const N: usize> {
+    devices: Vec<SimulatedPCA9539, N>,
+}
+
+/// Wired-OR INT line of all devices of a simulated bus, low if any interrupt is active
+pub struct SimulatedInterruptLine<'a, const N: usize> {
+    bus: &'a RefCell<SimulatedBus<N>>,
+}
+
+/// Error of simulated bus transactions
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SimulatorError(pub ErrorKind);
+
+/// Power-on value of output register
+const RESET_OUTPUT: u8 = 0b1111_1111;
+/// Power-on value of polarity inversion register
+const RESET_POLARITY: u8 = 0b0000_0000;
+/// Power-on value of configuration register
+const RESET_CONFIGURATION: u8 = 0b1111_1111;
+
+impl SimulatedPCA9539 {
+    /// Creates a device in power-on reset state with all pins externally pulled high
+    pub fn new(address: Address) -> Self {
+        let mut device = Self {
+            address,
+            registers: [0x0; 8],
+            levels: [0b1111_1111; 2],
+            pointer: Register::Input0,
+            latched: [0x0; 2],
+        };
+
+        device.reset();
+        device
+    }
+
+    /// Returns the I2C address of the device
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Simulates a power-on reset. Registers are reset to default values, external pin levels are kept.
+    pub fn reset(&mut self) {
+        for bank in [Bank::Bank0, Bank::Bank1] {
+            self.registers[Register::output(bank) as usize] = RESET_OUTPUT;
+            self.registers[Register::polarity(bank) as usize] = RESET_POLARITY;
+            self.registers[Register::configuration(bank) as usize] = RESET_CONFIGURATION;
+            self.latched[bank as usize] = self.port_levels(bank);
+        }
+
+        self.pointer = Register::Input0;
+    }
+
+    /// Drives the external level of the given pin
+    /// Only takes effect, if the pin is configured as input
+    pub fn set_pin_level(&mut self, bank: Bank, id: PinID, is_high: bool) {
+        let mask = 1 << id as u8;
+
+        match is_high {
+            true => self.levels[bank as usize] |= mask,
+            false => self.levels[bank as usize] &= !mask,
+        }
+    }
+
+    /// Drives the external levels of all pins of the given bank
+    pub fn set_port_levels(&mut self, bank: Bank, levels: u8) {
+        self.levels[bank as usize] = levels;
+    }
+
+    /// Returns the actual level of the given pin, which is the output state for output pins and the
+    /// external level for input pins
+    pub fn pin_level(&self, bank: Bank, id: PinID) -> bool {
+        self.port_levels(bank) & (1 << id as u8) != 0
+    }
+
+    /// Returns the current value of the given register
+    /// Reading input registers this way does not clear the interrupt.
+    pub fn register(&self, register: Register) -> u8 {
+        match register {
+            Register::Input0 | Register::Input1 => {
+                self.port_levels(register.bank()) ^ self.registers[Register::polarity(register.bank()) as usize]
+            }
+            _ => self.registers[register as usize],
+        }
+    }
+
+    /// Returns true if the INT output is asserted
+    pub fn is_interrupt_active(&self) -> bool {
+        [Bank::Bank0, Bank::Bank1].into_iter().any(|bank| {
+            let configuration = self.registers[Register::configuration(bank) as usize];
+            (self.port_levels(bank) ^ self.latched[bank as usize]) & configuration != 0
+        })
+    }
+
+    /// Returns the actual levels of all pins of the given bank
+    fn port_levels(&self, bank: Bank) -> u8 {
+        let configuration = self.registers[Register::configuration(bank) as usize];
+        let output = self.registers[Register::output(bank) as usize];
+
+        (self.levels[bank as usize] & configuration) | (output & !configuration)
+    }
+
+    /// Handles a write operation. First byte is the command, followed by data bytes.
+    fn write(&mut self, data: &[u8]) -> Result<(), SimulatorError> {
+        let Some((command, values)) = data.split_first() else {
+            return Ok(());
+        };
+
+        self.pointer = Register::try_from(*command)
+            .map_err(|_| SimulatorError(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data)))?;
+
+        for value in values {
+            // Writes to input registers are ignored by the device
+            if !self.pointer.is_input() {
+                self.registers[self.pointer as usize] = *value;
+            }
+
+            self.pointer = self.pointer.pair();
+        }
+
+        Ok(())
+    }
+
+    /// Handles a read operation
+    fn read(&mut self, buffer: &mut [u8]) {
+        for value in buffer {
+            *value = self.register(self.pointer);
+
+            if self.pointer.is_input() {
+                let bank = self.pointer.bank();
+                self.latched[bank as usize] = self.port_levels(bank);
+            }
+
+            self.pointer = self.pointer.pair();
+        }
+    }
+}
+
+impl ErrorType for SimulatedPCA9539 {
+    type Error = SimulatorError;
+}
+
+impl I2c<SevenBitAddress> for SimulatedPCA9539 {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        if address != self.address.value() {
+            return Err(SimulatorError(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)));
+        }
+
+        for operation in operations {
+            match operation {
+                Operation::Write(data) => self.write(data)?,
+                Operation::Read(buffer) => self.read(buffer),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<const N: usize> SimulatedBus<N> {
+    pub fn new() -> Self {
+        Self { devices: Vec::new() }
+    }
+
+    /// Attaches the given device to the bus. Returns the device if the capacity is exhausted.
+    pub fn attach(&mut self, device: SimulatedPCA9539) -> Result<(), SimulatedPCA9539> {
+        self.devices.push(device)
+    }
+
+    /// Returns the device with the given address
+    pub fn device(&self, address: Address) -> Option<&SimulatedPCA9539> {
+        self.devices.iter().find(|device| device.address == address)
+    }
+
+    /// Returns the device with the given address
+    pub fn device_mut(&mut self, address: Address) -> Option<&mut SimulatedPCA9539> {
+        self.devices.iter_mut().find(|device| device.address == address)
+    }
+
+    /// Returns true if the INT output of any device is asserted
+    pub fn is_interrupt_active(&self) -> bool {
+        self.devices.iter().any(|device| device.is_interrupt_active())
+    }
+}
+
+impl<const N: usize> ErrorType for SimulatedBus<N> {
+    type Error = SimulatorError;
+}
+
+impl<const N: usize> I2c<SevenBitAddress> for SimulatedBus<N> {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        match self.devices.iter_mut().find(|device| device.address.value() == address) {
+            Some(device) => device.transaction(address, operations),
+            None => Err(SimulatorError(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address))),
+        }
+    }
+}
+
+impl<'a, const N: usize> SimulatedInterruptLine<'a, N> {
+    pub fn new(bus: &'a RefCell<SimulatedBus<N>>) -> Self {
+        Self { bus }
+    }
+}
+
+impl<const N: usize> digital::ErrorType for SimulatedInterruptLine<'_, N> {
+    type Error = Infallible;
+}
+
+impl<const N: usize> InputPin for SimulatedInterruptLine<'_, N> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.bus.borrow().is_interrupt_active())
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.bus.borrow().is_interrupt_active())
+    }
+}
+
+impl Error for SimulatorError {
+    fn kind(&self) -> ErrorKind {
+        self.0
+    }
+}
@@ -0,0 +1,183 @@
+//! # State snapshots
+//!
+//! A [Snapshot] captures the cached state of all eight registers, e.g. for warm restarts or
+//! black-box logging. It is serialized to a compact, fixed-size byte representation:
+//!
+//! | Offset | Size | Content                                                    |
+//! |--------|------|------------------------------------------------------------|
+//! | 0      | 1    | Format version, s. [Snapshot::VERSION]                     |
+//! | 1      | 8    | Register values, ordered by command byte                   |
+//! | 9      | 2    | CRC-16/CCITT-FALSE of the preceding bytes, big endian      |
+//!
+//! [restore()](PCA9539::restore) writes the snapshot back in glitch-free order (output, polarity,
+//! configuration) and honors the safe state, [pin locks](crate::lock) and [interlocks](crate::interlock).
+//! As input registers are read-only and inputs have most likely changed since taking the snapshot,
+//! their snapshot values are not restored. Instead, the input state is refreshed from the device.
+//!
+//! With the `serde` feature, [Snapshot] implements `Serialize` and `Deserialize`.
+//!
+//! ## Example
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::example::DummyI2CBus;
+//! use pca9539::expander::Bank::Bank0;
+//! use pca9539::expander::Mode::Output;
+//! use pca9539::expander::PCA9539;
+//! use pca9539::expander::PinID::Pin4;
+//! use pca9539::register::Register;
+//! use pca9539::snapshot::Snapshot;
+//!
+//! let mut expander = PCA9539::new(DummyI2CBus::default(), Address::default());
+//! expander.set_mode(Bank0, Pin4, Output).unwrap();
+//!
+//! // Persist, e.g. in retained RAM
+//! let bytes = expander.snapshot().to_bytes();
+//!
+//! // After warm restart
+//! let mut expander = PCA9539::new(DummyI2CBus::default(), Address::default());
+//! expander.restore(&Snapshot::from_bytes(&bytes).unwrap()).unwrap();
+//! assert_eq!(0b1110_1111, expander.cached_register(Register::Configuration0));
+//! ```
+use crate::error::Error;
+use crate::expander::PCA9539;
+use crate::register::{Register, RegisterDump};
+use core::fmt::{Display, Formatter};
+use embedded_hal::i2c::{I2c, SevenBitAddress};
+
+/// Cached state of all registers
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    /// Register values, ordered by command byte
+    registers: [u8; 8],
+}
+
+/// Error when decoding a snapshot
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SnapshotError {
+    /// Number of bytes differs from [Snapshot::SIZE]
+    InvalidLength(usize),
+    /// Format version is not supported
+    UnsupportedVersion(u8),
+    /// Checksum does not match, data is corrupted
+    ChecksumMismatch,
+}
+
+impl Snapshot {
+    /// Current format version
+    pub const VERSION: u8 = 1;
+
+    /// Size of the byte representation
+    pub const SIZE: usize = 11;
+
+    /// Snapshot of the given register values, ordered by command byte
+    pub const fn new(registers: [u8; 8]) -> Self {
+        Self { registers }
+    }
+
+    /// Returns the value of the given register
+    pub fn get(&self, register: Register) -> u8 {
+        self.registers[register as usize]
+    }
+
+    /// Returns the byte representation, including version and checksum
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0x0; Self::SIZE];
+        bytes[0] = Self::VERSION;
+        bytes[1..9].copy_from_slice(&self.registers);
+
+        let crc = crc16(&bytes[..9]);
+        bytes[9..].copy_from_slice(&crc.to_be_bytes());
+        bytes
+    }
+
+    /// Decodes the given byte representation, verifying length, version and checksum
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        if bytes.len() != Self::SIZE {
+            return Err(SnapshotError::InvalidLength(bytes.len()));
+        }
+
+        if bytes[0] != Self::VERSION {
+            return Err(SnapshotError::UnsupportedVersion(bytes[0]));
+        }
+
+        if crc16(&bytes[..9]).to_be_bytes() != bytes[9..] {
+            return Err(SnapshotError::ChecksumMismatch);
+        }
+
+        let mut registers = [0x0; 8];
+        registers.copy_from_slice(&bytes[1..9]);
+        Ok(Self { registers })
+    }
+}
+
+impl From<RegisterDump> for Snapshot {
+    fn from(dump: RegisterDump) -> Self {
+        Self::new(*dump.as_bytes())
+    }
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SnapshotError::InvalidLength(length) => {
+                write!(f, "Invalid snapshot length {}, expected {}", length, Snapshot::SIZE)
+            }
+            SnapshotError::UnsupportedVersion(version) => write!(f, "Unsupported snapshot version {}", version),
+            SnapshotError::ChecksumMismatch => f.write_str("Snapshot checksum mismatch"),
+        }
+    }
+}
+
+impl<B: I2c<SevenBitAddress>> PCA9539<B> {
+    /// Returns a snapshot of the cached register state
+    pub fn snapshot(&self) -> Snapshot {
+        let mut registers = [0x0; 8];
+
+        for register in Register::ALL {
+            registers[register as usize] = self.cached_register(register);
+        }
+
+        Snapshot::new(registers)
+    }
+
+    /// Writes the given snapshot in glitch-free order (output, polarity, configuration)
+    /// Fails before writing if outputs are locked by the safe state, a locked pin would change or an
+    /// interlock would be violated. Afterwards the input state of both banks is refreshed, as the
+    /// input values of the snapshot are outdated. A failed refresh is reported, although the snapshot
+    /// has been written already.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), Error<B::Error>> {
+        self.write_banks(
+            [snapshot.get(Register::Output0), snapshot.get(Register::Output1)],
+            [snapshot.get(Register::Polarity0), snapshot.get(Register::Polarity1)],
+            [
+                snapshot.get(Register::Configuration0),
+                snapshot.get(Register::Configuration1),
+            ],
+            false,
+        )?;
+
+        self.read_register_pair(Register::Input0, true)?;
+        Ok(())
+    }
+}
+
+/// CRC-16/CCITT-FALSE (polynomial 0x1021, initial value 0xFFFF)
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+
+        for _ in 0..8 {
+            crc = match crc & 0x8000 {
+                0 => crc << 1,
+                _ => (crc << 1) ^ 0x1021,
+            };
+        }
+    }
+
+    crc
+}
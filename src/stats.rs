@@ -0,0 +1,257 @@
+//! # Bus statistics
+//!
+//! Each [PCA9539](crate::expander::PCA9539) instance counts its register accesses, transferred bytes
+//! and bus errors. A snapshot of the counters is returned by
+//! [statistics()](crate::expander::PCA9539::statistics), which may be forwarded to telemetry.
+//!
+//! *Requires activation of `stats` feature. Without it, no counters are compiled in.*
+//!
+//! ## Example
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::example::DummyI2CBus;
+//! use pca9539::expander::Bank::Bank0;
+//! use pca9539::expander::PCA9539;
+//! use pca9539::register::Register;
+//!
+//! let mut expander = PCA9539::new(DummyI2CBus::default(), Address::default());
+//! expander.refresh_input_state(Bank0).unwrap();
+//!
+//! let statistics = expander.statistics();
+//! assert_eq!(1, statistics.reads(Register::Input0));
+//! assert_eq!(1, statistics.bytes_read());
+//! assert_eq!(0, statistics.failure_streak());
+//!
+//! expander.reset_statistics();
+//! assert_eq!(0, expander.statistics().reads(Register::Input0));
+//! ```
+//! ## Failure streak
+//! The number of consecutive errors is the same counter, which drives the [link state](crate::link)
+//! and the automatic [safe state](crate::safe_state) triggering. So it is just reset by a successful
+//! access, but not by [reset_statistics()](crate::expander::PCA9539::reset_statistics).
+//!
+//! ## Timestamps
+//! As there is no common clock in no_std environments, the time of the last successful access is
+//! only recorded after a clock function has been set. The unit is up to the application, e.g.
+//! milliseconds since boot.
+//! ```
+//!# use pca9539::address::Address;
+//!# use pca9539::example::DummyI2CBus;
+//!# use pca9539::expander::Bank::Bank0;
+//!# use pca9539::expander::PCA9539;
+//!#
+//! fn now() -> u64 {
+//!     // E.g. reading a monotonic system timer
+//!     1500
+//! }
+//!
+//! let mut expander = PCA9539::new(DummyI2CBus::default(), Address::default());
+//! assert_eq!(None, expander.statistics().last_success());
+//!
+//! expander.set_clock(now);
+//! expander.refresh_input_state(Bank0).unwrap();
+//! assert_eq!(Some(1500), expander.statistics().last_success());
+//! ```
+use crate::error::{BusOperation, Error};
+use crate::register::Register;
+use embedded_hal::i2c;
+use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource};
+
+/// Snapshot of the bus statistics of one expander
+/// Counters are saturating, so they never wrap around.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Statistics {
+    /// Successful reads, indexed by register
+    reads: [u32; 8],
+
+    /// Successful writes, indexed by register
+    writes: [u32; 8],
+
+    /// Bytes successfully read
+    bytes_read: u32,
+
+    /// Bytes successfully written, including command bytes
+    bytes_written: u32,
+
+    read_errors: ErrorCounters,
+    write_errors: ErrorCounters,
+
+    /// Number of errors since the last successful access, set when taking the snapshot
+    failure_streak: u32,
+
+    /// Longest failure streak since the last reset
+    longest_failure_streak: u32,
+
+    /// Clock value of the last successful access
+    last_success: Option<u64>,
+}
+
+/// Number of bus errors by error kind
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ErrorCounters {
+    bus: u32,
+    arbitration_loss: u32,
+    nack_address: u32,
+    nack_data: u32,
+    nack_unknown: u32,
+    overrun: u32,
+    other: u32,
+}
+
+impl Statistics {
+    /// Returns the number of successful reads of the given register
+    /// A read of a register pair counts for both registers.
+    pub fn reads(&self, register: Register) -> u32 {
+        self.reads[register as usize]
+    }
+
+    /// Returns the number of successful writes of the given register
+    /// A write of a register pair counts for both registers.
+    pub fn writes(&self, register: Register) -> u32 {
+        self.writes[register as usize]
+    }
+
+    /// Returns the number of bytes successfully read
+    pub fn bytes_read(&self) -> u32 {
+        self.bytes_read
+    }
+
+    /// Returns the number of bytes successfully written, including command bytes
+    pub fn bytes_written(&self) -> u32 {
+        self.bytes_written
+    }
+
+    /// Returns the errors of read operations
+    pub fn read_errors(&self) -> &ErrorCounters {
+        &self.read_errors
+    }
+
+    /// Returns the errors of write operations, including the command writes preceding reads
+    pub fn write_errors(&self) -> &ErrorCounters {
+        &self.write_errors
+    }
+
+    /// Returns the number of errors since the last successful access
+    pub fn failure_streak(&self) -> u32 {
+        self.failure_streak
+    }
+
+    /// Returns the longest failure streak since the last reset
+    pub fn longest_failure_streak(&self) -> u32 {
+        self.longest_failure_streak
+    }
+
+    /// Returns the clock value of the last successful access
+    /// None if no access succeeded since the last reset or no clock is set
+    pub fn last_success(&self) -> Option<u64> {
+        self.last_success
+    }
+
+    /// Records a successful read of the given values, starting at the given register
+    pub(crate) fn record_read(&mut self, register: Register, values: &[u8], timestamp: Option<u64>) {
+        let mut register = register;
+
+        for _ in values {
+            increment(&mut self.reads[register as usize]);
+            register = register.pair();
+        }
+
+        add(&mut self.bytes_written, 1);
+        add(&mut self.bytes_read, values.len());
+        self.record_success(timestamp);
+    }
+
+    /// Records a successful write of the given values, starting at the given register
+    pub(crate) fn record_write(&mut self, register: Register, values: &[u8], timestamp: Option<u64>) {
+        let mut register = register;
+
+        for _ in values {
+            increment(&mut self.writes[register as usize]);
+            register = register.pair();
+        }
+
+        add(&mut self.bytes_written, values.len() + 1);
+        self.record_success(timestamp);
+    }
+
+    /// Records a failed bus operation, which extended the failure streak to the given length
+    pub(crate) fn record_error<E: i2c::Error>(&mut self, error: &Error<E>, failure_streak: u32) {
+        if let (Some(operation), Some(kind)) = (error.operation(), error.bus_error_kind()) {
+            match operation {
+                BusOperation::Read => self.read_errors.record(kind),
+                BusOperation::Write => self.write_errors.record(kind),
+            }
+        }
+
+        self.longest_failure_streak = self.longest_failure_streak.max(failure_streak);
+    }
+
+    /// Returns the snapshot including the current failure streak
+    pub(crate) fn with_failure_streak(mut self, failure_streak: u32) -> Self {
+        self.failure_streak = failure_streak;
+        self.longest_failure_streak = self.longest_failure_streak.max(failure_streak);
+        self
+    }
+
+    fn record_success(&mut self, timestamp: Option<u64>) {
+        if timestamp.is_some() {
+            self.last_success = timestamp;
+        }
+    }
+}
+
+impl ErrorCounters {
+    /// Returns the number of errors of the given kind
+    /// NACKs are counted by source, so [NoAcknowledgeSource::Unknown] just returns the errors of
+    /// unknown source.
+    pub fn get(&self, kind: ErrorKind) -> u32 {
+        match kind {
+            ErrorKind::Bus => self.bus,
+            ErrorKind::ArbitrationLoss => self.arbitration_loss,
+            ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address) => self.nack_address,
+            ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data) => self.nack_data,
+            ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown) => self.nack_unknown,
+            ErrorKind::Overrun => self.overrun,
+            _ => self.other,
+        }
+    }
+
+    /// Returns the total number of errors
+    pub fn total(&self) -> u32 {
+        [
+            self.bus,
+            self.arbitration_loss,
+            self.nack_address,
+            self.nack_data,
+            self.nack_unknown,
+            self.overrun,
+            self.other,
+        ]
+        .iter()
+        .fold(0, |total, count| total.saturating_add(*count))
+    }
+
+    fn record(&mut self, kind: ErrorKind) {
+        let counter = match kind {
+            ErrorKind::Bus => &mut self.bus,
+            ErrorKind::ArbitrationLoss => &mut self.arbitration_loss,
+            ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address) => &mut self.nack_address,
+            ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data) => &mut self.nack_data,
+            ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown) => &mut self.nack_unknown,
+            ErrorKind::Overrun => &mut self.overrun,
+            _ => &mut self.other,
+        };
+
+        increment(counter);
+    }
+}
+
+fn increment(counter: &mut u32) {
+    add(counter, 1);
+}
+
+fn add(counter: &mut u32, count: usize) {
+    *counter = counter.saturating_add(count as u32);
+}
@@ -0,0 +1,378 @@
+//! # Test support
+//!
+//! Expectation based I2C bus for unit tests of code using PCA9539, without depending on a mocking
+//! framework. Each expected operation is matched in order against the actual operations. On
+//! mismatch, the bus panics with a message decoding the register names, e.g.
+//! `Unexpected write to 0x74: Output1 = 0b00000001, expected write to 0x74: Configuration1 = 0b00000001`
+//!
+//! Expectations are stored in a fixed capacity buffer, so the bus is usable in no_std tests as well.
+//!
+//! *Requires activation of `testing` feature*
+//!
+//! ## Example
+//! As the expander takes ownership of the bus, a mutable reference to the bus is passed. After the
+//! test, [ExpectationBus::done()] verifies that all expectations have been met.
+//! ```
+//! use pca9539::address::Address;
+//! use pca9539::address::Strap::High;
+//! use pca9539::expander::Bank::{Bank0, Bank1};
+//! use pca9539::expander::Mode::Output;
+//! use pca9539::expander::PCA9539;
+//! use pca9539::expander::PinID::{Pin1, Pin4};
+//! use pca9539::testing::ExpectationBus;
+//!
+//! const ADDRESS: Address = Address::new(High, High);
+//!
+//! let mut i2c_bus: ExpectationBus<8> = ExpectationBus::new()
+//!     .address(ADDRESS)
+//!     .expect_configuration_write(Bank1, 0b1110_1111)
+//!     .expect_input_read(Bank0, 0b0000_0010);
+//!
+//! let mut expander = PCA9539::new(&mut i2c_bus, ADDRESS);
+//! expander.set_mode(Bank1, Pin4, Output).unwrap();
+//! expander.refresh_input_state(Bank0).unwrap();
+//! assert!(expander.is_pin_input_high(Bank0, Pin1));
+//!
+//! i2c_bus.done();
+//! ```
+//! ## Errors
+//! Bus errors are simulated by expecting failing operations.
+//! ```
+//!# use pca9539::address::Address;
+//!# use pca9539::expander::Bank::Bank0;
+//!# use pca9539::expander::PCA9539;
+//! use pca9539::register::Register;
+//! use pca9539::testing::ExpectationBus;
+//! use embedded_hal::i2c::ErrorKind;
+//!
+//! let mut i2c_bus: ExpectationBus<2> = ExpectationBus::new()
+//!     .expect_write(&[Register::Input0.command()])
+//!     .expect_read_error(1, ErrorKind::Bus);
+//!
+//! let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+//! assert!(expander.refresh_input_state(Bank0).is_err());
+//! ```
+use crate::address::Address;
+use crate::expander::Bank;
+use crate::register::{Register, RegisterValues};
+use core::fmt::{Display, Formatter};
+use embedded_hal::i2c::{Error, ErrorKind, ErrorType, I2c, Operation, SevenBitAddress};
+use heapless::Vec;
+
+/// Maximum number of data bytes per expected operation
+pub const MAX_DATA: usize = 8;
+
+/// I2C bus verifying the operations against a list of expectations
+pub struct ExpectationBus<const N: usize> {
+    expectations: Vec<Expectation, N>,
+
+    /// Address of subsequently added expectations
+    address: u8,
+
+    /// Index of the next expectation to match
+    position: usize,
+
+    /// Register selected by the last write, used for decoding reads
+    pointer: Option<Register>,
+}
+
+/// Error returned for operations expected to fail
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TestingError(pub ErrorKind);
+
+#[derive(Clone, Debug)]
+enum Expectation {
+    Write {
+        address: u8,
+        data: Vec<u8, MAX_DATA>,
+        error: Option<ErrorKind>,
+    },
+    Read {
+        address: u8,
+        data: Vec<u8, MAX_DATA>,
+        error: Option<ErrorKind>,
+    },
+}
+
+/// Human readable description of an operation, decoding register names
+enum Description<'a> {
+    /// Write of the given data
+    Write(u8, &'a [u8]),
+    /// Read of the given data, starting at the given register
+    Read(u8, Option<Register>, &'a [u8]),
+    /// Read of the given number of bytes, starting at the given register
+    ReadLength(u8, Option<Register>, usize),
+}
+
+impl<const N: usize> ExpectationBus<N> {
+    /// Creates a bus without expectations, using the default address (0x74)
+    pub fn new() -> Self {
+        Self {
+            expectations: Vec::new(),
+            address: Address::default().value(),
+            position: 0,
+            pointer: None,
+        }
+    }
+
+    /// Sets the address of all subsequently added expectations
+    pub fn address(mut self, address: Address) -> Self {
+        self.address = address.value();
+        self
+    }
+
+    /// Expects a write of the given data (command byte followed by register values)
+    pub fn expect_write(self, data: &[u8]) -> Self {
+        let address = self.address;
+        self.push(Expectation::Write {
+            address,
+            data: Self::data(data),
+            error: None,
+        })
+    }
+
+    /// Expects a write of the given data, which fails with the given error
+    pub fn expect_write_error(self, data: &[u8], kind: ErrorKind) -> Self {
+        let address = self.address;
+        self.push(Expectation::Write {
+            address,
+            data: Self::data(data),
+            error: Some(kind),
+        })
+    }
+
+    /// Expects a read, returning the given data
+    pub fn expect_read(self, data: &[u8]) -> Self {
+        let address = self.address;
+        self.push(Expectation::Read {
+            address,
+            data: Self::data(data),
+            error: None,
+        })
+    }
+
+    /// Expects a read of the given number of bytes, which fails with the given error
+    pub fn expect_read_error(self, length: usize, kind: ErrorKind) -> Self {
+        assert!(length <= MAX_DATA, "Expectation data exceeds {} bytes", MAX_DATA);
+
+        let address = self.address;
+        let mut data = Vec::new();
+        data.resize(length, 0x0).unwrap();
+
+        self.push(Expectation::Read {
+            address,
+            data,
+            error: Some(kind),
+        })
+    }
+
+    /// Expects a write of the given value to the given register
+    pub fn expect_register_write(self, register: Register, value: u8) -> Self {
+        self.expect_write(&[register.command(), value])
+    }
+
+    /// Expects a read of the given register (command write followed by read), returning the given value
+    pub fn expect_register_read(self, register: Register, value: u8) -> Self {
+        self.expect_write(&[register.command()]).expect_read(&[value])
+    }
+
+    /// Expects a write to the output register of the given bank
+    pub fn expect_output_write(self, bank: Bank, value: u8) -> Self {
+        self.expect_register_write(Register::output(bank), value)
+    }
+
+    /// Expects a write to the polarity inversion register of the given bank
+    pub fn expect_polarity_write(self, bank: Bank, value: u8) -> Self {
+        self.expect_register_write(Register::polarity(bank), value)
+    }
+
+    /// Expects a write to the configuration register of the given bank
+    pub fn expect_configuration_write(self, bank: Bank, value: u8) -> Self {
+        self.expect_register_write(Register::configuration(bank), value)
+    }
+
+    /// Expects a read of the input register of the given bank, returning the given value
+    pub fn expect_input_read(self, bank: Bank, value: u8) -> Self {
+        self.expect_register_read(Register::input(bank), value)
+    }
+
+    /// Returns the number of expectations not met yet
+    pub fn remaining(&self) -> usize {
+        self.expectations.len() - self.position
+    }
+
+    /// Panics if any expectation has not been met
+    pub fn done(&self) {
+        if let Some(expectation) = self.expectations.get(self.position) {
+            panic!(
+                "{} expectation(s) not met, next: {}",
+                self.remaining(),
+                expectation.describe(self.pointer)
+            );
+        }
+    }
+
+    fn push(mut self, expectation: Expectation) -> Self {
+        if self.expectations.push(expectation).is_err() {
+            panic!("Expectation capacity of {} exceeded", N);
+        }
+
+        self
+    }
+
+    fn data(data: &[u8]) -> Vec<u8, MAX_DATA> {
+        match Vec::from_slice(data) {
+            Ok(data) => data,
+            Err(_) => panic!("Expectation data exceeds {} bytes", MAX_DATA),
+        }
+    }
+
+    /// Updates the selected register after the given number of bytes has been transferred
+    fn advance(&mut self, count: usize) {
+        if count % 2 == 1 {
+            self.pointer = self.pointer.map(Register::pair);
+        }
+    }
+
+    fn fail(&self, actual: Description<'_>) -> ! {
+        match self.expectations.get(self.position) {
+            None => panic!("Unexpected {}, no expectations left", actual),
+            Some(expectation) => panic!(
+                "Unexpected {}, expected {} (expectation #{})",
+                actual,
+                expectation.describe(self.pointer),
+                self.position
+            ),
+        }
+    }
+}
+
+impl<const N: usize> Default for ExpectationBus<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> ErrorType for ExpectationBus<N> {
+    type Error = TestingError;
+}
+
+impl<const N: usize> I2c<SevenBitAddress> for ExpectationBus<N> {
+    fn transaction(
+        &mut self,
+        address: SevenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        for operation in operations {
+            match operation {
+                Operation::Write(data) => {
+                    let error = match self.expectations.get(self.position) {
+                        Some(Expectation::Write {
+                            address: expected_address,
+                            data: expected,
+                            error,
+                        }) if *expected_address == address && expected.as_slice() == *data => *error,
+                        _ => self.fail(Description::Write(address, data)),
+                    };
+
+                    self.position += 1;
+
+                    if let Some((command, values)) = data.split_first() {
+                        self.pointer = Register::try_from(*command).ok();
+                        self.advance(values.len());
+                    }
+
+                    if let Some(kind) = error {
+                        return Err(TestingError(kind));
+                    }
+                }
+                Operation::Read(buffer) => {
+                    let error = match self.expectations.get(self.position) {
+                        Some(Expectation::Read {
+                            address: expected_address,
+                            data: expected,
+                            error,
+                        }) if *expected_address == address && expected.len() == buffer.len() => {
+                            buffer.copy_from_slice(expected);
+                            *error
+                        }
+                        _ => self.fail(Description::ReadLength(address, self.pointer, buffer.len())),
+                    };
+
+                    self.position += 1;
+                    self.advance(buffer.len());
+
+                    if let Some(kind) = error {
+                        return Err(TestingError(kind));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Expectation {
+    fn describe(&self, pointer: Option<Register>) -> Description<'_> {
+        match self {
+            Expectation::Write { address, data, .. } => Description::Write(*address, data),
+            Expectation::Read { address, data, .. } => Description::Read(*address, pointer, data),
+        }
+    }
+}
+
+impl Display for Description<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Description::Write(address, data) => {
+                write!(f, "write to {:#04x}: ", address)?;
+
+                match data.split_first() {
+                    None => f.write_str("no data"),
+                    Some((command, values)) => match Register::try_from(*command) {
+                        Err(_) => write!(f, "invalid command {:#04x}", command),
+                        Ok(register) if values.is_empty() => write!(f, "select {}", register),
+                        Ok(register) => fmt_values(f, Some(register), values),
+                    },
+                }
+            }
+            Description::Read(address, pointer, data) => {
+                write!(f, "read from {:#04x}: ", address)?;
+                fmt_values(f, *pointer, data)
+            }
+            Description::ReadLength(address, pointer, length) => {
+                write!(f, "read of {} byte(s) from {:#04x}", length, address)?;
+
+                match pointer {
+                    None => Ok(()),
+                    Some(register) => write!(f, " starting at {}", register),
+                }
+            }
+        }
+    }
+}
+
+/// Formats values of consecutive registers, or raw values if no register is selected
+fn fmt_values(f: &mut Formatter<'_>, register: Option<Register>, values: &[u8]) -> core::fmt::Result {
+    match register {
+        Some(register) => write!(f, "{}", RegisterValues::new(register, values)),
+        None => {
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+
+                write!(f, "{:#04x}", value)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+impl Error for TestingError {
+    fn kind(&self) -> ErrorKind {
+        self.0
+    }
+}
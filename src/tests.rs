@@ -1,16 +1,58 @@
+use crate::address::Strap::{High, Low};
+use crate::address::{Address, InvalidAddress};
+use crate::config::{BankConfig, Config, PinConfig};
+use crate::decoder::{CsvError, CsvFormat, CsvParser, Decoder, TooManyAddresses};
+use crate::error::{BusOperation, Error};
 use crate::expander::Bank::{Bank0, Bank1};
 use crate::expander::Mode::{Input, Output};
 use crate::expander::PinID::{Pin0, Pin1, Pin2, Pin3, Pin4, Pin5, Pin6, Pin7};
 use crate::expander::PCA9539;
-#[cfg(not(feature = "spin"))]
-use crate::guard::LockFreeGuard;
+use crate::fault::{Fault, FaultPolicy, FaultyBus, RandomFaults, ScheduledFaults};
 #[cfg(feature = "spin")]
 use crate::guard::SpinGuard;
-use crate::mocks::{BusMockBuilder, DummyError, MockI2CBus};
+use crate::guard::{AccessError, LockFreeGuard, RefGuard};
+use crate::interlock::{Interlock, InterlockPin, Resolution};
+use crate::interrupt::{DispatchError, InterruptDispatcher};
+use crate::link::{LinkChange, LinkPolicy, LinkState, Recovery};
+use crate::mocks::{mock_line, BusMockBuilder, DummyError, MockI2CBus};
+use crate::monitor::OutputMonitor;
+use crate::multi::{locate, Expanders, MultiError};
+use crate::pin_map;
 use crate::pin_refreshable::{RefreshableInputPin, RefreshableOutputPin};
-use crate::pins::Pins;
+use crate::pins::{Pin, Pins};
+use crate::probe::{probe, probe_address};
+use crate::profile::Profile;
+use crate::recording::{ParseError, RecordedOperation, RecordingBus, ReplayBus, Trace};
+use crate::register::{InvalidCommand, Register, RegisterDump};
+use crate::retry::{NoDelay, RetryBus, RetryPolicy, RetryStatistics};
+use crate::safe_state::SafeState;
+use crate::simulator::{SimulatedBus, SimulatedInterruptLine, SimulatedPCA9539};
+use crate::snapshot::{Snapshot, SnapshotError};
 use crate::sync_state::SyncState;
+use crate::testing::ExpectationBus;
+use core::cell::{Cell, RefCell};
+use embedded_hal::delay::DelayNs;
 use embedded_hal::digital::{InputPin, OutputPin, PinState, StatefulOutputPin};
+use embedded_hal::i2c::{Error as _, ErrorKind, I2c, NoAcknowledgeSource, Operation};
+use embedded_hal_bus::i2c::RefCellDevice;
+
+/// Returns the expected error of a failed write operation on the given register
+fn write_error(register: Register) -> Error<DummyError> {
+    Error::Bus {
+        error: DummyError::WriteError,
+        operation: BusOperation::Write,
+        register: Some(register),
+    }
+}
+
+/// Returns the expected error of a failed read operation on the given register
+fn read_error(register: Register) -> Error<DummyError> {
+    Error::Bus {
+        error: DummyError::ReadError,
+        operation: BusOperation::Read,
+        register: Some(register),
+    }
+}
 
 #[test]
 fn test_expander_output_mode_bank0() {
@@ -19,7 +61,7 @@ fn test_expander_output_mode_bank0() {
         .expect_write(1, &[0x06, 0b1111_0110])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.set_mode(Bank0, Pin3, Output).unwrap();
     expander.set_mode(Bank0, Pin0, Output).unwrap();
 }
@@ -31,7 +73,7 @@ fn test_expander_output_mode_bank1() {
         .expect_write(1, &[0x07, 0b0011_1111])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.set_mode(Bank1, Pin6, Output).unwrap();
     expander.set_mode(Bank1, Pin7, Output).unwrap();
 }
@@ -44,7 +86,7 @@ fn test_expander_input_mode_bank0() {
         .expect_write(1, &[0x06, 0b1000_0100])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.set_mode_all(Bank0, Output).unwrap();
     expander.set_mode(Bank0, Pin2, Input).unwrap();
     expander.set_mode(Bank0, Pin7, Input).unwrap();
@@ -58,7 +100,7 @@ fn test_expander_input_mode_bank1() {
         .expect_write(1, &[0x07, 0b0000_1001])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.set_mode_all(Bank1, Output).unwrap();
     expander.set_mode(Bank1, Pin0, Input).unwrap();
     expander.set_mode(Bank1, Pin3, Input).unwrap();
@@ -71,10 +113,10 @@ fn test_expander_state_low_bank0() {
         .expect_write(1, &[0x02, 0b1110_1101])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
-    expander.set_state(Bank0, Pin1, false);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+    expander.set_state(Bank0, Pin1, false).unwrap();
     expander.write_output_state(Bank0).unwrap();
-    expander.set_state(Bank0, Pin4, false);
+    expander.set_state(Bank0, Pin4, false).unwrap();
     expander.write_output_state(Bank0).unwrap();
 }
 
@@ -85,10 +127,10 @@ fn test_expander_state_low_bank1() {
         .expect_write(1, &[0x03, 0b1111_1001])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
-    expander.set_state(Bank1, Pin2, false);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+    expander.set_state(Bank1, Pin2, false).unwrap();
     expander.write_output_state(Bank1).unwrap();
-    expander.set_state(Bank1, Pin1, false);
+    expander.set_state(Bank1, Pin1, false).unwrap();
     expander.write_output_state(Bank1).unwrap();
 }
 
@@ -100,11 +142,11 @@ fn test_expander_state_high_bank0() {
         .expect_write(1, &[0x02, 0b0010_0001])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.set_state_all(Bank0, false).unwrap();
-    expander.set_state(Bank0, Pin5, true);
+    expander.set_state(Bank0, Pin5, true).unwrap();
     expander.write_output_state(Bank0).unwrap();
-    expander.set_state(Bank0, Pin0, true);
+    expander.set_state(Bank0, Pin0, true).unwrap();
     expander.write_output_state(Bank0).unwrap();
 }
 
@@ -116,11 +158,11 @@ fn test_expander_state_high_bank1() {
         .expect_write(1, &[0x03, 0b0101_0000])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.set_state_all(Bank1, false).unwrap();
-    expander.set_state(Bank1, Pin6, true);
+    expander.set_state(Bank1, Pin6, true).unwrap();
     expander.write_output_state(Bank1).unwrap();
-    expander.set_state(Bank1, Pin4, true);
+    expander.set_state(Bank1, Pin4, true).unwrap();
     expander.write_output_state(Bank1).unwrap();
 }
 
@@ -131,7 +173,7 @@ fn test_set_mode_all_input_bank0() {
         .expect_write(1, &[0x06, 0b1111_1111])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.set_mode_all(Bank0, Output).unwrap();
     expander.set_mode_all(Bank0, Input).unwrap();
 }
@@ -140,7 +182,7 @@ fn test_set_mode_all_input_bank0() {
 fn test_set_mode_all_output_bank0() {
     let i2c_bus = BusMockBuilder::new().expect_write(1, &[0x06, 0b0000_0000]).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.set_mode_all(Bank0, Output).unwrap();
 }
 
@@ -151,7 +193,7 @@ fn test_set_mode_all_input_bank1() {
         .expect_write(1, &[0x07, 0b1111_1111])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.set_mode_all(Bank1, Output).unwrap();
     expander.set_mode_all(Bank1, Input).unwrap();
 }
@@ -160,7 +202,7 @@ fn test_set_mode_all_input_bank1() {
 fn test_set_mode_all_output_bank1() {
     let i2c_bus = BusMockBuilder::new().expect_write(1, &[0x07, 0b0000_0000]).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.set_mode_all(Bank1, Output).unwrap();
 }
 
@@ -171,7 +213,7 @@ fn test_set_state_all_low_bank0() {
         .expect_write(1, &[0x02, 0b0000_0000])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.set_state_all(Bank0, true).unwrap();
     expander.set_state_all(Bank0, false).unwrap();
 }
@@ -183,7 +225,7 @@ fn test_set_state_all_low_bank1() {
         .expect_write(1, &[0x03, 0b0000_0000])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.set_state_all(Bank1, true).unwrap();
     expander.set_state_all(Bank1, false).unwrap();
 }
@@ -192,7 +234,7 @@ fn test_set_state_all_low_bank1() {
 fn test_set_state_all_high_bank0() {
     let i2c_bus = BusMockBuilder::new().expect_write(1, &[0x02, 0b1111_1111]).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.set_state_all(Bank0, true).unwrap();
 }
 
@@ -200,7 +242,7 @@ fn test_set_state_all_high_bank0() {
 fn test_set_state_all_high_bank1() {
     let i2c_bus = BusMockBuilder::new().expect_write(1, &[0x03, 0b1111_1111]).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.set_state_all(Bank1, true).unwrap();
 }
 
@@ -212,7 +254,7 @@ fn test_reverse_polarity_bank0() {
         .expect_write(1, &[0x04, 0b0001_0000])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.reverse_polarity(Bank0, Pin2, true).unwrap();
     expander.reverse_polarity(Bank0, Pin4, true).unwrap();
     expander.reverse_polarity(Bank0, Pin2, false).unwrap();
@@ -225,7 +267,7 @@ fn test_refresh_input_state_bank0_success() {
         .expect_read(1, 0b0001_0000)
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.refresh_input_state(Bank0).unwrap();
 }
 
@@ -236,7 +278,7 @@ fn test_refresh_input_state_bank1_success() {
         .expect_read(1, 0b0001_0000)
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.refresh_input_state(Bank1).unwrap();
 }
 
@@ -244,20 +286,20 @@ fn test_refresh_input_state_bank1_success() {
 fn test_refresh_input_state_write_error() {
     let i2c_bus = BusMockBuilder::new().write_error(0x00).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let result = expander.refresh_input_state(Bank0);
 
-    assert_eq!("WriteError", result.unwrap_err().to_string().as_str());
+    assert_eq!(write_error(Register::Input0), result.unwrap_err());
 }
 
 #[test]
 fn test_refresh_input_state_read_error() {
     let i2c_bus = BusMockBuilder::new().expect_write(1, &[0x00]).read_error().into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let result = expander.refresh_input_state(Bank0);
 
-    assert_eq!("ReadError", result.unwrap_err().to_string());
+    assert_eq!(read_error(Register::Input0), result.unwrap_err());
 }
 
 #[test]
@@ -267,7 +309,7 @@ fn test_is_pin_high_bank0() {
         .expect_read(1, 0b0111_1010)
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.refresh_input_state(Bank0).unwrap();
 
     assert!(!expander.is_pin_input_high(Bank0, Pin7));
@@ -288,7 +330,7 @@ fn test_is_pin_high_bank1() {
         .expect_read(1, 0b0100_0111)
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.refresh_input_state(Bank1).unwrap();
 
     assert!(!expander.is_pin_input_high(Bank1, Pin7));
@@ -315,7 +357,7 @@ fn test_regular_pin_input_bank0() {
         .expect_read(1, 0b0100_0000)
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let mut pin = pins.get_pin(Bank0, Pin2);
 
@@ -338,7 +380,7 @@ fn test_regular_pin_input_bank1() {
         .expect_read(1, 0b0000_0000)
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let mut pin = pins.get_pin(Bank1, Pin6);
 
@@ -352,22 +394,22 @@ fn test_regular_pin_input_bank1() {
 fn test_regular_pin_input_write_error() {
     let i2c_bus = BusMockBuilder::new().write_error(0x01).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let mut pin = pins.get_pin(Bank1, Pin6);
 
-    assert_eq!("WriteError", pin.is_high().unwrap_err().to_string())
+    assert_eq!(write_error(Register::Input1), pin.is_high().unwrap_err())
 }
 
 #[test]
 fn test_regular_pin_input_read_error() {
     let i2c_bus = BusMockBuilder::new().mock_transaction(1).read_error().into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let mut pin = pins.get_pin(Bank1, Pin6);
 
-    assert_eq!("ReadError", pin.is_high().unwrap_err().to_string())
+    assert_eq!(read_error(Register::Input1), pin.is_high().unwrap_err())
 }
 
 #[test]
@@ -379,7 +421,7 @@ fn test_refreshable_pin_input_bank0() {
         .expect_read(1, 0b0100_1000)
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
 
     let mut pin02 = pins.get_refreshable_pin(Bank0, Pin2);
@@ -407,7 +449,7 @@ fn test_refreshable_pin_input_bank1() {
         .expect_read(1, 0b0000_0000)
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
 
     let mut pin12 = pins.get_refreshable_pin(Bank1, Pin2);
@@ -439,7 +481,7 @@ fn test_refreshable_pin_input_mixed_banks() {
         .expect_read(1, 0b0000_0000)
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
 
     let mut pin00 = pins.get_refreshable_pin(Bank0, Pin0);
@@ -462,13 +504,13 @@ fn test_refreshable_pin_input_mixed_banks() {
 fn test_refreshable_pin_refresh_bank_write_error() {
     let i2c_bus = BusMockBuilder::new().write_error(0x0).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
 
     let mut pin = pins.get_refreshable_pin(Bank0, Pin0);
     let error = pin.refresh_bank().unwrap_err();
 
-    assert_eq!("WriteError", error.to_string());
+    assert_eq!(write_error(Register::Input0), error);
     assert!(pin.is_low().unwrap());
 }
 
@@ -476,13 +518,13 @@ fn test_refreshable_pin_refresh_bank_write_error() {
 fn test_refreshable_pin_refresh_bank_read_error() {
     let i2c_bus = BusMockBuilder::new().expect_write(1, &[0x00]).read_error().into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
 
     let mut pin = pins.get_refreshable_pin(Bank0, Pin0);
     let error = pin.refresh_bank().unwrap_err();
 
-    assert_eq!("ReadError", error.to_string());
+    assert_eq!(read_error(Register::Input0), error);
     assert!(pin.is_low().unwrap());
 }
 
@@ -494,13 +536,13 @@ fn test_refreshable_pin_refresh_all_write_error() {
         .write_error(0x1)
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
 
     let mut pin = pins.get_refreshable_pin(Bank0, Pin0);
     let error = pin.refresh_all().unwrap_err();
 
-    assert_eq!("WriteError", error.to_string());
+    assert_eq!(write_error(Register::Input1), error);
     assert!(pin.is_low().unwrap());
 }
 
@@ -513,13 +555,13 @@ fn test_refreshable_pin_refresh_all_read_error() {
         .read_error()
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
 
     let mut pin = pins.get_refreshable_pin(Bank0, Pin0);
     let error = pin.refresh_all().unwrap_err();
 
-    assert_eq!("ReadError", error.to_string());
+    assert_eq!(read_error(Register::Input1), error);
     assert!(pin.is_low().unwrap());
 }
 
@@ -536,7 +578,7 @@ fn test_regular_pin_set_output_state() {
         .expect_write(1, &[0x03, 0b1111_1111])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let mut pin00 = pins.get_pin(Bank0, Pin0).into_output_pin(PinState::High).unwrap();
     let mut pin04 = pins.get_pin(Bank0, Pin4).into_output_pin(PinState::High).unwrap();
@@ -575,36 +617,36 @@ fn test_regular_pin_set_output_state() {
 fn test_regular_pin_set_low_write_error() {
     let i2c_bus = BusMockBuilder::new().mock_transaction(2).write_error(0x2).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let mut pin = pins.get_pin(Bank0, Pin0).into_output_pin(PinState::Low).unwrap();
 
     let result = pin.set_low();
-    assert_eq!("WriteError", result.unwrap_err().to_string());
+    assert_eq!(write_error(Register::Output0), result.unwrap_err());
 }
 
 #[test]
 fn test_regular_pin_set_high_write_error() {
     let i2c_bus = BusMockBuilder::new().mock_transaction(2).write_error(0x2).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let mut pin = pins.get_pin(Bank0, Pin0).into_output_pin(PinState::Low).unwrap();
 
     let result = pin.set_high();
-    assert_eq!("WriteError", result.unwrap_err().to_string());
+    assert_eq!(write_error(Register::Output0), result.unwrap_err());
 }
 
 #[test]
 fn test_regular_pin_set_state_write_error() {
     let i2c_bus = BusMockBuilder::new().mock_transaction(2).write_error(0x2).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let mut pin = pins.get_pin(Bank0, Pin0).into_output_pin(PinState::Low).unwrap();
 
     let result = pin.set_state(PinState::High);
-    assert_eq!("WriteError", result.unwrap_err().to_string());
+    assert_eq!(write_error(Register::Output0), result.unwrap_err());
 }
 
 #[test]
@@ -618,7 +660,7 @@ fn test_refreshable_pin_set_output_state() {
         .expect_write(1, &[0x03, 0b1110_0000]) // Update all
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.set_state_all(Bank0, false).unwrap();
     expander.set_state_all(Bank1, false).unwrap();
 
@@ -674,12 +716,12 @@ fn test_refreshable_pin_set_output_state() {
 fn test_refreshable_pin_update_bank_write_error() {
     let i2c_bus = BusMockBuilder::new().mock_transaction(2).write_error(0x2).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let pin = pins.get_refreshable_pin(Bank0, Pin0).into_output_pin(PinState::Low).unwrap();
 
     let result = pin.update_bank();
-    assert_eq!(DummyError::WriteError, result.unwrap_err());
+    assert_eq!(write_error(Register::Output0), result.unwrap_err());
 }
 
 #[test]
@@ -690,12 +732,12 @@ fn test_refreshable_pin_update_all_write_error() {
         .write_error(0x3)
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let pin = pins.get_refreshable_pin(Bank1, Pin0).into_output_pin(PinState::Low).unwrap();
 
     let result = pin.update_all();
-    assert_eq!(DummyError::WriteError, result.unwrap_err());
+    assert_eq!(write_error(Register::Output1), result.unwrap_err());
 }
 
 #[test]
@@ -706,7 +748,7 @@ fn test_regular_pin_into_output_pin() {
         .expect_write(1, &[0x02, 0b0000_0001])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.set_state_all(Bank0, false).unwrap();
     let pins = get_pins(&mut expander);
     let _pin = pins.get_pin(Bank0, Pin0).into_output_pin(PinState::High).unwrap();
@@ -719,7 +761,7 @@ fn test_regular_pin_into_input_pin() {
         .expect_write(1, &[0x06, 0b1111_1111])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
 
     let pins = get_pins(&mut expander);
     let _pin = pins
@@ -734,7 +776,7 @@ fn test_regular_pin_into_input_pin() {
 fn test_regular_pin_into_output_pin_mode_switch_error() {
     let i2c_bus = BusMockBuilder::new().write_error(0x6).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let result = pins.get_pin(Bank0, Pin0).into_output_pin(PinState::High);
 
@@ -745,7 +787,7 @@ fn test_regular_pin_into_output_pin_mode_switch_error() {
 fn test_regular_pin_into_output_pin_state_set_error() {
     let i2c_bus = BusMockBuilder::new().mock_transaction(1).write_error(0x2).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let result = pins.get_pin(Bank0, Pin0).into_output_pin(PinState::High);
 
@@ -756,7 +798,7 @@ fn test_regular_pin_into_output_pin_state_set_error() {
 fn test_regular_pin_into_input_pin_mode_error() {
     let i2c_bus = BusMockBuilder::new().write_error(0x6).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let result = pins.get_pin(Bank0, Pin0).into_output_pin(PinState::High);
 
@@ -771,7 +813,7 @@ fn test_refreshable_pin_into_output_pin() {
         .expect_write(1, &[0x02, 0b0000_0001])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     expander.set_state_all(Bank0, false).unwrap();
     let pins = get_pins(&mut expander);
     let _pin = pins.get_refreshable_pin(Bank0, Pin0).into_output_pin(PinState::High).unwrap();
@@ -784,7 +826,7 @@ fn test_refreshable_pin_into_input_pin() {
         .expect_write(1, &[0x06, 0b1111_1111])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
 
     let pins = get_pins(&mut expander);
     let _pin = pins
@@ -799,7 +841,7 @@ fn test_refreshable_pin_into_input_pin() {
 fn test_refreshable_pin_into_output_pin_mode_switch_error() {
     let i2c_bus = BusMockBuilder::new().write_error(0x6).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let result = pins.get_refreshable_pin(Bank0, Pin0).into_output_pin(PinState::High);
 
@@ -810,7 +852,7 @@ fn test_refreshable_pin_into_output_pin_mode_switch_error() {
 fn test_refreshable_pin_into_output_pin_state_set_error() {
     let i2c_bus = BusMockBuilder::new().mock_transaction(1).write_error(0x2).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let result = pins.get_refreshable_pin(Bank0, Pin0).into_output_pin(PinState::High);
 
@@ -821,7 +863,7 @@ fn test_refreshable_pin_into_output_pin_state_set_error() {
 fn test_refreshable_pin_into_input_pin_mode_error() {
     let i2c_bus = BusMockBuilder::new().write_error(0x6).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let result = pins.get_refreshable_pin(Bank0, Pin0).into_output_pin(PinState::High);
 
@@ -835,7 +877,7 @@ fn test_regular_pin_toggle() {
         .expect_write(1, &[0x02, 0b1111_1011])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let mut pin = pins.get_pin(Bank0, Pin2).into_output_pin(PinState::High).unwrap();
 
@@ -849,12 +891,12 @@ fn test_regular_pin_toggle_error() {
         .write_error(0x2)
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let mut pin = pins.get_pin(Bank0, Pin2).into_output_pin(PinState::High).unwrap();
 
     let result = pin.toggle();
-    assert_eq!("WriteError", result.unwrap_err().to_string());
+    assert_eq!(write_error(Register::Output0), result.unwrap_err());
 }
 
 #[test]
@@ -864,7 +906,7 @@ fn test_refreshable_pin_toggle() {
         .expect_write(1, &[0x02, 0b1111_0111])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let mut pin = pins.get_refreshable_pin(Bank0, Pin3).into_output_pin(PinState::High).unwrap();
 
@@ -878,7 +920,7 @@ fn test_refreshable_pin_toggle_no_update() {
         .mock_transaction(2) // Mode switch
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
     let pins = get_pins(&mut expander);
     let mut pin = pins.get_refreshable_pin(Bank0, Pin3).into_output_pin(PinState::High).unwrap();
 
@@ -892,7 +934,7 @@ fn test_regular_pin_invert_polarity() {
         .expect_write(1, &[0x04, 0b0000_0000])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
 
     let pins = get_pins(&mut expander);
     let pin = pins.get_pin(Bank0, Pin4);
@@ -905,13 +947,13 @@ fn test_regular_pin_invert_polarity() {
 fn test_regular_pin_invert_polarity_error() {
     let i2c_bus = BusMockBuilder::new().write_error(0x04).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
 
     let pins = get_pins(&mut expander);
     let pin = pins.get_pin(Bank0, Pin4);
 
     let result = pin.invert_polarity(true);
-    assert_eq!(DummyError::WriteError, result.unwrap_err());
+    assert_eq!(write_error(Register::Polarity0), result.unwrap_err());
 }
 
 #[test]
@@ -921,7 +963,7 @@ fn test_refreshable_pin_invert_polarity() {
         .expect_write(1, &[0x05, 0b0000_0000])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
 
     let pins = get_pins(&mut expander);
     let pin = pins.get_refreshable_pin(Bank1, Pin5);
@@ -934,13 +976,13 @@ fn test_refreshable_pin_invert_polarity() {
 fn test_refreshable_pin_invert_polarity_error() {
     let i2c_bus = BusMockBuilder::new().write_error(0x05).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
 
     let pins = get_pins(&mut expander);
     let pin = pins.get_refreshable_pin(Bank1, Pin4);
 
     let result = pin.invert_polarity(true);
-    assert_eq!(DummyError::WriteError, result.unwrap_err());
+    assert_eq!(write_error(Register::Polarity1), result.unwrap_err());
 }
 
 #[test]
@@ -966,7 +1008,7 @@ fn test_expander_sync_state_success() {
         .expect_write(1, &[0x07, 0b1101_1011])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
 
     let pins = get_pins(&mut expander);
     let pin1 = pins.get_pin(Bank0, Pin4);
@@ -987,8 +1029,8 @@ fn test_expander_sync_state_success() {
 fn test_expander_sync_state_error_polarity_registers() {
     let i2c_bus = BusMockBuilder::new().write_error(0x4).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
-    assert_eq!(DummyError::WriteError, expander.sync_state().unwrap_err());
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+    assert_eq!(write_error(Register::Polarity0), expander.sync_state().unwrap_err());
 }
 
 #[test]
@@ -1001,8 +1043,8 @@ fn test_expander_sync_state_error_output_state_registers() {
         .write_error(0x2)
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
-    assert_eq!(DummyError::WriteError, expander.sync_state().unwrap_err());
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+    assert_eq!(write_error(Register::Output0), expander.sync_state().unwrap_err());
 }
 
 #[test]
@@ -1018,8 +1060,11 @@ fn test_expander_sync_state_error_pin_mode_registers() {
         .write_error(0x06)
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
-    assert_eq!(DummyError::WriteError, expander.sync_state().unwrap_err());
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+    assert_eq!(
+        write_error(Register::Configuration0),
+        expander.sync_state().unwrap_err()
+    );
 }
 
 #[test]
@@ -1040,7 +1085,7 @@ fn test_regular_pin_sync_state() {
         .expect_write(1, &[0x07, 0b1111_1111])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
 
     let pins = get_pins(&mut expander);
     let pin = pins.get_pin(Bank0, Pin6);
@@ -1056,12 +1101,12 @@ fn test_regular_pin_sync_state() {
 fn test_regular_pin_sync_state_error() {
     let i2c_bus = BusMockBuilder::new().write_error(0x4).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
 
     let pins = get_pins(&mut expander);
     let pin = pins.get_pin(Bank0, Pin6);
 
-    assert_eq!(DummyError::WriteError, pin.sync_state().unwrap_err());
+    assert_eq!(write_error(Register::Polarity0), pin.sync_state().unwrap_err());
 }
 
 #[test]
@@ -1082,7 +1127,7 @@ fn test_refreshable_pin_sync_state() {
         .expect_write(1, &[0x07, 0b1111_0111])
         .into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
 
     let pins = get_pins(&mut expander);
     let pin = pins.get_refreshable_pin(Bank1, Pin3);
@@ -1098,12 +1143,2887 @@ fn test_refreshable_pin_sync_state() {
 fn test_refreshable_pin_sync_state_error() {
     let i2c_bus = BusMockBuilder::new().write_error(0x4).into_mock();
 
-    let mut expander = PCA9539::new(i2c_bus, 0x74);
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
 
     let pins = get_pins(&mut expander);
     let pin = pins.get_pin(Bank1, Pin2);
 
-    assert_eq!(DummyError::WriteError, pin.sync_state().unwrap_err());
+    assert_eq!(write_error(Register::Polarity0), pin.sync_state().unwrap_err());
+}
+
+#[test]
+fn test_interrupt_dispatch_line_not_asserted() {
+    let mut expander = PCA9539::new(BusMockBuilder::new().into_mock(), Address::default());
+
+    let mut dispatcher: InterruptDispatcher<_, _, 2> = InterruptDispatcher::new(mock_line(&[true]));
+    dispatcher.register(&mut expander).unwrap();
+
+    let read = dispatcher.dispatch(|_| panic!("Unexpected change event")).unwrap();
+    assert_eq!(0, read);
+}
+
+#[test]
+fn test_interrupt_dispatch_stops_after_deassert() {
+    let i2c_bus = BusMockBuilder::new()
+        .expect_write(1, &[0x00])
+        .expect_read_pair(1, [0b0000_0100, 0b1000_0000])
+        .into_mock();
+    let mut expander_0 = PCA9539::new(i2c_bus, Address::default());
+    let mut expander_1 = PCA9539::new(BusMockBuilder::new().into_mock(), Address::default());
+
+    let mut dispatcher: InterruptDispatcher<_, _, 2> = InterruptDispatcher::new(mock_line(&[false, true]));
+    assert_eq!(0, dispatcher.register(&mut expander_0).unwrap());
+    assert_eq!(1, dispatcher.register(&mut expander_1).unwrap());
+
+    let mut changes = Vec::new();
+    let read = dispatcher.dispatch(|change| changes.push(change)).unwrap();
+
+    assert_eq!(1, read);
+    assert_eq!(1, changes.len());
+    assert_eq!(0, changes[0].device);
+    assert_eq!(0b0000_0100, changes[0].changed(Bank0));
+    assert_eq!(0b1000_0000, changes[0].changed(Bank1));
+    assert!(changes[0].has_changed(Bank0, Pin2));
+    assert!(changes[0].is_high(Bank0, Pin2));
+    assert!(!changes[0].has_changed(Bank0, Pin3));
+    assert!(changes[0].has_changed(Bank1, Pin7));
+}
+
+#[test]
+fn test_interrupt_dispatch_reports_changed_devices_only() {
+    let i2c_bus_0 = BusMockBuilder::new()
+        .expect_write(1, &[0x00])
+        .expect_read_pair(1, [0b0000_0000, 0b0000_0000])
+        .into_mock();
+    let i2c_bus_1 = BusMockBuilder::new()
+        .expect_write(1, &[0x00])
+        .expect_read_pair(1, [0b0000_0000, 0b0001_0000])
+        .into_mock();
+    let mut expander_0 = PCA9539::new(i2c_bus_0, Address::default());
+    let mut expander_1 = PCA9539::new(i2c_bus_1, Address::default());
+
+    let mut dispatcher: InterruptDispatcher<_, _, 2> = InterruptDispatcher::new(mock_line(&[false, false]));
+    dispatcher.register(&mut expander_0).unwrap();
+    dispatcher.register(&mut expander_1).unwrap();
+
+    let mut changes = Vec::new();
+    let read = dispatcher.dispatch(|change| changes.push(change)).unwrap();
+
+    assert_eq!(2, read);
+    assert_eq!(1, changes.len());
+    assert_eq!(1, changes[0].device);
+    assert!(changes[0].has_changed(Bank1, Pin4));
+    assert!(changes[0].is_high(Bank1, Pin4));
+    assert!(dispatcher.device(1).unwrap().is_pin_input_high(Bank1, Pin4));
+}
+
+#[test]
+fn test_interrupt_dispatch_bus_error() {
+    let i2c_bus_1 = BusMockBuilder::new().write_error(0x00).into_mock();
+    let i2c_bus_0 = BusMockBuilder::new().mock_transaction(2).into_mock();
+    let mut expander_0 = PCA9539::new(i2c_bus_0, Address::default());
+    let mut expander_1 = PCA9539::new(i2c_bus_1, Address::default());
+
+    let mut dispatcher: InterruptDispatcher<_, _, 2> = InterruptDispatcher::new(mock_line(&[false, false]));
+    dispatcher.register(&mut expander_0).unwrap();
+    dispatcher.register(&mut expander_1).unwrap();
+
+    match dispatcher.dispatch(|_| {}).unwrap_err() {
+        DispatchError::BusError(device, error) => {
+            assert_eq!(1, device);
+            assert_eq!(write_error(Register::Input0), error);
+        }
+        DispatchError::LineError(_) => panic!("Expected bus error"),
+    }
+}
+
+#[test]
+fn test_interrupt_dispatch_error_debug() {
+    type LineErrorKind = embedded_hal::digital::ErrorKind;
+
+    let error: DispatchError<MockI2CBus, LineErrorKind> = DispatchError::LineError(LineErrorKind::Other);
+    assert_eq!("DispatchError::LineError(Other)", format!("{:?}", error));
+
+    let error: DispatchError<MockI2CBus, LineErrorKind> =
+        DispatchError::BusError(1, write_error(Register::Input0));
+    assert!(format!("{:?}", error).starts_with("DispatchError::BusError(1, Bus {"));
+}
+
+#[test]
+fn test_interrupt_register_capacity_exhausted() {
+    let mut expander_0 = PCA9539::new(BusMockBuilder::new().into_mock(), Address::default());
+    let mut expander_1 = PCA9539::new(BusMockBuilder::new().into_mock(), Address::default());
+
+    let mut dispatcher: InterruptDispatcher<_, _, 1> = InterruptDispatcher::new(mock_line(&[]));
+    dispatcher.register(&mut expander_0).unwrap();
+    assert!(dispatcher.register(&mut expander_1).is_err());
+}
+
+#[test]
+fn test_multi_locate() {
+    assert!(matches!(locate(0), (0, Bank0, Pin0)));
+    assert!(matches!(locate(15), (0, Bank1, Pin7)));
+    assert!(matches!(locate(17), (1, Bank0, Pin1)));
+    assert!(matches!(locate(60), (3, Bank1, Pin4)));
+}
+
+#[test]
+fn test_multi_set_mode() {
+    let i2c_bus_0 = BusMockBuilder::new().into_mock();
+    let i2c_bus_1 = BusMockBuilder::new().expect_write(1, &[0x07, 0b1111_0111]).into_mock();
+
+    let mut expanders = Expanders::new([
+        PCA9539::new(i2c_bus_0, Address::default()),
+        PCA9539::new(i2c_bus_1, Address::default()),
+    ]);
+    expanders.set_mode(27, Output).unwrap();
+}
+
+#[test]
+fn test_multi_invalid_index() {
+    let i2c_bus_0 = BusMockBuilder::new().into_mock();
+    let i2c_bus_1 = BusMockBuilder::new().into_mock();
+
+    let mut expanders = Expanders::new([
+        PCA9539::new(i2c_bus_0, Address::default()),
+        PCA9539::new(i2c_bus_1, Address::default()),
+    ]);
+    assert_eq!(
+        MultiError::InvalidIndex(32),
+        expanders.set_mode(32, Output).unwrap_err()
+    );
+    assert_eq!(MultiError::InvalidIndex(40), expanders.set_state(40, true).unwrap_err());
+    assert!(expanders.is_pin_input_high(32).is_err());
+
+    let pins = expanders.pins();
+    assert!(pins.get_pin(32).is_none());
+    assert!(pins.get_refreshable_pin(32).is_none());
+}
+
+#[test]
+fn test_multi_refresh_input_state_all() {
+    let i2c_bus_0 = BusMockBuilder::new()
+        .expect_write(1, &[0x00])
+        .expect_read(1, 0b0000_0001)
+        .expect_write(1, &[0x01])
+        .expect_read(1, 0b0000_0000)
+        .into_mock();
+    let i2c_bus_1 = BusMockBuilder::new()
+        .expect_write(1, &[0x00])
+        .expect_read(1, 0b0000_0000)
+        .expect_write(1, &[0x01])
+        .expect_read(1, 0b1000_0000)
+        .into_mock();
+
+    let mut expanders = Expanders::new([
+        PCA9539::new(i2c_bus_0, Address::default()),
+        PCA9539::new(i2c_bus_1, Address::default()),
+    ]);
+    expanders.refresh_input_state_all().unwrap();
+
+    assert!(expanders.is_pin_input_high(0).unwrap());
+    assert!(!expanders.is_pin_input_high(16).unwrap());
+    assert!(expanders.is_pin_input_high(31).unwrap());
+}
+
+#[test]
+fn test_multi_refresh_input_state_all_error() {
+    let i2c_bus_0 = BusMockBuilder::new().mock_transaction(4).into_mock();
+    let i2c_bus_1 = BusMockBuilder::new().expect_write(1, &[0x00]).read_error().into_mock();
+
+    let mut expanders = Expanders::new([
+        PCA9539::new(i2c_bus_0, Address::default()),
+        PCA9539::new(i2c_bus_1, Address::default()),
+    ]);
+
+    match expanders.refresh_input_state_all().unwrap_err() {
+        MultiError::DeviceError(device, error) => {
+            assert_eq!(1, device);
+            assert_eq!(read_error(Register::Input0), error);
+        }
+        MultiError::InvalidIndex(_) => panic!("Expected device error"),
+    }
+}
+
+#[test]
+fn test_multi_write_output_state_all() {
+    let i2c_bus_0 = BusMockBuilder::new()
+        .expect_write(1, &[0x02, 0b1111_1111])
+        .expect_write(1, &[0x03, 0b1111_1011])
+        .into_mock();
+    let i2c_bus_1 = BusMockBuilder::new()
+        .expect_write(1, &[0x02, 0b1111_1110])
+        .expect_write(1, &[0x03, 0b1111_1111])
+        .into_mock();
+
+    let mut expanders = Expanders::new([
+        PCA9539::new(i2c_bus_0, Address::default()),
+        PCA9539::new(i2c_bus_1, Address::default()),
+    ]);
+    expanders.set_state(10, false).unwrap();
+    expanders.set_state(16, false).unwrap();
+    expanders.write_output_state_all().unwrap();
+
+    assert!(!expanders.is_pin_output_high(10).unwrap());
+    assert!(expanders.is_pin_output_high(11).unwrap());
+}
+
+#[test]
+fn test_multi_pins_update_all_reports_interlock() {
+    const INTERLOCKS: [Interlock; 1] = [Interlock::new(
+        InterlockPin::high(Bank0, Pin0),
+        InterlockPin::high(Bank1, Pin0),
+    )];
+
+    let mut i2c_bus_0: ExpectationBus<6> = ExpectationBus::new()
+        .expect_output_write(Bank0, 0b1111_1111)
+        .expect_output_write(Bank1, 0b1111_1111);
+    let mut i2c_bus_1: ExpectationBus<6> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b1111_1110)
+        .expect_output_write(Bank0, 0b1111_1110)
+        .expect_configuration_write(Bank1, 0b1111_1110)
+        .expect_output_write(Bank1, 0b1111_1110)
+        .expect_output_write(Bank0, 0b1111_1111)
+        .expect_output_write(Bank1, 0b1111_1110);
+
+    let mut expander_1 = PCA9539::new(&mut i2c_bus_1, Address::default());
+    expander_1.set_interlocks(&INTERLOCKS);
+
+    let mut expanders = Expanders::new([PCA9539::new(&mut i2c_bus_0, Address::default()), expander_1]);
+    let pins = expanders.pins();
+    let mut pin16 = pins.get_refreshable_pin(16).unwrap().into_output_pin(PinState::Low).unwrap();
+    let mut pin24 = pins.get_refreshable_pin(24).unwrap().into_output_pin(PinState::Low).unwrap();
+
+    pin16.set_high().unwrap();
+    pin24.set_high().unwrap();
+
+    assert_eq!(
+        MultiError::DeviceError(1, Error::Interlock(INTERLOCKS[0])),
+        pins.update_all().unwrap_err()
+    );
+    assert!(pin24.is_set_low().unwrap());
+
+    i2c_bus_0.done();
+    i2c_bus_1.done();
+}
+
+#[test]
+fn test_multi_pins() {
+    let i2c_bus_0 = BusMockBuilder::new()
+        .expect_write(1, &[0x00])
+        .expect_read(1, 0b0000_0100)
+        .expect_write(1, &[0x02, 0b1111_1111])
+        .expect_write(1, &[0x03, 0b1111_1111])
+        .into_mock();
+    let i2c_bus_1 = BusMockBuilder::new()
+        .expect_write(1, &[0x07, 0b1111_1110])
+        .expect_write(1, &[0x03, 0b1111_1110])
+        .expect_write(1, &[0x02, 0b1111_1111])
+        .expect_write(1, &[0x03, 0b1111_1111])
+        .into_mock();
+
+    let mut expander_0 = PCA9539::new(i2c_bus_0, Address::default());
+    let mut expander_1 = PCA9539::new(i2c_bus_1, Address::default());
+    let mut expanders = Expanders::new([&mut expander_0, &mut expander_1]);
+    let pins = expanders.pins();
+
+    let mut pin02 = pins.get_pin(2).unwrap();
+    assert!(pin02.is_high().unwrap());
+
+    let mut pin24 = pins.get_refreshable_pin(24).unwrap().into_output_pin(PinState::Low).unwrap();
+    pin24.set_high().unwrap();
+    pins.update_all().unwrap();
+}
+
+#[test]
+fn test_address_from_straps() {
+    assert_eq!(0x74, Address::new(Low, Low).value());
+    assert_eq!(0x75, Address::new(Low, High).value());
+    assert_eq!(0x76, Address::new(High, Low).value());
+    assert_eq!(0x77, Address::new(High, High).value());
+    assert_eq!(Address::new(Low, Low), Address::default());
+}
+
+#[test]
+fn test_address_try_from() {
+    assert_eq!(Address::new(High, Low), Address::try_from(0x76).unwrap());
+    assert_eq!(InvalidAddress(0x73), Address::try_from(0x73).unwrap_err());
+    assert_eq!(InvalidAddress(0x78), Address::try_from(0x78).unwrap_err());
+}
+
+#[test]
+fn test_address_custom() {
+    assert_eq!(0x20, Address::custom(0x20).value());
+    assert_eq!(0x20, u8::from(Address::custom(0x20)));
+}
+
+#[test]
+#[should_panic(expected = "Reserved I2C address")]
+fn test_address_custom_reserved() {
+    Address::custom(0x03);
+}
+
+#[test]
+#[should_panic(expected = "I2C address exceeds 7 bits")]
+fn test_address_custom_exceeds_seven_bits() {
+    Address::custom(0x80);
+}
+
+#[test]
+fn test_probe_address_detected() {
+    let mut i2c_bus = BusMockBuilder::new()
+        .expect_write(1, &[0x04])
+        .expect_read(1, 0b0000_0011)
+        .expect_write(1, &[0x04, 0b1111_1100])
+        .expect_write(1, &[0x04])
+        .expect_read(1, 0b1111_1100)
+        .expect_write(1, &[0x04, 0b0000_0011])
+        .expect_write(1, &[0x04])
+        .expect_read(1, 0b0000_0011)
+        .into_mock();
+
+    assert!(probe_address(&mut i2c_bus, Address::default()).unwrap());
+}
+
+#[test]
+fn test_probe_address_pattern_mismatch() {
+    let mut i2c_bus = BusMockBuilder::new()
+        .expect_write(1, &[0x04])
+        .expect_read(1, 0b0000_0000)
+        .expect_write(1, &[0x04, 0b1111_1111])
+        .expect_write(1, &[0x04])
+        .expect_read(1, 0b0000_0000)
+        .expect_write(1, &[0x04, 0b0000_0000])
+        .into_mock();
+
+    assert!(!probe_address(&mut i2c_bus, Address::default()).unwrap());
+}
+
+#[test]
+fn test_probe_address_not_acknowledged() {
+    let mut i2c_bus = BusMockBuilder::new().nack(1).into_mock();
+
+    assert!(!probe_address(&mut i2c_bus, Address::default()).unwrap());
+}
+
+#[test]
+fn test_probe_address_bus_error() {
+    let mut i2c_bus = BusMockBuilder::new().expect_write(1, &[0x04]).read_error().into_mock();
+
+    assert_eq!(
+        DummyError::ReadError,
+        probe_address(&mut i2c_bus, Address::default()).unwrap_err()
+    );
+}
+
+#[test]
+fn test_probe_address_restores_on_bus_error() {
+    let mut i2c_bus = BusMockBuilder::new()
+        .expect_write(1, &[0x04])
+        .expect_read(1, 0b0000_0011)
+        .expect_write(1, &[0x04, 0b1111_1100])
+        .expect_write(1, &[0x04])
+        .read_error()
+        .expect_write(1, &[0x04, 0b0000_0011])
+        .into_mock();
+
+    assert_eq!(
+        DummyError::ReadError,
+        probe_address(&mut i2c_bus, Address::default()).unwrap_err()
+    );
+}
+
+#[test]
+fn test_probe_address_restore_fails() {
+    let mut i2c_bus = BusMockBuilder::new()
+        .expect_write(1, &[0x04])
+        .expect_read(1, 0b0000_0011)
+        .write_error(0x04)
+        .write_error(0x04)
+        .into_mock();
+
+    assert_eq!(
+        DummyError::WriteError,
+        probe_address(&mut i2c_bus, Address::default()).unwrap_err()
+    );
+}
+
+#[test]
+fn test_probe_all_addresses() {
+    let mut i2c_bus = MockI2CBus::new();
+    let mut polarity = [0x0_u8; 0x80];
+    let mut command = 0x0;
+
+    // Devices responding at 0x75 and 0x77
+    i2c_bus.expect_transaction().returning(move |address, operations| {
+        if address != 0x75 && address != 0x77 {
+            return Err(DummyError::Nack);
+        }
+
+        match &mut operations[0] {
+            Operation::Write(data) => {
+                command = data[0];
+                if data.len() == 2 {
+                    polarity[address as usize] = data[1];
+                }
+            }
+            Operation::Read(buffer) => {
+                assert_eq!(0x04, command);
+                buffer[0] = polarity[address as usize];
+            }
+        }
+
+        Ok(())
+    });
+
+    let devices = probe(&mut i2c_bus).unwrap();
+    assert_eq!(&[Address::new(Low, High), Address::new(High, High)], devices.as_slice());
+}
+
+#[test]
+fn test_register_helpers() {
+    assert_eq!(Register::Output1, Register::output(Bank1));
+    assert_eq!(Register::Configuration0, Register::configuration(Bank0));
+    assert_eq!(Register::Polarity1, Register::Polarity0.pair());
+    assert_eq!(Register::Input0, Register::Input1.pair());
+    assert!(matches!(Register::Polarity1.bank(), Bank1));
+    assert!(Register::Input1.is_input());
+    assert!(!Register::Output0.is_input());
+    assert_eq!(Register::Configuration1, Register::try_from(0x07).unwrap());
+    assert_eq!(InvalidCommand(0x08), Register::try_from(0x08).unwrap_err());
+    assert_eq!("Configuration1", Register::Configuration1.to_string());
+}
+
+#[test]
+fn test_read_register() {
+    let i2c_bus = BusMockBuilder::new()
+        .expect_write(1, &[0x05])
+        .expect_read(1, 0b1010_0000)
+        .expect_write(1, &[0x06])
+        .expect_read(1, 0b0000_1111)
+        .into_mock();
+
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+
+    assert_eq!(0b1010_0000, expander.read_register(Register::Polarity1, false).unwrap());
+    assert_eq!(0b0000_0000, expander.cached_register(Register::Polarity1));
+
+    assert_eq!(
+        0b0000_1111,
+        expander.read_register(Register::Configuration0, true).unwrap()
+    );
+    assert_eq!(0b0000_1111, expander.cached_register(Register::Configuration0));
+}
+
+#[test]
+fn test_read_register_error() {
+    let i2c_bus = BusMockBuilder::new().write_error(0x02).into_mock();
+
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+    let error = expander.read_register(Register::Output0, true).unwrap_err();
+
+    assert_eq!(write_error(Register::Output0), error);
+    assert_eq!(0b1111_1111, expander.cached_register(Register::Output0));
+}
+
+#[test]
+fn test_read_register_pair() {
+    let i2c_bus = BusMockBuilder::new()
+        .expect_write(1, &[0x03])
+        .expect_read_pair(1, [0b0000_0001, 0b0000_0010])
+        .into_mock();
+
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+
+    assert_eq!(
+        [0b0000_0001, 0b0000_0010],
+        expander.read_register_pair(Register::Output1, true).unwrap()
+    );
+    assert_eq!(0b0000_0001, expander.cached_register(Register::Output1));
+    assert_eq!(0b0000_0010, expander.cached_register(Register::Output0));
+}
+
+#[test]
+fn test_write_register() {
+    let i2c_bus = BusMockBuilder::new()
+        .expect_write(1, &[0x02, 0b0101_0101])
+        .expect_write(1, &[0x07, 0b0000_0000])
+        .expect_write(1, &[0x00, 0b1111_1111])
+        .into_mock();
+
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+
+    expander.write_register(Register::Output0, 0b0101_0101, false).unwrap();
+    assert_eq!(0b1111_1111, expander.cached_register(Register::Output0));
+
+    expander.write_register(Register::Configuration1, 0b0000_0000, true).unwrap();
+    assert_eq!(0b0000_0000, expander.cached_register(Register::Configuration1));
+
+    expander.write_register(Register::Input0, 0b1111_1111, true).unwrap();
+    assert_eq!(0b0000_0000, expander.cached_register(Register::Input0));
+}
+
+#[test]
+fn test_dump_registers() {
+    let mut i2c_bus = MockI2CBus::new();
+    i2c_bus.expect_transaction().times(1).returning(|address, operations| {
+        assert_eq!(0x74, address);
+        assert_eq!(8, operations.len());
+
+        for (pair, chunk) in operations.chunks_mut(2).enumerate() {
+            match &chunk[0] {
+                Operation::Write(data) => assert_eq!(&[pair as u8 * 2], data),
+                Operation::Read(_) => panic!("Expected write operation"),
+            }
+
+            match &mut chunk[1] {
+                Operation::Read(buffer) => {
+                    buffer[0] = pair as u8 * 2 + 0x10;
+                    buffer[1] = pair as u8 * 2 + 0x11;
+                }
+                Operation::Write(_) => panic!("Expected read operation"),
+            }
+        }
+
+        Ok(())
+    });
+
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+    let dump = expander.dump_registers(true).unwrap();
+
+    assert_eq!(&[0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17], dump.as_bytes());
+    assert_eq!(0x15, dump.get(Register::Polarity1));
+    for register in Register::ALL {
+        assert_eq!(dump.get(register), expander.cached_register(register));
+    }
+}
+
+#[test]
+fn test_simulator_power_on_state() {
+    let mut device = SimulatedPCA9539::new(Address::default());
+
+    let mut buffer = [0x0; 8];
+    for register in Register::ALL {
+        device
+            .write_read(
+                0x74,
+                &[register.command()],
+                &mut buffer[register as usize..register as usize + 1],
+            )
+            .unwrap();
+    }
+
+    assert_eq!([0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0xFF, 0xFF], buffer);
+    assert!(!device.is_interrupt_active());
+}
+
+#[test]
+fn test_simulator_auto_increment() {
+    let mut device = SimulatedPCA9539::new(Address::default());
+
+    device.write(0x74, &[0x05, 0b0000_0001, 0b0000_0010, 0b0000_0011]).unwrap();
+    assert_eq!(0b0000_0011, device.register(Register::Polarity1));
+    assert_eq!(0b0000_0010, device.register(Register::Polarity0));
+
+    let mut buffer = [0x0; 3];
+    device.write_read(0x74, &[0x04], &mut buffer).unwrap();
+    assert_eq!([0b0000_0010, 0b0000_0011, 0b0000_0010], buffer);
+}
+
+#[test]
+fn test_simulator_input_register() {
+    let mut device = SimulatedPCA9539::new(Address::default());
+
+    // Pin0 output low, Pin1 input driven low, Pin2 input inverted
+    device.write(0x74, &[0x02, 0b1111_1110]).unwrap();
+    device.write(0x74, &[0x06, 0b1111_1110]).unwrap();
+    device.write(0x74, &[0x04, 0b0000_0100]).unwrap();
+    device.set_pin_level(Bank0, Pin1, false);
+
+    assert!(!device.pin_level(Bank0, Pin0));
+    assert!(!device.pin_level(Bank0, Pin1));
+    assert!(device.pin_level(Bank0, Pin2));
+
+    let mut buffer = [0x0; 1];
+    device.write_read(0x74, &[0x00], &mut buffer).unwrap();
+    assert_eq!(0b1111_1000, buffer[0]);
+
+    // External level does not override outputs
+    device.set_pin_level(Bank0, Pin0, true);
+    assert!(!device.pin_level(Bank0, Pin0));
+}
+
+#[test]
+fn test_simulator_interrupt() {
+    let mut device = SimulatedPCA9539::new(Address::default());
+
+    // Changes of output pins do not trigger the interrupt
+    device.write(0x74, &[0x07, 0b0111_1111]).unwrap();
+    device.write(0x74, &[0x03, 0b0111_1111]).unwrap();
+    assert!(!device.is_interrupt_active());
+
+    // Cleared when restoring the original level
+    device.set_pin_level(Bank1, Pin3, false);
+    assert!(device.is_interrupt_active());
+    device.set_pin_level(Bank1, Pin3, true);
+    assert!(!device.is_interrupt_active());
+
+    // Reading the other port does not clear the interrupt
+    device.set_pin_level(Bank1, Pin3, false);
+    let mut buffer = [0x0; 1];
+    device.write_read(0x74, &[0x00], &mut buffer).unwrap();
+    assert!(device.is_interrupt_active());
+
+    device.write_read(0x74, &[0x01], &mut buffer).unwrap();
+    assert!(!device.is_interrupt_active());
+}
+
+#[test]
+fn test_simulator_reset() {
+    let mut device = SimulatedPCA9539::new(Address::default());
+
+    device.write(0x74, &[0x06, 0b0000_0000, 0b0000_0000]).unwrap();
+    device.write(0x74, &[0x04, 0b1111_1111]).unwrap();
+    device.set_port_levels(Bank1, 0b0000_1111);
+    device.reset();
+
+    for register in Register::ALL {
+        let expected = match register {
+            Register::Input1 => 0b0000_1111,
+            Register::Polarity0 | Register::Polarity1 => 0x00,
+            _ => 0xFF,
+        };
+        assert_eq!(expected, device.register(register));
+    }
+    assert!(!device.is_interrupt_active());
+}
+
+#[test]
+fn test_simulator_not_acknowledged() {
+    let mut device = SimulatedPCA9539::new(Address::default());
+
+    let error = device.write(0x75, &[0x00]).unwrap_err();
+    assert_eq!(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address), error.kind());
+
+    let error = device.write(0x74, &[0x08]).unwrap_err();
+    assert_eq!(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data), error.kind());
+}
+
+#[test]
+fn test_simulator_expander() {
+    let device = RefCell::new(SimulatedPCA9539::new(Address::default()));
+    let mut expander = PCA9539::new(RefCellDevice::new(&device), Address::default());
+
+    expander.set_mode(Bank0, Pin4, Output).unwrap();
+    expander.set_state(Bank0, Pin4, false).unwrap();
+    expander.reverse_polarity(Bank1, Pin7, true).unwrap();
+    expander.write_output_state(Bank0).unwrap();
+    device.borrow_mut().set_pin_level(Bank1, Pin6, false);
+
+    expander.refresh_input_state(Bank0).unwrap();
+    expander.refresh_input_state(Bank1).unwrap();
+
+    assert!(!expander.is_pin_input_high(Bank0, Pin4));
+    assert!(expander.is_pin_input_high(Bank0, Pin5));
+    assert!(!expander.is_pin_input_high(Bank1, Pin6));
+    assert!(!expander.is_pin_input_high(Bank1, Pin7));
+    assert!(!device.borrow().is_interrupt_active());
+
+    let dump = expander.dump_registers(false).unwrap();
+    assert_eq!(0b1110_1111, dump.get(Register::Configuration0));
+    assert_eq!(0b1000_0000, dump.get(Register::Polarity1));
+}
+
+#[test]
+fn test_simulator_bus() {
+    let mut bus: SimulatedBus<2> = SimulatedBus::new();
+    bus.attach(SimulatedPCA9539::new(Address::new(Low, High))).unwrap();
+    bus.attach(SimulatedPCA9539::new(Address::new(High, Low))).unwrap();
+    assert!(bus.attach(SimulatedPCA9539::new(Address::default())).is_err());
+
+    let bus = RefCell::new(bus);
+    let mut line = SimulatedInterruptLine::new(&bus);
+
+    let devices = probe(&mut RefCellDevice::new(&bus)).unwrap();
+    assert_eq!(&[Address::new(Low, High), Address::new(High, Low)], devices.as_slice());
+    assert!(line.is_high().unwrap());
+
+    bus.borrow_mut()
+        .device_mut(Address::new(High, Low))
+        .unwrap()
+        .set_pin_level(Bank0, Pin1, false);
+    assert!(line.is_low().unwrap());
+
+    let mut expander = PCA9539::new(RefCellDevice::new(&bus), Address::new(High, Low));
+    expander.refresh_input_state(Bank0).unwrap();
+    assert!(!expander.is_pin_input_high(Bank0, Pin1));
+    assert!(line.is_high().unwrap());
+
+    let error = RefCellDevice::new(&bus).write(0x74, &[0x00]).unwrap_err();
+    assert_eq!(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address), error.kind());
+}
+
+#[test]
+fn test_fault_scheduled() {
+    const FAULTS: [(usize, Fault); 2] = [(0, Fault::Nack(NoAcknowledgeSource::Address)), (4, Fault::Timeout)];
+
+    let i2c_bus = RefCell::new(FaultyBus::new(
+        SimulatedPCA9539::new(Address::default()),
+        ScheduledFaults::new(&FAULTS),
+    ));
+    let mut expander = PCA9539::new(RefCellDevice::new(&i2c_bus), Address::default());
+
+    let error = expander.refresh_input_state(Bank0).unwrap_err();
+    assert_eq!(
+        "I2C write error on register Input0: Injected(Nack(Address))",
+        error.to_string()
+    );
+
+    expander.refresh_input_state(Bank0).unwrap();
+
+    let error = expander.refresh_input_state(Bank1).unwrap_err();
+    assert_eq!(
+        "I2C read error on register Input1: Injected(Timeout)",
+        error.to_string()
+    );
+
+    assert_eq!(5, i2c_bus.borrow().transactions());
+    assert_eq!(2, i2c_bus.borrow().injected());
+}
+
+#[test]
+fn test_fault_error_kind() {
+    let mut faults = [
+        Fault::Nack(NoAcknowledgeSource::Data),
+        Fault::ArbitrationLoss,
+        Fault::Timeout,
+        Fault::Bus,
+    ]
+    .into_iter();
+
+    let mut i2c_bus = FaultyBus::new(SimulatedPCA9539::new(Address::default()), |_| faults.next());
+
+    let kinds = [0x0; 5].map(|_| i2c_bus.write(0x74, &[0x00]).map_err(|error| error.kind()));
+    assert_eq!(
+        [
+            Err(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data)),
+            Err(ErrorKind::ArbitrationLoss),
+            Err(ErrorKind::Other),
+            Err(ErrorKind::Bus),
+            Ok(())
+        ],
+        kinds
+    );
+
+    // Errors of the wrapped bus are forwarded
+    let error = i2c_bus.write(0x75, &[0x00]).unwrap_err();
+    assert_eq!(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address), error.kind());
+}
+
+#[test]
+fn test_fault_bit_flip() {
+    const FAULTS: [(usize, Fault); 1] = [(1, Fault::BitFlip(0b1000_0001))];
+
+    let i2c_bus = FaultyBus::new(SimulatedPCA9539::new(Address::default()), ScheduledFaults::new(&FAULTS));
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+
+    expander.refresh_input_state(Bank1).unwrap();
+    assert!(!expander.is_pin_input_high(Bank1, Pin0));
+    assert!(expander.is_pin_input_high(Bank1, Pin1));
+    assert!(!expander.is_pin_input_high(Bank1, Pin7));
+
+    expander.refresh_input_state(Bank1).unwrap();
+    assert!(expander.is_pin_input_high(Bank1, Pin0));
+    assert!(expander.is_pin_input_high(Bank1, Pin7));
+}
+
+#[test]
+fn test_fault_random() {
+    const FAULTS: [Fault; 2] = [Fault::Bus, Fault::ArbitrationLoss];
+
+    let mut first = RandomFaults::new(42, 4, &FAULTS);
+    let mut second = RandomFaults::new(42, 4, &FAULTS);
+
+    let mut injected = 0;
+    for transaction in 0..1000 {
+        let fault = first.fault(transaction);
+        assert_eq!(fault, second.fault(transaction));
+
+        if fault.is_some() {
+            injected += 1;
+        }
+    }
+
+    assert!((150..350).contains(&injected));
+}
+
+#[test]
+fn test_fault_pin_error() {
+    const FAULTS: [(usize, Fault); 1] = [(1, Fault::Bus)];
+
+    let i2c_bus = FaultyBus::new(SimulatedPCA9539::new(Address::default()), ScheduledFaults::new(&FAULTS));
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+    let pins = expander.pins();
+
+    let mut pin = pins.get_refreshable_pin(Bank0, Pin2);
+    assert!(pin.refresh_bank().is_err());
+    pin.refresh_bank().unwrap();
+    assert!(pin.is_high().unwrap());
+}
+
+#[test]
+fn test_testing_expectations_met() {
+    let mut i2c_bus: ExpectationBus<8> = ExpectationBus::new()
+        .address(Address::new(High, Low))
+        .expect_configuration_write(Bank0, 0b1111_1110)
+        .expect_output_write(Bank0, 0b1111_1110)
+        .expect_polarity_write(Bank1, 0b0000_0100)
+        .expect_input_read(Bank1, 0b0000_0100);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::new(High, Low));
+    expander.set_mode(Bank0, Pin0, Output).unwrap();
+    expander.set_state(Bank0, Pin0, false).unwrap();
+    expander.write_output_state(Bank0).unwrap();
+    expander.reverse_polarity(Bank1, Pin2, true).unwrap();
+    expander.refresh_input_state(Bank1).unwrap();
+    assert!(expander.is_pin_input_high(Bank1, Pin2));
+
+    assert_eq!(0, i2c_bus.remaining());
+    i2c_bus.done();
+}
+
+#[test]
+fn test_testing_errors() {
+    let mut i2c_bus: ExpectationBus<3> = ExpectationBus::new()
+        .expect_write_error(&[0x07, 0b0111_1111], ErrorKind::ArbitrationLoss)
+        .expect_write(&[0x01])
+        .expect_read_error(1, ErrorKind::Bus);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    let error = expander.set_mode(Bank1, Pin7, Output).unwrap_err();
+    assert_eq!(Some(ErrorKind::ArbitrationLoss), error.bus_error_kind());
+    assert_eq!(
+        "I2C read error on register Input1: TestingError(Bus)",
+        expander.refresh_input_state(Bank1).unwrap_err().to_string()
+    );
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_testing_register_pair() {
+    let mut i2c_bus: ExpectationBus<2> = ExpectationBus::new()
+        .expect_write(&[0x04])
+        .expect_read(&[0b0000_0001, 0b0000_0010]);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    assert_eq!(
+        [0b0000_0001, 0b0000_0010],
+        expander.read_register_pair(Register::Polarity0, false).unwrap()
+    );
+}
+
+#[test]
+#[should_panic(
+    expected = "Unexpected write to 0x74: Output1 = 0b00000001, expected write to 0x74: Configuration1 = 0b00000001 (expectation #0)"
+)]
+fn test_testing_unexpected_write() {
+    let mut i2c_bus: ExpectationBus<1> = ExpectationBus::new().expect_configuration_write(Bank1, 0b0000_0001);
+    i2c_bus.write(0x74, &[0x03, 0b0000_0001]).unwrap();
+}
+
+#[test]
+#[should_panic(
+    expected = "Unexpected read of 2 byte(s) from 0x74 starting at Input1, expected read from 0x74: Input1 = 0b00000011 (expectation #1)"
+)]
+fn test_testing_unexpected_read() {
+    let mut i2c_bus: ExpectationBus<2> = ExpectationBus::new().expect_input_read(Bank1, 0b0000_0011);
+
+    let mut buffer = [0x0; 2];
+    i2c_bus.write_read(0x74, &[0x01], &mut buffer).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "Unexpected write to 0x75: select Input0, expected write to 0x74: select Input0")]
+fn test_testing_unexpected_address() {
+    let mut i2c_bus: ExpectationBus<2> = ExpectationBus::new().expect_input_read(Bank0, 0b0000_0000);
+    i2c_bus.write(0x75, &[0x00]).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "Unexpected write to 0x74: invalid command 0x08, no expectations left")]
+fn test_testing_no_expectations_left() {
+    let mut i2c_bus: ExpectationBus<1> = ExpectationBus::new();
+    i2c_bus.write(0x74, &[0x08]).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "1 expectation(s) not met, next: read from 0x74: Input0 = 0b00000101")]
+fn test_testing_done_expectations_remaining() {
+    let mut i2c_bus: ExpectationBus<2> = ExpectationBus::new().expect_input_read(Bank0, 0b0000_0101);
+    i2c_bus.write(0x74, &[0x00]).unwrap();
+    i2c_bus.done();
+}
+
+#[test]
+#[should_panic(expected = "Expectation capacity of 1 exceeded")]
+fn test_testing_capacity_exceeded() {
+    let _: ExpectationBus<1> = ExpectationBus::new().expect_input_read(Bank0, 0b0000_0000);
+}
+
+#[test]
+fn test_recording_bus() {
+    let device = RefCell::new(SimulatedPCA9539::new(Address::default()));
+    let mut i2c_bus: RecordingBus<_, 8> = RecordingBus::new(RefCellDevice::new(&device));
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_mode(Bank1, Pin0, Output).unwrap();
+    expander.read_register_pair(Register::Polarity0, false).unwrap();
+
+    i2c_bus.write(0x75, &[0x00]).unwrap_err();
+
+    assert_eq!(
+        "0x74 W:07,FE OK\n0x74 W:04 OK\n0x74 R:00,00 OK\n0x75 W:00 ERR:NackAddress\n",
+        i2c_bus.trace().to_string()
+    );
+    assert_eq!(
+        "0x74 write Configuration1 = 0b11111110\n\
+         0x74 select Polarity0\n\
+         0x74 read Polarity0 = 0b00000000, Polarity1 = 0b00000000\n\
+         0x75 select Input0 (failed: NackAddress)\n",
+        i2c_bus.trace().decoded().to_string()
+    );
+}
+
+#[test]
+fn test_recording_bounded() {
+    let mut i2c_bus: RecordingBus<_, 2> = RecordingBus::new(SimulatedPCA9539::new(Address::default()));
+
+    i2c_bus.write(0x74, &[0x02, 0x01]).unwrap();
+    i2c_bus.write(0x74, &[0x02, 0x02]).unwrap();
+    i2c_bus.write(0x74, &[0x02, 0x03]).unwrap();
+    i2c_bus.write(0x74, &[0x02; 9]).unwrap();
+
+    let trace = i2c_bus.trace();
+    assert_eq!(2, trace.len());
+    assert_eq!(2, trace.dropped());
+    assert_eq!("0x74 W:02,02 OK\n0x74 W:02,03 OK\n", trace.to_string());
+}
+
+#[test]
+fn test_recording_parse() {
+    let trace: Trace<4> = Trace::parse(
+        "# Comment\n\
+         0x74 W:06,F7 OK\n\
+         \n\
+         0x76 W:00 R:01,02 ERR:Bus\n\
+         0x74 W: ERR:NackData",
+    )
+    .unwrap();
+
+    assert_eq!(3, trace.len());
+    assert_eq!(
+        "0x74 W:06,F7 OK\n0x76 W:00 R:01,02 ERR:Bus\n0x74 W: ERR:NackData\n",
+        trace.to_string()
+    );
+
+    let transaction = trace.iter().nth(1).unwrap();
+    assert_eq!(0x76, transaction.address);
+    assert_eq!(Err(ErrorKind::Bus), transaction.result);
+    assert_eq!(
+        RecordedOperation::Read(heapless::Vec::from_slice(&[0x01, 0x02]).unwrap()),
+        transaction.operations[1]
+    );
+}
+
+#[test]
+fn test_recording_parse_error() {
+    assert_eq!(
+        ParseError::InvalidLine(2),
+        Trace::<4>::parse("0x74 W:00 OK\n0x74 W:00").unwrap_err()
+    );
+    assert_eq!(
+        ParseError::InvalidLine(1),
+        Trace::<4>::parse("0x74 X:00 OK").unwrap_err()
+    );
+    assert_eq!(
+        ParseError::InvalidLine(1),
+        Trace::<4>::parse("0x74 W:00 ERR:Unknown").unwrap_err()
+    );
+    assert_eq!(
+        ParseError::CapacityExceeded,
+        Trace::<1>::parse("0x74 W:00 OK\n0x74 W:00 OK").unwrap_err()
+    );
+}
+
+#[test]
+fn test_replay_bus() {
+    let mut recorder: RecordingBus<_, 8> = RecordingBus::new(SimulatedPCA9539::new(Address::default()));
+    recorder.bus().set_pin_level(Bank0, Pin6, false);
+
+    let mut expander = PCA9539::new(&mut recorder, Address::default());
+    expander.refresh_input_state(Bank0).unwrap();
+    expander.set_mode(Bank0, Pin1, Output).unwrap();
+
+    let trace: Trace<8> = Trace::parse(&recorder.trace().to_string()).unwrap();
+    let mut i2c_bus = ReplayBus::new(trace);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.refresh_input_state(Bank0).unwrap();
+    expander.set_mode(Bank0, Pin1, Output).unwrap();
+    assert!(!expander.is_pin_input_high(Bank0, Pin6));
+    assert!(expander.is_pin_input_high(Bank0, Pin7));
+
+    assert_eq!(0, i2c_bus.remaining());
+    i2c_bus.done();
+}
+
+#[test]
+fn test_replay_bus_error() {
+    let mut i2c_bus = ReplayBus::new(Trace::<2>::parse("0x74 W:00 ERR:ArbitrationLoss").unwrap());
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    assert_eq!(
+        "I2C write error on register Input0: ReplayError(ArbitrationLoss)",
+        expander.refresh_input_state(Bank0).unwrap_err().to_string()
+    );
+}
+
+#[test]
+#[should_panic(expected = "Replay mismatch at transaction #1: expected 0x74 W:02,FF OK, actual 0x74 W:02,FE")]
+fn test_replay_bus_mismatch() {
+    let mut i2c_bus = ReplayBus::new(Trace::<2>::parse("0x74 W:00 R:FF OK\n0x74 W:02,FF OK").unwrap());
+
+    let mut buffer = [0x0; 1];
+    i2c_bus.write_read(0x74, &[0x00], &mut buffer).unwrap();
+    i2c_bus.write(0x74, &[0x02, 0xFE]).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "1 transaction(s) not replayed, next: 0x74 W:00 OK")]
+fn test_replay_bus_not_done() {
+    let i2c_bus = ReplayBus::new(Trace::<2>::parse("0x74 W:00 OK").unwrap());
+    i2c_bus.done();
+}
+
+/// Parses and decodes the given capture, returning the decoded operations as text
+fn decode_capture(capture: &str, decoder: &mut Decoder) -> std::vec::Vec<String> {
+    let mut lines = capture.lines();
+    let mut parser = CsvParser::new(lines.next().unwrap()).unwrap();
+    let mut operations = std::vec::Vec::new();
+
+    let mut feed = |event| decoder.feed(event, &mut |operation| operations.push(operation.to_string()));
+
+    for line in lines {
+        parser.parse_line(line, &mut feed).unwrap();
+    }
+
+    parser.finish(&mut feed);
+    operations
+}
+
+#[test]
+fn test_decoder_logic2() {
+    let capture = "name,type,start_time,duration,\"ack\",\"address\",\"read\",\"data\"\n\
+        \"I2C\",\"start\",0.1,0.0,,,,\n\
+        \"I2C\",\"address\",0.2,0.0,true,0x74,false,\n\
+        \"I2C\",\"data\",0.3,0.0,true,,,0x07\n\
+        \"I2C\",\"data\",0.4,0.0,true,,,0xF0\n\
+        \"I2C\",\"data\",0.5,0.0,true,,,0xF0\n\
+        \"I2C\",\"stop\",0.6,0.0,,,,\n\
+        \"I2C\",\"start\",0.7,0.0,,,,\n\
+        \"I2C\",\"address\",0.8,0.0,true,0x74,false,\n\
+        \"I2C\",\"data\",0.9,0.0,true,,,0x00\n\
+        \"I2C\",\"start\",1.0,0.0,,,,\n\
+        \"I2C\",\"address\",1.1,0.0,true,0x74,true,\n\
+        \"I2C\",\"data\",1.2,0.0,true,,,0x26\n\
+        \"I2C\",\"data\",1.3,0.0,false,,,0x01\n\
+        \"I2C\",\"stop\",1.4,0.0,,,,";
+
+    let mut decoder = Decoder::new();
+    let operations = decode_capture(capture, &mut decoder);
+
+    assert_eq!(
+        [
+            "0x74 write Bank1 configuration = 0b1111_0000 → P10-P13 output, P14-P17 input",
+            "0x74 write Bank0 configuration = 0b1111_0000 → P00-P03 output, P04-P07 input",
+            "0x74 read Bank0 input = 0b0010_0110",
+            "0x74 read Bank1 input = 0b0000_0001",
+        ],
+        operations.as_slice()
+    );
+
+    let state = decoder.device(Address::default()).unwrap();
+    assert_eq!(Some(0b1111_0000), state.register(Register::Configuration1));
+    assert_eq!(Some(0b0000_0001), state.register(Register::Input1));
+    assert_eq!(None, state.register(Register::Output0));
+    assert_eq!(Some(Register::Input0), state.pointer());
+}
+
+#[test]
+fn test_decoder_logic1() {
+    let capture = "Time [s],Packet ID,Address,Data,Read/Write,ACK/NAK\n\
+        0.1,0,0xEC,0x02,Write,ACK\n\
+        0.2,0,0xEC,0xFF,Write,ACK\n\
+        0.3,1,0xEC,0x02,Write,ACK\n\
+        0.4,1,0xEC,0xFB,Write,ACK\n\
+        0.5,2,0xEC,0x02,Write,ACK\n\
+        0.6,2,0xEC,0xFB,Write,ACK\n\
+        0.7,3,0xE0,0x02,Write,ACK\n\
+        0.8,4,0xEC,0x04,Write,ACK\n\
+        0.9,5,0xED,0x01,Read,NAK\n";
+
+    let mut decoder = Decoder::with_addresses(&[Address::new(High, Low)]).unwrap();
+    let operations = decode_capture(capture, &mut decoder);
+
+    assert_eq!(
+        [
+            "0x76 write Bank0 output = 0b1111_1111 → P00-P07 high",
+            "0x76 write Bank0 output = 0b1111_1011 → P02 low",
+            "0x76 write Bank0 output = 0b1111_1011 (unchanged)",
+            "0x76 select Polarity0",
+            "0x76 read Bank0 polarity = 0b0000_0001",
+        ],
+        operations.as_slice()
+    );
+}
+
+#[test]
+fn test_decoder_anomalies() {
+    let capture = "Time [s],Packet ID,Address,Data,Read/Write,ACK/NAK\n\
+        0.1,0,0x75,0x26,Read,NAK\n\
+        0.2,1,0x75,0x09,Write,ACK\n\
+        0.3,2,0x75,0x00,Write,ACK\n\
+        0.4,2,0x75,0x01,Write,ACK\n\
+        0.5,3,0x75,0x03,Write,ACK\n\
+        0.6,3,0x75,0x0F,Write,NAK\n\
+        0.7,4,0x77,,Write,NAK\n";
+
+    let operations = decode_capture(capture, &mut Decoder::new());
+
+    assert_eq!(
+        [
+            "0x75 ANOMALY: read of 0x26 without preceding command write",
+            "0x75 ANOMALY: invalid command 0x09",
+            "0x75 ANOMALY: write of 0x01 to read-only Input0",
+            "0x75 ANOMALY: data 0x0f not acknowledged",
+            "0x77 ANOMALY: address not acknowledged",
+        ],
+        operations.as_slice()
+    );
+}
+
+#[test]
+fn test_decoder_with_addresses() {
+    let decoder = Decoder::with_addresses(&[Address::default(), Address::default()]).unwrap();
+    assert!(decoder.device(Address::default()).is_some());
+    assert!(decoder.device(Address::new(High, Low)).is_none());
+
+    let addresses = [Address::ALL.as_slice(), &[Address::default()]].concat();
+    assert!(Decoder::with_addresses(&addresses).is_ok());
+
+    let addresses = [Address::ALL.as_slice(), &[Address::custom(0x20)]].concat();
+    assert_eq!(Some(TooManyAddresses), Decoder::with_addresses(&addresses).err());
+}
+
+#[test]
+fn test_decoder_csv_errors() {
+    assert_eq!(CsvError::UnknownFormat, CsvParser::new("a,b,c").err().unwrap());
+    assert_eq!(
+        CsvError::MissingColumn("data"),
+        CsvParser::new("type,start_time,ack,address,read").err().unwrap()
+    );
+
+    let mut parser = CsvParser::new("Time [s],Packet ID,Address,Data,Read/Write,ACK/NAK").unwrap();
+    assert_eq!(CsvFormat::Logic1, parser.format());
+    assert_eq!(
+        CsvError::InvalidValue("Read/Write"),
+        parser.parse_line("0.1,0,0x74,0x00,Other,ACK", &mut |_| {}).unwrap_err()
+    );
+    assert_eq!(
+        CsvError::InvalidValue("Data"),
+        parser.parse_line("0.1,0,0x74,0xGG,Write,ACK", &mut |_| {}).unwrap_err()
+    );
+}
+
+#[test]
+fn test_error_accessors() {
+    let error = read_error(Register::Input1);
+
+    assert_eq!(Some(&DummyError::ReadError), error.bus_error());
+    assert_eq!(Some(BusOperation::Read), error.operation());
+    assert_eq!(Some(Register::Input1), error.register());
+    assert_eq!(Some(ErrorKind::Other), error.bus_error_kind());
+    assert_eq!(
+        embedded_hal::digital::ErrorKind::Other,
+        embedded_hal::digital::Error::kind(&error)
+    );
+}
+
+#[test]
+fn test_error_display() {
+    let error: &dyn std::error::Error = &write_error(Register::Configuration1);
+    assert_eq!(
+        "I2C write error on register Configuration1: WriteError",
+        error.to_string()
+    );
+    assert_eq!(
+        "Bus { error: WriteError, operation: Write, register: Some(Configuration1) }",
+        format!("{:?}", error)
+    );
+
+    let error: Error<DummyError> = Error::SafeStateLocked;
+    assert_eq!("Outputs are locked by safe state", error.to_string());
+    assert_eq!(None, error.bus_error());
+    assert_eq!(None, error.register());
+
+    let error: Error<DummyError> = Error::PinLocked(Bank1, Pin6);
+    assert_eq!("Pin16 is locked", error.to_string());
+    assert_eq!(None, error.operation());
+
+    let error: Error<DummyError> = Error::LocksSealed;
+    assert_eq!("Pin locks are irreversible", error.to_string());
+}
+
+#[test]
+fn test_dump_registers_error() {
+    let mut i2c_bus = MockI2CBus::new();
+    i2c_bus
+        .expect_transaction()
+        .times(1)
+        .returning(|_, _| Err(DummyError::ReadError));
+
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+    let error = expander.dump_registers(false).unwrap_err();
+
+    assert_eq!(None, error.register());
+    assert_eq!("I2C read error: ReadError", error.to_string());
+}
+
+/// Logger capturing all messages, for verifying the tracing of register accesses
+#[cfg(feature = "log")]
+struct CapturingLogger(std::sync::Mutex<Vec<String>>);
+
+#[cfg(feature = "log")]
+impl log::Log for CapturingLogger {
+    fn enabled(&self, _: &log::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record<'_>) {
+        let message = format!("{} {}", record.level(), record.args());
+        self.0.lock().unwrap().push(message);
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(feature = "log")]
+#[test]
+fn test_trace_log() {
+    static LOGGER: CapturingLogger = CapturingLogger(std::sync::Mutex::new(Vec::new()));
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(log::LevelFilter::Trace);
+
+    // Unique address, as tests are running in parallel
+    const ADDRESS: Address = Address::new(High, High);
+
+    let mut i2c_bus: ExpectationBus<6> = ExpectationBus::new()
+        .address(ADDRESS)
+        .expect_configuration_write(Bank0, 0b1111_0111)
+        .expect_write(&[Register::Input1.command()])
+        .expect_read(&[0b0000_0011, 0b0000_0001])
+        .expect_write_error(&[Register::Output0.command(), 0b1111_1111], ErrorKind::Bus);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, ADDRESS);
+    expander.set_mode(Bank0, Pin3, Output).unwrap();
+    expander.read_register_pair(Register::Input1, false).unwrap();
+    expander.write_output_state(Bank0).unwrap_err();
+
+    let messages: Vec<String> = LOGGER
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|message| message.contains("0x77"))
+        .cloned()
+        .collect();
+
+    assert_eq!(
+        vec![
+            "TRACE 0x77 write Configuration0 = 0b11110111",
+            "TRACE 0x77 read Input1 = 0b00000011",
+            "TRACE 0x77 read Input0 = 0b00000001",
+            "WARN 0x77 I2C write error on register Output0: TestingError(Bus)",
+        ],
+        messages
+    );
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn test_stats_register_accesses() {
+    let mut i2c_bus: ExpectationBus<8> = ExpectationBus::new()
+        .expect_configuration_write(Bank1, 0b1110_1111)
+        .expect_write(&[Register::Input1.command()])
+        .expect_read(&[0b0000_0011, 0b0000_0001])
+        .expect_write(&[Register::Input0.command()])
+        .expect_read(&[0b0000_0001]);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_mode(Bank1, Pin4, Output).unwrap();
+    expander.read_register_pair(Register::Input1, false).unwrap();
+    expander.refresh_input_state(Bank0).unwrap();
+
+    let statistics = expander.statistics();
+    assert_eq!(1, statistics.writes(Register::Configuration1));
+    assert_eq!(0, statistics.writes(Register::Configuration0));
+    assert_eq!(2, statistics.reads(Register::Input0));
+    assert_eq!(1, statistics.reads(Register::Input1));
+    assert_eq!(0, statistics.reads(Register::Output0));
+    assert_eq!(3, statistics.bytes_read());
+    assert_eq!(4, statistics.bytes_written());
+    assert_eq!(0, statistics.read_errors().total());
+    assert_eq!(0, statistics.write_errors().total());
+    assert_eq!(None, statistics.last_success());
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn test_stats_dump_registers() {
+    let mut i2c_bus: ExpectationBus<8> = ExpectationBus::new()
+        .expect_write(&[Register::Input0.command()])
+        .expect_read(&[0b0000_0000, 0b0000_0000])
+        .expect_write(&[Register::Output0.command()])
+        .expect_read(&[0b1111_1111, 0b1111_1111])
+        .expect_write(&[Register::Polarity0.command()])
+        .expect_read(&[0b0000_0000, 0b0000_0000])
+        .expect_write(&[Register::Configuration0.command()])
+        .expect_read(&[0b1111_1111, 0b1111_1111]);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.dump_registers(false).unwrap();
+
+    let statistics = expander.statistics();
+    for register in Register::ALL {
+        assert_eq!(1, statistics.reads(register));
+    }
+
+    assert_eq!(8, statistics.bytes_read());
+    assert_eq!(4, statistics.bytes_written());
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn test_stats_errors() {
+    let mut i2c_bus: ExpectationBus<8> = ExpectationBus::new()
+        .expect_write_error(
+            &[Register::Input0.command()],
+            ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address),
+        )
+        .expect_write(&[Register::Input0.command()])
+        .expect_read_error(1, ErrorKind::Bus)
+        .expect_write_error(&[Register::Output0.command(), 0b1111_1111], ErrorKind::Bus)
+        .expect_output_write(Bank0, 0b1111_1111)
+        .expect_write_error(&[Register::Output0.command(), 0b1111_1111], ErrorKind::Overrun);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_clock(|| 42);
+    expander.refresh_input_state(Bank0).unwrap_err();
+    expander.refresh_input_state(Bank0).unwrap_err();
+    expander.write_output_state(Bank0).unwrap_err();
+
+    let statistics = expander.statistics();
+    assert_eq!(3, statistics.failure_streak());
+    assert_eq!(3, statistics.longest_failure_streak());
+    assert_eq!(None, statistics.last_success());
+    assert_eq!(1, statistics.read_errors().get(ErrorKind::Bus));
+    assert_eq!(1, statistics.read_errors().total());
+    assert_eq!(1, statistics.write_errors().get(ErrorKind::Bus));
+    assert_eq!(
+        1,
+        statistics
+            .write_errors()
+            .get(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address))
+    );
+    assert_eq!(2, statistics.write_errors().total());
+
+    expander.write_output_state(Bank0).unwrap();
+    expander.write_output_state(Bank0).unwrap_err();
+
+    let statistics = expander.statistics();
+    assert_eq!(1, statistics.failure_streak());
+    assert_eq!(3, statistics.longest_failure_streak());
+    assert_eq!(Some(42), statistics.last_success());
+    assert_eq!(1, statistics.write_errors().get(ErrorKind::Overrun));
+
+    // Failure streak is shared with the link state, so it is not reset
+    expander.reset_statistics();
+    let statistics = expander.statistics();
+    assert_eq!(1, statistics.failure_streak());
+    assert_eq!(1, statistics.longest_failure_streak());
+    assert_eq!(0, statistics.write_errors().total());
+    assert_eq!(None, statistics.last_success());
+}
+
+/// Delay summing up all delays in nanoseconds
+#[derive(Default)]
+struct SummingDelay(u32);
+
+impl DelayNs for SummingDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.0 += ns;
+    }
+}
+
+#[test]
+fn test_retry_read_restores_pointer() {
+    const FAULTS: [(usize, Fault); 1] = [(1, Fault::Nack(NoAcknowledgeSource::Data))];
+
+    let mut i2c_bus: ExpectationBus<3> = ExpectationBus::new()
+        .expect_write(&[Register::Input1.command()])
+        .expect_write(&[Register::Input1.command()])
+        .expect_read(&[0b0000_0100]);
+
+    let faulty_bus = FaultyBus::new(&mut i2c_bus, ScheduledFaults::new(&FAULTS));
+    let mut expander = PCA9539::with_retry(faulty_bus, Address::default(), RetryPolicy::default(), NoDelay);
+
+    expander.refresh_input_state(Bank1).unwrap();
+    assert!(expander.is_pin_input_high(Bank1, Pin2));
+
+    let statistics = expander.retry_statistics();
+    assert_eq!(1, statistics.retries);
+    assert_eq!(1, statistics.recovered);
+    assert_eq!(0, statistics.failed);
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_retry_pointer_after_pair_write() {
+    // Read fails, the pointer was toggled back to Output0 by writing both registers of the pair
+    const FAULTS: [(usize, Fault); 1] = [(1, Fault::ArbitrationLoss)];
+
+    let i2c_bus: ExpectationBus<3> = ExpectationBus::new()
+        .expect_write(&[Register::Output0.command(), 0b0000_0001, 0b0000_0010])
+        .expect_write(&[Register::Output0.command()])
+        .expect_read(&[0b0000_0011]);
+
+    let mut retry_bus = RetryBus::new(
+        FaultyBus::new(i2c_bus, ScheduledFaults::new(&FAULTS)),
+        RetryPolicy::default(),
+        NoDelay,
+    );
+
+    let mut buffer = [0x0; 1];
+    retry_bus
+        .write(0x74, &[Register::Output0.command(), 0b0000_0001, 0b0000_0010])
+        .unwrap();
+    retry_bus.read(0x74, &mut buffer).unwrap();
+
+    assert_eq!([0b0000_0011], buffer);
+    assert_eq!(1, retry_bus.statistics().recovered);
+    retry_bus.release().0.release().0.done();
+}
+
+#[test]
+fn test_retry_not_retryable() {
+    const FAULTS: [(usize, Fault); 1] = [(0, Fault::Timeout)];
+
+    let i2c_bus = FaultyBus::new(ExpectationBus::<1>::new(), ScheduledFaults::new(&FAULTS));
+    let mut expander = PCA9539::with_retry(i2c_bus, Address::default(), RetryPolicy::default(), NoDelay);
+
+    let error = expander.write_output_state(Bank0).unwrap_err();
+    assert_eq!(Some(ErrorKind::Other), error.bus_error_kind());
+
+    let statistics = expander.retry_statistics();
+    assert_eq!(0, statistics.retries);
+    assert_eq!(1, statistics.failed);
+}
+
+#[test]
+fn test_retry_attempts_exhausted() {
+    const POLICY: RetryPolicy = RetryPolicy::new(3)
+        .backoff_us(100)
+        .retryable(&[ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown)]);
+
+    let policy = |transaction: usize| match transaction {
+        0..=2 => Some(Fault::Nack(NoAcknowledgeSource::Address)),
+        _ => None,
+    };
+
+    let i2c_bus = FaultyBus::new(ExpectationBus::<1>::new(), policy);
+    let mut expander = PCA9539::with_retry(i2c_bus, Address::default(), POLICY, SummingDelay::default());
+
+    assert_eq!(
+        "I2C write error on register Configuration0: Injected(Nack(Address))",
+        expander.set_mode(Bank0, Pin0, Output).unwrap_err().to_string()
+    );
+
+    let statistics = expander.retry_statistics();
+    assert_eq!(2, statistics.retries);
+    assert_eq!(0, statistics.recovered);
+    assert_eq!(1, statistics.failed);
+
+    expander.reset_retry_statistics();
+    assert_eq!(RetryStatistics::default(), expander.retry_statistics());
+}
+
+#[test]
+fn test_retry_backoff() {
+    const FAULTS: [(usize, Fault); 2] = [(0, Fault::Bus), (1, Fault::Bus)];
+
+    let i2c_bus = FaultyBus::new(
+        ExpectationBus::<1>::new().expect_output_write(Bank0, 0b1111_1111),
+        ScheduledFaults::new(&FAULTS),
+    );
+    let mut retry_bus = RetryBus::new(i2c_bus, RetryPolicy::new(3).backoff_us(100), SummingDelay::default());
+
+    retry_bus.write(0x74, &[Register::Output0.command(), 0b1111_1111]).unwrap();
+
+    let (faulty_bus, delay) = retry_bus.release();
+    assert_eq!(300_000, delay.0);
+    faulty_bus.release().0.done();
+}
+
+#[test]
+fn test_retry_policy_nack_source() {
+    let policy = RetryPolicy::new(2).retryable(&[ErrorKind::NoAcknowledge(NoAcknowledgeSource::Unknown)]);
+    assert!(policy.is_retryable(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)));
+    assert!(policy.is_retryable(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data)));
+    assert!(!policy.is_retryable(ErrorKind::Bus));
+
+    let policy = RetryPolicy::new(0).retryable(&[ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data)]);
+    assert!(!policy.is_retryable(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address)));
+    assert!(policy.is_retryable(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data)));
+    assert_eq!(1, policy.max_attempts());
+}
+
+thread_local! {
+    /// Link state changes reported by the callback
+    static LINK_CHANGES: RefCell<Vec<LinkChange>> = const { RefCell::new(Vec::new()) };
+}
+
+fn record_link_change(change: LinkChange) {
+    LINK_CHANGES.with(|changes| changes.borrow_mut().push(change));
+}
+
+/// Returns the reported link state transitions and clears them
+fn take_link_changes() -> Vec<(LinkState, LinkState)> {
+    LINK_CHANGES.with(|changes| {
+        changes
+            .borrow_mut()
+            .drain(..)
+            .map(|change| (change.previous, change.current))
+            .collect()
+    })
+}
+
+#[test]
+fn test_link_state_transitions() {
+    let connected = Cell::new(true);
+    let policy = |_| match connected.get() {
+        true => None,
+        false => Some(Fault::Nack(NoAcknowledgeSource::Address)),
+    };
+
+    let i2c_bus = RefCell::new(FaultyBus::new(SimulatedPCA9539::new(Address::default()), policy));
+    let mut expander = PCA9539::new(RefCellDevice::new(&i2c_bus), Address::default());
+    expander.set_link_policy(LinkPolicy::new().degraded_after(2).offline_after(3));
+    expander.set_link_callback(record_link_change);
+
+    connected.set(false);
+    expander.refresh_input_state(Bank0).unwrap_err();
+    assert_eq!(LinkState::Online, expander.link_state());
+    expander.refresh_input_state(Bank0).unwrap_err();
+    assert_eq!(LinkState::Degraded, expander.link_state());
+
+    // Device is not responding, so no transition
+    assert_eq!(LinkState::Offline, expander.poll_link());
+    assert_eq!(LinkState::Offline, expander.poll_link());
+    assert_eq!(4, i2c_bus.borrow().injected());
+
+    connected.set(true);
+    assert_eq!(LinkState::Online, expander.poll_link());
+    assert_eq!(LinkState::Online, expander.poll_link());
+
+    assert_eq!(
+        vec![
+            (LinkState::Online, LinkState::Degraded),
+            (LinkState::Degraded, LinkState::Offline),
+            (LinkState::Offline, LinkState::Recovering),
+            (LinkState::Recovering, LinkState::Online),
+        ],
+        take_link_changes()
+    );
+}
+
+#[test]
+fn test_link_degraded_recovers_on_success() {
+    const FAULTS: [(usize, Fault); 1] = [(0, Fault::Bus)];
+
+    let i2c_bus = FaultyBus::new(
+        ExpectationBus::<1>::new().expect_output_write(Bank0, 0b1111_1111),
+        ScheduledFaults::new(&FAULTS),
+    );
+
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+    expander.write_output_state(Bank0).unwrap_err();
+    assert_eq!(LinkState::Degraded, expander.link_state());
+
+    expander.write_output_state(Bank0).unwrap();
+    assert_eq!(LinkState::Online, expander.link_state());
+}
+
+#[test]
+fn test_link_recovery_sync_state() {
+    let connected = Cell::new(true);
+    let policy = |_| match connected.get() {
+        true => None,
+        false => Some(Fault::Nack(NoAcknowledgeSource::Address)),
+    };
+
+    let i2c_bus = RefCell::new(FaultyBus::new(SimulatedPCA9539::new(Address::default()), policy));
+    let mut expander = PCA9539::new(RefCellDevice::new(&i2c_bus), Address::default());
+    expander.set_link_policy(LinkPolicy::new().offline_after(1));
+    expander.set_mode(Bank1, Pin3, Output).unwrap();
+
+    connected.set(false);
+    assert!(expander.set_mode(Bank1, Pin5, Output).is_err());
+    assert_eq!(LinkState::Offline, expander.link_state());
+
+    // Device lost its state while being disconnected
+    i2c_bus.borrow_mut().bus().reset();
+    connected.set(true);
+
+    // Successful access does not declare the device online before recovery
+    expander.refresh_input_state(Bank0).unwrap();
+    assert_eq!(LinkState::Recovering, expander.link_state());
+
+    assert_eq!(LinkState::Online, expander.poll_link());
+    assert_eq!(
+        0b1101_0111,
+        i2c_bus.borrow_mut().bus().register(Register::Configuration1)
+    );
+}
+
+#[test]
+fn test_link_recovery_adopt_hardware() {
+    let connected = Cell::new(true);
+    let policy = |_| match connected.get() {
+        true => None,
+        false => Some(Fault::Nack(NoAcknowledgeSource::Address)),
+    };
+
+    let i2c_bus = RefCell::new(FaultyBus::new(SimulatedPCA9539::new(Address::default()), policy));
+    let mut expander = PCA9539::new(RefCellDevice::new(&i2c_bus), Address::default());
+    expander.set_link_policy(LinkPolicy::new().offline_after(1).recovery(Recovery::AdoptHardware));
+    expander.set_mode(Bank1, Pin3, Output).unwrap();
+
+    connected.set(false);
+    expander.refresh_input_state(Bank0).unwrap_err();
+    assert_eq!(LinkState::Offline, expander.poll_link());
+
+    // Device got reconfigured by someone else while being disconnected
+    i2c_bus.borrow_mut().bus().reset();
+    connected.set(true);
+
+    assert_eq!(LinkState::Online, expander.poll_link());
+    assert_eq!(0b1111_1111, expander.cached_register(Register::Configuration1));
+}
+
+#[test]
+fn test_safe_state_enter() {
+    const SAFE_STATE: SafeState = SafeState::new().output(Bank0, Pin2, false).output(Bank1, Pin5, true);
+
+    let mut i2c_bus: ExpectationBus<6> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b1111_0111)
+        .expect_output_write(Bank0, 0b1111_0111)
+        // Pin03 is switching to input mode and keeps its current level until being released
+        .expect_write(&[Register::Output0.command(), 0b1111_0011, 0b1111_1111])
+        .expect_write(&[Register::Configuration0.command(), 0b1111_1011, 0b1101_1111])
+        .expect_polarity_write(Bank0, 0b0000_0001)
+        .expect_output_write(Bank0, 0b1111_0111);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_safe_state(SAFE_STATE);
+    expander.set_mode(Bank0, Pin3, Output).unwrap();
+    expander.set_state(Bank0, Pin3, false).unwrap();
+    expander.write_output_state(Bank0).unwrap();
+
+    expander.enter_safe_state().unwrap();
+    assert!(expander.is_safe_state_locked());
+    assert!(!expander.is_pin_output_high(Bank0, Pin2));
+    assert!(expander.is_pin_output_high(Bank1, Pin5));
+
+    assert_eq!(Err(Error::SafeStateLocked), expander.set_state(Bank0, Pin2, true));
+    assert_eq!(Err(Error::SafeStateLocked), expander.set_state_all(Bank1, true));
+    assert_eq!(Err(Error::SafeStateLocked), expander.set_mode(Bank0, Pin3, Output));
+    assert_eq!(Err(Error::SafeStateLocked), expander.set_mode_all(Bank1, Input));
+    assert_eq!(Err(Error::SafeStateLocked), expander.write_output_state(Bank0));
+    assert_eq!(
+        Err(Error::SafeStateLocked),
+        expander.write_register(Register::Configuration1, 0b0000_0000, true)
+    );
+    assert!(!expander.is_pin_output_high(Bank0, Pin2));
+
+    // Polarity does not affect outputs
+    expander.reverse_polarity(Bank0, Pin0, true).unwrap();
+
+    expander.release_safe_state();
+    assert!(!expander.is_safe_state_locked());
+    expander.set_state(Bank0, Pin2, true).unwrap();
+    expander.write_output_state(Bank0).unwrap();
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_safe_state_locks_pins() {
+    const FAULTS: [(usize, Fault); 1] = [(2, Fault::Nack(NoAcknowledgeSource::Data))];
+
+    let i2c_bus = FaultyBus::new(
+        ExpectationBus::<4>::new()
+            .expect_configuration_write(Bank1, 0b1111_1110)
+            .expect_output_write(Bank1, 0b1111_1111)
+            .expect_write(&[Register::Output0.command(), 0b1111_1111, 0b1111_1110])
+            .expect_write(&[Register::Configuration0.command(), 0b1111_1111, 0b1111_1111]),
+        ScheduledFaults::new(&FAULTS),
+    );
+
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+    expander.set_safe_state(SafeState::new().enter_after_errors(1));
+
+    let pins = expander.pins();
+    let mut pin = pins.get_pin(Bank1, Pin0).into_output_pin(PinState::High).unwrap();
+
+    assert!(pin.set_low().is_err());
+    assert_eq!(Err(Error::SafeStateLocked), pin.set_high());
+}
+
+#[test]
+fn test_safe_state_triggered_by_errors() {
+    const SAFE_STATE: SafeState = SafeState::new().output(Bank1, Pin7, false).enter_after_errors(2);
+    const FAULTS: [(usize, Fault); 2] = [(0, Fault::Bus), (1, Fault::Bus)];
+
+    let i2c_bus = FaultyBus::new(
+        ExpectationBus::<2>::new()
+            .expect_write(&[Register::Output0.command(), 0b1111_1111, 0b0111_1111])
+            .expect_write(&[Register::Configuration0.command(), 0b1111_1111, 0b0111_1111]),
+        ScheduledFaults::new(&FAULTS),
+    );
+
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+    expander.set_safe_state(SAFE_STATE);
+
+    expander.set_mode(Bank0, Pin0, Output).unwrap_err();
+    assert!(!expander.is_safe_state_locked());
+
+    expander.set_mode(Bank0, Pin1, Output).unwrap_err();
+    assert!(expander.is_safe_state_locked());
+    assert_eq!(0b0111_1111, expander.cached_register(Register::Configuration1));
+    assert_eq!(0b1111_1111, expander.cached_register(Register::Configuration0));
+}
+
+#[test]
+fn test_safe_state_pins() {
+    let state = SafeState::new()
+        .output(Bank0, Pin1, true)
+        .output(Bank0, Pin2, false)
+        .input(Bank0, Pin1);
+
+    assert!(state.mode(Bank0, Pin1) == Input);
+    assert!(state.mode(Bank0, Pin2) == Output);
+    assert!(!state.is_output_high(Bank0, Pin1));
+    assert!(!state.is_output_high(Bank0, Pin2));
+    assert!(SafeState::default().mode(Bank1, Pin3) == Input);
+}
+
+#[test]
+fn test_interlock_rejects_state() {
+    const INTERLOCKS: [Interlock; 1] = [Interlock::new(
+        InterlockPin::high(Bank0, Pin0),
+        InterlockPin::high(Bank0, Pin1),
+    )];
+
+    let mut i2c_bus: ExpectationBus<3> = ExpectationBus::new()
+        .expect_output_write(Bank0, 0b0000_0000)
+        .expect_configuration_write(Bank0, 0b1111_1110)
+        .expect_configuration_write(Bank0, 0b1111_1100);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_interlocks(&INTERLOCKS);
+    expander.set_state_all(Bank0, false).unwrap();
+    expander.set_mode(Bank0, Pin0, Output).unwrap();
+    expander.set_mode(Bank0, Pin1, Output).unwrap();
+
+    expander.set_state(Bank0, Pin0, true).unwrap();
+    assert_eq!(
+        Err(Error::Interlock(INTERLOCKS[0])),
+        expander.set_state(Bank0, Pin1, true)
+    );
+    assert!(expander.is_pin_output_high(Bank0, Pin0));
+    assert!(!expander.is_pin_output_high(Bank0, Pin1));
+
+    expander.set_state(Bank0, Pin0, false).unwrap();
+    expander.set_state(Bank0, Pin1, true).unwrap();
+    assert_eq!(
+        Err(Error::Interlock(INTERLOCKS[0])),
+        expander.set_state_all(Bank0, true)
+    );
+    assert!(!expander.is_pin_output_high(Bank0, Pin0));
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_interlock_deactivates_other_bank() {
+    const INTERLOCKS: [Interlock; 1] =
+        [
+            Interlock::new(InterlockPin::low(Bank0, Pin4), InterlockPin::low(Bank1, Pin4))
+                .resolution(Resolution::DeactivateOther),
+        ];
+
+    let mut i2c_bus: ExpectationBus<5> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b1110_1111)
+        .expect_configuration_write(Bank1, 0b1110_1111)
+        .expect_output_write(Bank0, 0b1110_1111)
+        // Bank 0 needs to be deactivated first
+        .expect_output_write(Bank0, 0b1111_1111)
+        .expect_output_write(Bank1, 0b1110_1111);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_interlocks(&INTERLOCKS);
+    expander.set_mode(Bank0, Pin4, Output).unwrap();
+    expander.set_mode(Bank1, Pin4, Output).unwrap();
+
+    expander.set_state(Bank0, Pin4, false).unwrap();
+    expander.write_output_state(Bank0).unwrap();
+
+    expander.set_state(Bank1, Pin4, false).unwrap();
+    assert!(expander.is_pin_output_high(Bank0, Pin4));
+    expander.write_output_state(Bank1).unwrap();
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_interlock_rejects_mode() {
+    const INTERLOCKS: [Interlock; 1] = [Interlock::new(
+        InterlockPin::high(Bank0, Pin0),
+        InterlockPin::high(Bank1, Pin0),
+    )];
+
+    let mut i2c_bus: ExpectationBus<3> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b1111_1110)
+        .expect_output_write(Bank0, 0b0000_0000)
+        .expect_configuration_write(Bank1, 0b1111_1110);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_interlocks(&INTERLOCKS);
+    expander.set_mode(Bank0, Pin0, Output).unwrap();
+
+    // Output register is still high after power-on
+    assert_eq!(
+        Err(Error::Interlock(INTERLOCKS[0])),
+        expander.set_mode(Bank1, Pin0, Output)
+    );
+    assert_eq!(
+        Err(Error::Interlock(INTERLOCKS[0])),
+        expander.set_mode_all(Bank1, Output)
+    );
+    assert_eq!(0b1111_1111, expander.cached_register(Register::Configuration1));
+
+    expander.set_state_all(Bank0, false).unwrap();
+    expander.set_mode(Bank1, Pin0, Output).unwrap();
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_interlock_regular_pin() {
+    const INTERLOCKS: [Interlock; 1] = [Interlock::new(
+        InterlockPin::high(Bank0, Pin0),
+        InterlockPin::high(Bank0, Pin1),
+    )];
+
+    let mut i2c_bus: ExpectationBus<5> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b1111_1110)
+        .expect_output_write(Bank0, 0b1111_1110)
+        .expect_configuration_write(Bank0, 0b1111_1100)
+        .expect_output_write(Bank0, 0b1111_1100)
+        .expect_output_write(Bank0, 0b1111_1101);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_interlocks(&INTERLOCKS);
+
+    let pins = expander.pins();
+    let mut pin00 = pins.get_pin(Bank0, Pin0).into_output_pin(PinState::Low).unwrap();
+    let mut pin01 = pins.get_pin(Bank0, Pin1).into_output_pin(PinState::Low).unwrap();
+
+    pin00.set_high().unwrap();
+    assert_eq!(Err(Error::Interlock(INTERLOCKS[0])), pin01.set_high());
+    assert!(pin01.is_set_low().unwrap());
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_interlock_refreshable_pin() {
+    const INTERLOCKS: [Interlock; 1] = [Interlock::new(
+        InterlockPin::high(Bank0, Pin0),
+        InterlockPin::high(Bank1, Pin0),
+    )];
+
+    let mut i2c_bus: ExpectationBus<6> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b1111_1110)
+        .expect_output_write(Bank0, 0b1111_1110)
+        .expect_configuration_write(Bank1, 0b1111_1110)
+        .expect_output_write(Bank1, 0b1111_1110)
+        .expect_output_write(Bank0, 0b1111_1111)
+        .expect_output_write(Bank1, 0b1111_1110);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_interlocks(&INTERLOCKS);
+
+    let pins = expander.pins();
+    let mut pin00 = pins.get_refreshable_pin(Bank0, Pin0).into_output_pin(PinState::Low).unwrap();
+    let mut pin10 = pins.get_refreshable_pin(Bank1, Pin0).into_output_pin(PinState::Low).unwrap();
+
+    pin00.set_high().unwrap();
+    pin10.set_high().unwrap();
+
+    // Rejected change is dropped and reported by the next update
+    assert_eq!(Err(Error::Interlock(INTERLOCKS[0])), pin00.update_bank());
+    assert!(pin00.is_set_high().unwrap());
+    assert!(pin10.is_set_low().unwrap());
+
+    // Further updates are not affected
+    pin10.update_bank().unwrap();
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_interlock_refreshable_pin_deactivates_other() {
+    const INTERLOCKS: [Interlock; 1] =
+        [
+            Interlock::new(InterlockPin::high(Bank0, Pin0), InterlockPin::high(Bank1, Pin0))
+                .resolution(Resolution::DeactivateOther),
+        ];
+
+    let mut i2c_bus: ExpectationBus<6> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b1111_1110)
+        .expect_output_write(Bank0, 0b1111_1110)
+        .expect_configuration_write(Bank1, 0b1111_1110)
+        .expect_output_write(Bank1, 0b1111_1110)
+        .expect_output_write(Bank0, 0b1111_1110)
+        .expect_output_write(Bank1, 0b1111_1111);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_interlocks(&INTERLOCKS);
+
+    let pins = expander.pins();
+    let mut pin00 = pins.get_refreshable_pin(Bank0, Pin0).into_output_pin(PinState::Low).unwrap();
+    let mut pin10 = pins.get_refreshable_pin(Bank1, Pin0).into_output_pin(PinState::Low).unwrap();
+
+    pin00.set_high().unwrap();
+    pin10.set_high().unwrap();
+    pin10.update_all().unwrap();
+
+    // Pin10 was applied last, so Pin00 got deactivated
+    assert!(pin00.is_set_low().unwrap());
+    assert!(pin10.is_set_high().unwrap());
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_interlock_display() {
+    let interlock = Interlock::new(InterlockPin::high(Bank0, Pin3), InterlockPin::low(Bank1, Pin7));
+    let error: Error<DummyError> = Error::Interlock(interlock);
+
+    assert_eq!(
+        "Output change violates interlock of Pin03 high and Pin17 low",
+        format!("{}", error)
+    );
+    assert_eq!(None, error.bus_error_kind());
+}
+
+#[test]
+fn test_monitor_debounce() {
+    let mut i2c_bus: ExpectationBus<15> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b1111_1101)
+        .expect_configuration_write(Bank1, 0b0111_1111)
+        .expect_polarity_write(Bank1, 0b1000_0000)
+        .expect_write(&[Register::Input0.command()])
+        .expect_read(&[0b1111_1101, 0b0111_1111])
+        .expect_write(&[Register::Input0.command()])
+        .expect_read(&[0b1111_1101, 0b0111_1111])
+        // Mismatching input pins are ignored
+        .expect_write(&[Register::Input0.command()])
+        .expect_read(&[0b1111_0110, 0b0111_1111])
+        .expect_write(&[Register::Input0.command()])
+        .expect_read(&[0b1111_1101, 0b0111_1111])
+        .expect_write(&[Register::Input0.command()])
+        .expect_read(&[0b1111_0110, 0b0111_1111])
+        .expect_write(&[Register::Input0.command()])
+        .expect_read(&[0b1111_0110, 0b0111_1111]);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_mode(Bank0, Pin1, Output).unwrap();
+    expander.set_mode(Bank1, Pin7, Output).unwrap();
+    expander.reverse_polarity(Bank1, Pin7, true).unwrap();
+
+    let mut monitor = OutputMonitor::new(2);
+    assert!(monitor.sample(&mut expander).unwrap().is_empty());
+
+    let faults = monitor.sample(&mut expander).unwrap();
+    assert!(faults.is_faulty(Bank0, Pin1));
+    assert!(!faults.is_faulty(Bank1, Pin7));
+    assert_eq!(0b0000_0010, faults.bank(Bank0));
+    assert_eq!(0b0000_0000, faults.bank(Bank1));
+
+    // A single matching sample does not clear the fault
+    assert!(monitor.sample(&mut expander).unwrap().is_faulty(Bank0, Pin1));
+    assert!(monitor.sample(&mut expander).unwrap().is_faulty(Bank0, Pin1));
+    assert!(monitor.sample(&mut expander).unwrap().is_faulty(Bank0, Pin1));
+    assert!(monitor.sample(&mut expander).unwrap().is_empty());
+    assert!(monitor.faults().is_empty());
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_monitor_compares_written_output() {
+    let mut i2c_bus: ExpectationBus<4> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b0000_0000)
+        .expect_output_write(Bank0, 0b0000_0000)
+        .expect_write(&[Register::Input0.command()])
+        .expect_read(&[0b1000_0000, 0b1111_1111]);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_mode_all(Bank0, Output).unwrap();
+    expander.set_state_all(Bank0, false).unwrap();
+
+    // Not written yet
+    expander.set_state(Bank0, Pin7, true).unwrap();
+
+    let mut monitor = OutputMonitor::new(1);
+    assert_eq!(0b1000_0000, monitor.sample(&mut expander).unwrap().bank(Bank0));
+
+    monitor.reset();
+    assert!(monitor.faults().is_empty());
+    assert!(OutputMonitor::new(0).evaluate(&expander).is_faulty(Bank0, Pin7));
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_regular_pin_actual_level() {
+    let mut i2c_bus: ExpectationBus<6> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b1111_1011)
+        .expect_output_write(Bank0, 0b1111_1011)
+        .expect_input_read(Bank0, 0b0000_0100)
+        .expect_input_read(Bank0, 0b0000_0000);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    let pins = expander.pins();
+    let pin = pins.get_pin(Bank0, Pin2).into_output_pin(PinState::Low).unwrap();
+
+    assert_eq!(PinState::High, pin.actual_level().unwrap());
+    assert_eq!(PinState::Low, pin.actual_level().unwrap());
+    i2c_bus.done();
+}
+
+#[test]
+fn test_refreshable_pin_actual_level() {
+    let mut i2c_bus: ExpectationBus<4> = ExpectationBus::new()
+        .expect_configuration_write(Bank1, 0b1111_0111)
+        .expect_output_write(Bank1, 0b1111_1111)
+        .expect_input_read(Bank1, 0b0000_1000);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    let pins = expander.pins();
+    let output = pins.get_refreshable_pin(Bank1, Pin3).into_output_pin(PinState::High).unwrap();
+    let input = pins.get_refreshable_pin(Bank1, Pin0);
+
+    assert_eq!(PinState::Low, output.actual_level());
+    input.refresh_bank().unwrap();
+    assert_eq!(PinState::High, output.actual_level());
+    i2c_bus.done();
+}
+
+#[test]
+fn test_lock_rejects_changes() {
+    let mut i2c_bus: ExpectationBus<6> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b1111_1011)
+        .expect_output_write(Bank0, 0b1111_1011)
+        .expect_configuration_write(Bank0, 0b1111_1011)
+        .expect_polarity_write(Bank0, 0b0000_1000)
+        .expect_configuration_write(Bank0, 0b1111_0011)
+        .expect_register_write(Register::Output0, 0b0000_0011);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_mode(Bank0, Pin2, Output).unwrap();
+    expander.set_state(Bank0, Pin2, false).unwrap();
+    expander.write_output_state(Bank0).unwrap();
+
+    expander.lock_pin(Bank0, Pin2);
+    assert!(expander.is_pin_locked(Bank0, Pin2));
+    assert!(!expander.is_pin_locked(Bank1, Pin2));
+    assert_eq!(0b0000_0100, expander.locked_pins(Bank0));
+
+    assert_eq!(
+        Err(Error::PinLocked(Bank0, Pin2)),
+        expander.set_mode(Bank0, Pin2, Input)
+    );
+    assert_eq!(
+        Err(Error::PinLocked(Bank0, Pin2)),
+        expander.reverse_polarity(Bank0, Pin2, true)
+    );
+    assert_eq!(
+        Err(Error::PinLocked(Bank0, Pin2)),
+        expander.set_state(Bank0, Pin2, true)
+    );
+    assert_eq!(
+        Err(Error::PinLocked(Bank0, Pin2)),
+        expander.write_register(Register::Output0, 0b1111_1111, true)
+    );
+
+    // Unchanged state of locked pins is accepted
+    expander.set_state(Bank0, Pin2, false).unwrap();
+    expander.set_mode(Bank0, Pin2, Output).unwrap();
+    expander.reverse_polarity(Bank0, Pin3, true).unwrap();
+    expander.set_mode(Bank0, Pin3, Output).unwrap();
+    expander.write_register(Register::Output0, 0b0000_0011, true).unwrap();
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_lock_all_variants_skip_locked_pins() {
+    let mut i2c_bus: ExpectationBus<5> = ExpectationBus::new()
+        .expect_configuration_write(Bank1, 0b0111_1111)
+        .expect_configuration_write(Bank1, 0b0111_1111)
+        .expect_configuration_write(Bank1, 0b0000_0000)
+        .expect_output_write(Bank1, 0b1000_0000)
+        .expect_output_write(Bank1, 0b1111_1111);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_mode(Bank1, Pin7, Output).unwrap();
+    expander.lock_pins(Bank1, 0b1000_0000);
+
+    // Pin17 keeps output mode and high state
+    expander.set_mode_all(Bank1, Input).unwrap();
+    expander.set_mode_all(Bank1, Output).unwrap();
+    expander.set_state_all(Bank1, false).unwrap();
+
+    expander.unlock_pins(Bank1, 0b1000_0000).unwrap();
+    expander.set_state_all(Bank1, true).unwrap();
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_lock_sealed() {
+    let mut expander = PCA9539::new(ExpectationBus::<0>::new(), Address::default());
+    expander.lock_pins(Bank0, 0b0000_0011);
+    assert!(!expander.are_locks_sealed());
+
+    expander.seal_locks();
+    assert!(expander.are_locks_sealed());
+    assert_eq!(Err(Error::LocksSealed), expander.unlock_pins(Bank0, 0b0000_0001));
+    assert_eq!(0b0000_0011, expander.locked_pins(Bank0));
+
+    // Further pins may still be locked
+    expander.lock_pin(Bank1, Pin5);
+    assert!(expander.is_pin_locked(Bank1, Pin5));
+}
+
+#[test]
+fn test_lock_pins() {
+    let mut i2c_bus: ExpectationBus<6> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b1111_1110)
+        .expect_configuration_write(Bank0, 0b1111_1110)
+        .expect_output_write(Bank0, 0b1111_1111)
+        .expect_configuration_write(Bank0, 0b1111_1110)
+        .expect_output_write(Bank0, 0b1111_1111)
+        .expect_output_write(Bank0, 0b1111_1111);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_mode(Bank0, Pin0, Output).unwrap();
+    expander.lock_pin(Bank0, Pin0);
+
+    let pins = expander.pins();
+    let mut pin = pins.get_pin(Bank0, Pin0).into_output_pin(PinState::High).unwrap();
+    assert_eq!(Err(Error::PinLocked(Bank0, Pin0)), pin.set_low());
+    assert_eq!(Error::PinLocked(Bank0, Pin0), pin.into_input_pin().err().unwrap());
+
+    // Refresh mode ignores changes of locked pins and reports them by the next update
+    let mut pin = pins.get_refreshable_pin(Bank0, Pin0).into_output_pin(PinState::High).unwrap();
+    pin.set_low().unwrap();
+    assert!(pin.is_set_high().unwrap());
+    assert_eq!(Err(Error::PinLocked(Bank0, Pin0)), pin.update_bank());
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_config_with_config() {
+    const BOARD: Config = Config::new()
+        .bank(
+            Bank0,
+            BankConfig::new()
+                .pin(Pin0, PinConfig::output(false).safe_output(false).label("PYRO_ARM"))
+                .pin(Pin1, PinConfig::output(true).label("PWR_EN")),
+        )
+        .pin(Bank1, Pin7, PinConfig::input().inverted().label("LID_OPEN"));
+
+    let i2c_bus = ExpectationBus::<5>::new()
+        .expect_write(&[Register::Output0.command(), 0b1111_1110, 0b1111_1111])
+        .expect_write(&[Register::Polarity0.command(), 0b0000_0000, 0b1000_0000])
+        .expect_write(&[Register::Configuration0.command(), 0b1111_1100, 0b1111_1111])
+        // Safe state of the configuration
+        .expect_write(&[Register::Output0.command(), 0b1111_1110, 0b1111_1111])
+        .expect_write(&[Register::Configuration0.command(), 0b1111_1110, 0b1111_1111]);
+
+    let mut expander = PCA9539::with_config(i2c_bus, Address::default(), &BOARD).unwrap();
+    assert!(expander.diff_config(&BOARD).is_empty());
+    assert!(!expander.is_pin_output_high(Bank0, Pin0));
+
+    expander.enter_safe_state().unwrap();
+}
+
+#[test]
+fn test_config_values() {
+    const BOARD: Config = Config::new()
+        .pin(Bank0, Pin3, PinConfig::output(false).safe_output(true).label("RELAY"))
+        .pin(Bank1, Pin1, PinConfig::input().inverted().label("BUTTON"));
+
+    let bank = BOARD.get_bank(Bank0);
+    assert_eq!(0b1111_0111, bank.output());
+    assert_eq!(0b1111_0111, bank.configuration());
+    assert_eq!(0b0000_0000, bank.polarity());
+    assert_eq!(0b0000_0010, BOARD.get_bank(Bank1).polarity());
+
+    let pin = BOARD.get_pin(Bank0, Pin3);
+    assert!(pin.mode() == Output);
+    assert!(!pin.is_high());
+    assert!(!pin.is_inverted());
+    assert_eq!(Some(true), pin.safe_level());
+    assert_eq!("RELAY", pin.get_label());
+
+    assert_eq!(Some((Bank1, Pin1)), BOARD.find("BUTTON"));
+    assert_eq!(None, BOARD.find("MISSING"));
+
+    let safe_state = BOARD.safe_state();
+    assert!(safe_state.mode(Bank0, Pin3) == Output);
+    assert!(safe_state.is_output_high(Bank0, Pin3));
+    assert!(safe_state.mode(Bank1, Pin1) == Input);
+    assert_eq!(Config::default(), Config::new());
+}
+
+#[test]
+fn test_config_diff() {
+    const BOARD: Config =
+        Config::new()
+            .pin(Bank0, Pin2, PinConfig::output(true))
+            .pin(Bank1, Pin4, PinConfig::input().inverted());
+
+    let mut i2c_bus: ExpectationBus<5> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b1111_1011)
+        .expect_output_write(Bank0, 0b1111_1011)
+        .expect_output_write(Bank1, 0b0000_0000)
+        .expect_configuration_write(Bank0, 0b1111_1111)
+        .expect_configuration_write(Bank1, 0b1111_1110);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    let diff = expander.diff_config(&BOARD);
+    assert_eq!(0b0000_0100, diff.mode(Bank0));
+    assert_eq!(0b0001_0000, diff.polarity(Bank1));
+    assert!(diff.is_pin_different(Bank0, Pin2));
+    assert!(!diff.is_pin_different(Bank0, Pin3));
+
+    expander.set_mode(Bank0, Pin2, Output).unwrap();
+    expander.set_state(Bank0, Pin2, false).unwrap();
+    expander.write_output_state(Bank0).unwrap();
+
+    // Output level of input pins is ignored
+    expander.set_state_all(Bank1, false).unwrap();
+
+    let diff = expander.diff_config(&BOARD);
+    assert_eq!(0b0000_0000, diff.mode(Bank0));
+    assert_eq!(0b0000_0100, diff.output(Bank0));
+    assert_eq!(0b0000_0000, diff.output(Bank1));
+
+    expander.set_mode(Bank0, Pin2, Input).unwrap();
+    expander.set_mode(Bank1, Pin0, Output).unwrap();
+    let diff = expander.diff_config(&BOARD);
+    assert_eq!(0b0000_0100, diff.mode(Bank0));
+    assert_eq!(0b0000_0001, diff.mode(Bank1));
+    assert_eq!(0b0000_0100, diff.output(Bank0));
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_config_apply_rejected_by_lock() {
+    const BOARD: Config = Config::new().pin(Bank0, Pin2, PinConfig::output(true));
+
+    let mut i2c_bus = ExpectationBus::<2>::new()
+        .expect_write(&[Register::Output0.command(), 0b1111_1111, 0b1111_1111])
+        .expect_write(&[Register::Configuration0.command(), 0b1111_1111, 0b1111_1111]);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.lock_pin(Bank0, Pin2);
+
+    assert_eq!(Err(Error::PinLocked(Bank0, Pin2)), expander.apply_config(&BOARD));
+    assert_eq!(0b1111_1111, expander.cached_register(Register::Configuration0));
+
+    // Safe state of the configuration is not registered
+    expander.enter_safe_state().unwrap();
+    i2c_bus.done();
+}
+
+#[test]
+fn test_pin_map() {
+    pin_map! {
+        struct BoardPins {
+            led_status: output(Bank1, Pin3, Low),
+            relay_main: refreshable output(Bank0, Pin0, High),
+            sw_arm: input(Bank0, Pin7),
+            sw_mode: refreshable input(Bank1, Pin6),
+        }
+
+        const BOARD_CONFIG: Config;
+    }
+
+    let mut i2c_bus: ExpectationBus<9> = ExpectationBus::new()
+        .expect_configuration_write(Bank1, 0b1111_0111)
+        .expect_output_write(Bank1, 0b1111_0111)
+        .expect_configuration_write(Bank0, 0b1111_1110)
+        .expect_output_write(Bank0, 0b1111_1111)
+        .expect_configuration_write(Bank0, 0b1111_1110)
+        .expect_configuration_write(Bank1, 0b1111_0111)
+        .expect_output_write(Bank1, 0b1111_1111)
+        .expect_input_read(Bank0, 0b1000_0000);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    let pins = expander.pins();
+    let mut board = BoardPins::new(&pins).unwrap();
+
+    board.led_status.set_high().unwrap();
+    board.relay_main.set_low().unwrap();
+    assert!(board.sw_arm.is_high().unwrap());
+    assert!(!board.sw_mode.is_high().unwrap());
+
+    assert_eq!(0b1111_1110, BOARD_CONFIG.get_bank(Bank0).configuration());
+    assert_eq!(0b1111_0111, BOARD_CONFIG.get_bank(Bank1).configuration());
+    assert_eq!(0b1111_0111, BOARD_CONFIG.get_bank(Bank1).output());
+    assert_eq!(Some((Bank0, Pin0)), BOARD_CONFIG.find("relay_main"));
+    assert_eq!("sw_mode", BOARD_CONFIG.get_pin(Bank1, Pin6).get_label());
+}
+
+#[test]
+fn test_snapshot_bytes() {
+    let snapshot = Snapshot::new([0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+    let bytes = snapshot.to_bytes();
+
+    // Version, register values and CRC-16/CCITT-FALSE
+    assert_eq!(
+        [0x01, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x85, 0xFD],
+        bytes
+    );
+    assert_eq!(Ok(snapshot), Snapshot::from_bytes(&bytes));
+    assert_eq!(0x04, snapshot.get(Register::Output1));
+    assert_eq!(
+        snapshot,
+        Snapshot::from(RegisterDump::new([0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]))
+    );
+}
+
+#[test]
+fn test_snapshot_invalid_bytes() {
+    let mut bytes = Snapshot::new([0xFF; 8]).to_bytes();
+    bytes[3] ^= 0b0001_0000;
+    assert_eq!(Err(SnapshotError::ChecksumMismatch), Snapshot::from_bytes(&bytes));
+    assert_eq!(
+        Err(SnapshotError::InvalidLength(10)),
+        Snapshot::from_bytes(&bytes[..10])
+    );
+
+    bytes[0] = 2;
+    assert_eq!(Err(SnapshotError::UnsupportedVersion(2)), Snapshot::from_bytes(&bytes));
+
+    assert_eq!(
+        "Invalid snapshot length 3, expected 11",
+        SnapshotError::InvalidLength(3).to_string()
+    );
+    assert_eq!(
+        "Unsupported snapshot version 7",
+        SnapshotError::UnsupportedVersion(7).to_string()
+    );
+    assert_eq!(
+        "Snapshot checksum mismatch",
+        SnapshotError::ChecksumMismatch.to_string()
+    );
+}
+
+#[test]
+fn test_snapshot_restore() {
+    let mut i2c_bus: ExpectationBus<5> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b0000_0000)
+        .expect_output_write(Bank0, 0b1111_1101)
+        .expect_polarity_write(Bank1, 0b0000_0010)
+        .expect_input_read(Bank1, 0b0101_1010);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_mode_all(Bank0, Output).unwrap();
+    expander.set_state(Bank0, Pin1, false).unwrap();
+    expander.write_output_state(Bank0).unwrap();
+    expander.reverse_polarity(Bank1, Pin1, true).unwrap();
+    expander.refresh_input_state(Bank1).unwrap();
+
+    let bytes = expander.snapshot().to_bytes();
+    i2c_bus.done();
+
+    let mut i2c_bus: ExpectationBus<5> = ExpectationBus::new()
+        .expect_write(&[Register::Output0.command(), 0b1111_1101, 0b1111_1111])
+        .expect_write(&[Register::Polarity0.command(), 0b0000_0000, 0b0000_0010])
+        .expect_write(&[Register::Configuration0.command(), 0b0000_0000, 0b1111_1111])
+        .expect_write(&[Register::Input0.command()])
+        .expect_read(&[0b0000_0001, 0b1000_0000]);
+
+    let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.restore(&snapshot).unwrap();
+
+    for register in Register::ALL.into_iter().filter(|register| !register.is_input()) {
+        assert_eq!(snapshot.get(register), expander.cached_register(register));
+    }
+
+    // Input state is refreshed instead of taken from the snapshot
+    assert_eq!(0b0000_0001, expander.cached_register(Register::Input0));
+    assert_eq!(0b1000_0000, expander.cached_register(Register::Input1));
+    i2c_bus.done();
+
+    let mut expander = PCA9539::new(ExpectationBus::<0>::new(), Address::default());
+    expander.lock_pin(Bank0, Pin1);
+    assert_eq!(Err(Error::PinLocked(Bank0, Pin1)), expander.restore(&snapshot));
+}
+
+#[test]
+fn test_profile_switch_minimal() {
+    static PROFILES: [Profile; 2] = [
+        Profile::new("ground", Config::new().pin(Bank0, Pin0, PinConfig::output(false))),
+        Profile::new(
+            "flight",
+            Config::new().pin(Bank0, Pin0, PinConfig::output(true).safe_output(false)).pin(
+                Bank0,
+                Pin1,
+                PinConfig::output(false),
+            ),
+        ),
+    ];
+
+    let mut i2c_bus: ExpectationBus<6> = ExpectationBus::new()
+        .expect_output_write(Bank0, 0b1111_1110)
+        .expect_configuration_write(Bank0, 0b1111_1110)
+        .expect_output_write(Bank0, 0b1111_1101)
+        .expect_configuration_write(Bank0, 0b1111_1100)
+        .expect_write(&[Register::Output0.command(), 0b1111_1100, 0b1111_1111])
+        .expect_write(&[Register::Configuration0.command(), 0b1111_1110, 0b1111_1111]);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_profiles(&PROFILES);
+    assert_eq!(None, expander.active_profile());
+
+    let diff = expander.switch_profile("ground").unwrap();
+    assert_eq!(0b0000_0001, diff.mode(Bank0));
+    assert_eq!(0b0000_0001, diff.output(Bank0));
+    assert_eq!(Some("ground"), expander.active_profile());
+
+    let diff = expander.switch_profile("flight").unwrap();
+    assert_eq!(0b0000_0010, diff.mode(Bank0));
+    assert_eq!(0b0000_0011, diff.output(Bank0));
+    assert!(!diff.is_pin_different(Bank1, Pin0));
+    assert_eq!(Some("flight"), expander.active_profile());
+
+    // Nothing to write
+    assert!(expander.switch_profile("flight").unwrap().is_empty());
+    assert_eq!(Err(Error::UnknownProfile), expander.switch_profile("recovery"));
+    assert_eq!(Some("flight"), expander.active_profile());
+
+    // Safe state of the profile is registered
+    expander.enter_safe_state().unwrap();
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_profile_switch_both_banks() {
+    static PROFILES: [Profile; 1] = [Profile::new(
+        "recovery",
+        Config::new().pin(Bank0, Pin7, PinConfig::output(true)).pin(
+            Bank1,
+            Pin0,
+            PinConfig::output(true).inverted(),
+        ),
+    )];
+
+    let i2c_bus = ExpectationBus::<2>::new()
+        .expect_write(&[Register::Polarity1.command(), 0b0000_0001])
+        .expect_write(&[Register::Configuration0.command(), 0b0111_1111, 0b1111_1110]);
+
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+    expander.set_profiles(&PROFILES);
+
+    let diff = expander.switch_profile("recovery").unwrap();
+    assert_eq!(0b1000_0000, diff.mode(Bank0));
+    assert_eq!(0b0000_0001, diff.mode(Bank1));
+    assert_eq!(0b0000_0001, diff.polarity(Bank1));
+    assert_eq!(0b0000_0000, diff.output(Bank0));
+}
+
+#[test]
+fn test_profile_switch_bus_error() {
+    static PROFILES: [Profile; 2] = [
+        Profile::new("ground", Config::new().pin(Bank0, Pin0, PinConfig::output(false))),
+        Profile::new(
+            "flight",
+            Config::new()
+                .pin(Bank0, Pin0, PinConfig::output(false))
+                .pin(Bank0, Pin1, PinConfig::output(false)),
+        ),
+    ];
+    const FAULTS: [(usize, Fault); 1] = [(3, Fault::Bus)];
+
+    let i2c_bus = FaultyBus::new(
+        ExpectationBus::<4>::new()
+            .expect_output_write(Bank0, 0b1111_1110)
+            .expect_configuration_write(Bank0, 0b1111_1110)
+            .expect_output_write(Bank0, 0b1111_1100)
+            .expect_configuration_write(Bank0, 0b1111_1100),
+        ScheduledFaults::new(&FAULTS),
+    );
+
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+    expander.set_profiles(&PROFILES);
+    expander.switch_profile("ground").unwrap();
+
+    // Output register got switched, configuration register not
+    assert!(expander.switch_profile("flight").unwrap_err().bus_error().is_some());
+    assert_eq!(None, expander.active_profile());
+    assert_eq!(0b1111_1100, expander.cached_register(Register::Output0));
+    assert_eq!(0b0000_0010, expander.diff_config(PROFILES[1].config()).mode(Bank0));
+
+    expander.switch_profile("flight").unwrap();
+    assert_eq!(Some("flight"), expander.active_profile());
+}
+
+#[test]
+fn test_profile_switch_rejected_by_lock() {
+    static PROFILES: [Profile; 1] = [Profile::new(
+        "flight",
+        Config::new().pin(Bank1, Pin2, PinConfig::output(false)),
+    )];
+
+    let mut expander = PCA9539::new(ExpectationBus::<0>::new(), Address::default());
+    expander.set_profiles(&PROFILES);
+    expander.lock_pin(Bank1, Pin2);
+
+    assert_eq!(Err(Error::PinLocked(Bank1, Pin2)), expander.switch_profile("flight"));
+    assert_eq!(None, expander.active_profile());
+    assert_eq!(0b1111_1111, expander.cached_register(Register::Output1));
+}
+
+#[test]
+fn test_cache_lock_free_access() {
+    let mut i2c_bus: ExpectationBus<7> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b1111_1110)
+        .expect_output_write(Bank0, 0b1111_1111)
+        .expect_configuration_write(Bank0, 0b1111_1100)
+        .expect_output_write(Bank0, 0b1111_1111)
+        .expect_input_read(Bank1, 0b0000_0100)
+        .expect_output_write(Bank0, 0b1111_1100);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    let guard = LockFreeGuard::new(RefCell::new(&mut expander));
+    let mut input = Pin::refreshable(&guard, Bank1, Pin2);
+    let mut pin0 = Pin::refreshable(&guard, Bank0, Pin0).into_output_pin(PinState::High).unwrap();
+    let mut pin1 = Pin::refreshable(&guard, Bank0, Pin1).into_output_pin(PinState::High).unwrap();
+
+    guard.access(|expander| {
+        expander.refresh_input_state(Bank1).unwrap();
+        expander.set_state(Bank0, Pin1, false).unwrap();
+
+        // Pins are not blocked by the locked guard
+        assert!(!input.is_high().unwrap());
+        pin0.set_low().unwrap();
+        assert!(pin0.is_set_low().unwrap());
+    });
+
+    // Changes of the locked access and lock-free changes of the same bank are both kept
+    assert!(input.is_high().unwrap());
+    assert!(pin0.is_set_low().unwrap());
+    assert!(pin1.is_set_low().unwrap());
+    pin0.update_bank().unwrap();
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_cache_safe_state_ignores_changes() {
+    let mut i2c_bus: ExpectationBus<4> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b1111_1110)
+        .expect_output_write(Bank0, 0b1111_1110)
+        .expect_write(&[Register::Output0.command(), 0b1111_1110, 0b1111_1111])
+        .expect_write(&[Register::Configuration0.command(), 0b1111_1111, 0b1111_1111]);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    let guard = LockFreeGuard::new(RefCell::new(&mut expander));
+    let mut pin = Pin::refreshable(&guard, Bank0, Pin0).into_output_pin(PinState::Low).unwrap();
+
+    guard.access(|expander| expander.enter_safe_state().unwrap());
+
+    pin.set_high().unwrap();
+    assert!(pin.is_set_low().unwrap());
+    assert_eq!(Err(Error::SafeStateLocked), pin.update_bank());
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_cache_reports_locked_pin_change() {
+    let mut i2c_bus: ExpectationBus<4> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b1111_1110)
+        .expect_output_write(Bank0, 0b1111_1110)
+        .expect_output_write(Bank0, 0b1111_1110)
+        .expect_output_write(Bank0, 0b1111_1110);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    let guard = LockFreeGuard::new(RefCell::new(&mut expander));
+    let mut pin = Pin::refreshable(&guard, Bank0, Pin0).into_output_pin(PinState::Low).unwrap();
+
+    guard.access(|expander| expander.lock_pin(Bank0, Pin0));
+
+    // Unchanged state is no rejection
+    pin.set_low().unwrap();
+    guard.access(|_| ());
+    assert_eq!(Ok(()), pin.update_bank());
+
+    pin.set_high().unwrap();
+    assert!(pin.is_set_low().unwrap());
+
+    // Rejection is kept by other locked accesses until reported by the update
+    guard.access(|_| ());
+    assert_eq!(Err(Error::PinLocked(Bank0, Pin0)), pin.update_bank());
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_guard_access_returns_result() {
+    let i2c_bus = ExpectationBus::<2>::new().expect_input_read(Bank0, 0b0000_0010);
+
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+    let guard = LockFreeGuard::new(RefCell::new(&mut expander));
+
+    assert_eq!(
+        Ok(0b0000_0010),
+        guard.access(|expander| expander.read_register(Register::Input0, true))
+    );
+    assert_eq!(
+        Ok(0b0000_0010),
+        guard.try_access(|expander| expander.cached_register(Register::Input0))
+    );
+}
+
+#[test]
+fn test_guard_try_access_reentrant() {
+    let mut expander = PCA9539::new(ExpectationBus::<0>::new(), Address::default());
+    let guard = LockFreeGuard::new(RefCell::new(&mut expander));
+
+    let nested = guard.access(|_| guard.try_access(|_| ()));
+    assert_eq!(Err(AccessError::Reentrant), nested);
+    assert_eq!(Ok(()), guard.try_access(|_| ()));
+}
+
+#[test]
+#[cfg(feature = "spin")]
+fn test_guard_try_access_contended() {
+    let mut expander = PCA9539::new(ExpectationBus::<0>::new(), Address::default());
+    let guard = SpinGuard::new(spin::Mutex::new(RefCell::new(&mut expander)));
+
+    let nested = guard.access(|_| guard.try_access(|_| ()));
+    assert_eq!(Err(AccessError::Contended), nested);
+    assert_eq!(Ok(()), guard.try_access(|_| ()));
+}
+
+#[test]
+fn test_link_recovery_writes_failed_safe_state() {
+    const SAFE_STATE: SafeState = SafeState::new().output(Bank1, Pin3, false).enter_after_errors(1);
+
+    let connected = Cell::new(true);
+    let policy = |_| match connected.get() {
+        true => None,
+        false => Some(Fault::Nack(NoAcknowledgeSource::Address)),
+    };
+
+    let i2c_bus = RefCell::new(FaultyBus::new(SimulatedPCA9539::new(Address::default()), policy));
+    let mut expander = PCA9539::new(RefCellDevice::new(&i2c_bus), Address::default());
+    expander.set_link_policy(LinkPolicy::new().offline_after(1));
+    expander.set_safe_state(SAFE_STATE);
+
+    // Writing the safe state fails as well
+    connected.set(false);
+    assert!(expander.set_mode(Bank0, Pin0, Output).is_err());
+    assert!(expander.is_safe_state_locked());
+    assert_eq!(
+        0b1111_1111,
+        i2c_bus.borrow_mut().bus().register(Register::Configuration1)
+    );
+
+    connected.set(true);
+    assert_eq!(LinkState::Online, expander.poll_link());
+    assert_eq!(
+        0b1111_0111,
+        i2c_bus.borrow_mut().bus().register(Register::Configuration1)
+    );
+    assert_eq!(0b1111_0111, i2c_bus.borrow_mut().bus().register(Register::Output1));
+}
+
+#[test]
+fn test_link_recovery_adopt_hardware_keeps_safe_state() {
+    const SAFE_STATE: SafeState = SafeState::new().output(Bank1, Pin3, false);
+
+    let connected = Cell::new(true);
+    let policy = |_| match connected.get() {
+        true => None,
+        false => Some(Fault::Nack(NoAcknowledgeSource::Address)),
+    };
+
+    let i2c_bus = RefCell::new(FaultyBus::new(SimulatedPCA9539::new(Address::default()), policy));
+    let mut expander = PCA9539::new(RefCellDevice::new(&i2c_bus), Address::default());
+    expander.set_link_policy(LinkPolicy::new().offline_after(1).recovery(Recovery::AdoptHardware));
+    expander.set_safe_state(SAFE_STATE);
+    expander.enter_safe_state().unwrap();
+
+    connected.set(false);
+    expander.refresh_input_state(Bank0).unwrap_err();
+    assert_eq!(LinkState::Offline, expander.poll_link());
+
+    // Device lost its state while being disconnected
+    i2c_bus.borrow_mut().bus().reset();
+    connected.set(true);
+
+    assert_eq!(LinkState::Online, expander.poll_link());
+    assert_eq!(0b1111_0111, expander.cached_register(Register::Configuration1));
+    assert!(!expander.is_pin_output_high(Bank1, Pin3));
+    assert_eq!(
+        0b1111_0111,
+        i2c_bus.borrow_mut().bus().register(Register::Configuration1)
+    );
+    assert_eq!(0b1111_0111, i2c_bus.borrow_mut().bus().register(Register::Output1));
+}
+
+#[test]
+fn test_interlock_rejects_register_write() {
+    const INTERLOCKS: [Interlock; 1] = [Interlock::new(
+        InterlockPin::high(Bank0, Pin0),
+        InterlockPin::high(Bank1, Pin0),
+    )];
+
+    let mut i2c_bus: ExpectationBus<2> = ExpectationBus::new()
+        .expect_configuration_write(Bank0, 0b1111_1110)
+        .expect_output_write(Bank1, 0b0000_0000);
+
+    let mut expander = PCA9539::new(&mut i2c_bus, Address::default());
+    expander.set_interlocks(&INTERLOCKS);
+    expander.write_register(Register::Configuration0, 0b1111_1110, true).unwrap();
+
+    assert_eq!(
+        Err(Error::Interlock(INTERLOCKS[0])),
+        expander.write_register(Register::Configuration1, 0b1111_1110, true)
+    );
+
+    // Bank 1 is still in input mode
+    expander.write_register(Register::Output1, 0b0000_0000, false).unwrap();
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_config_apply_bus_error_keeps_cache() {
+    const BOARD: Config = Config::new().pin(Bank0, Pin2, PinConfig::output(false).inverted());
+    const FAULTS: [(usize, Fault); 1] = [(1, Fault::Bus)];
+
+    let i2c_bus = FaultyBus::new(
+        ExpectationBus::<1>::new().expect_write(&[Register::Output0.command(), 0b1111_1011, 0b1111_1111]),
+        ScheduledFaults::new(&FAULTS),
+    );
+
+    let mut expander = PCA9539::new(i2c_bus, Address::default());
+    assert!(expander.apply_config(&BOARD).is_err());
+
+    // Polarity and configuration never reached the device
+    let diff = expander.diff_config(&BOARD);
+    assert_eq!(0b0000_0100, diff.mode(Bank0));
+    assert_eq!(0b0000_0100, diff.polarity(Bank0));
+    assert_eq!(0b1111_1011, expander.cached_register(Register::Output0));
+    assert_eq!(0b0000_0000, expander.cached_register(Register::Polarity0));
+}
+
+#[test]
+fn test_retry_read_restores_pointer_of_custom_address() {
+    const ADDRESS: Address = Address::custom(0x20);
+    const FAULTS: [(usize, Fault); 1] = [(1, Fault::Nack(NoAcknowledgeSource::Data))];
+
+    let mut i2c_bus: ExpectationBus<3> = ExpectationBus::new()
+        .address(ADDRESS)
+        .expect_write(&[Register::Input0.command()])
+        .expect_write(&[Register::Input0.command()])
+        .expect_read(&[0b0000_0001]);
+
+    let faulty_bus = FaultyBus::new(&mut i2c_bus, ScheduledFaults::new(&FAULTS));
+    let mut expander = PCA9539::with_retry(faulty_bus, ADDRESS, RetryPolicy::default(), NoDelay);
+
+    expander.refresh_input_state(Bank0).unwrap();
+    assert!(expander.is_pin_input_high(Bank0, Pin0));
+
+    i2c_bus.done();
+}
+
+#[test]
+fn test_retry_pointer_tracking_replaces_oldest_address() {
+    const FAULTS: [(usize, Fault); 1] = [(5, Fault::Nack(NoAcknowledgeSource::Data))];
+
+    let mut i2c_bus: ExpectationBus<6> = ExpectationBus::new();
+    for address in [0x20, 0x21, 0x22, 0x23, 0x24] {
+        i2c_bus = i2c_bus
+            .address(Address::custom(address))
+            .expect_write(&[Register::Input1.command()]);
+    }
+
+    // Pointer of 0x20 got replaced by 0x24, so retrying is not preceded by restoring the pointer
+    let mut retry_bus = RetryBus::new(
+        FaultyBus::new(
+            i2c_bus.address(Address::custom(0x20)).expect_read(&[0b0000_0001]),
+            ScheduledFaults::new(&FAULTS),
+        ),
+        RetryPolicy::default(),
+        NoDelay,
+    );
+
+    for address in [0x20, 0x21, 0x22, 0x23, 0x24] {
+        retry_bus.write(address, &[Register::Input1.command()]).unwrap();
+    }
+
+    let mut buffer = [0x0; 1];
+    retry_bus.read(0x20, &mut buffer).unwrap();
+    assert_eq!([0b0000_0001], buffer);
+    retry_bus.release().0.release().0.done();
 }
 
 /// Testing spin based RefGuard
@@ -0,0 +1,62 @@
+//! Tracing of register accesses using defmt and/or log
+//!
+//! Register reads and writes are traced on `trace` level, bus errors on `warn` level. The verbosity is
+//! chosen at compile time by the logging framework, e.g. by the `max_level_*` features of log or the
+//! `DEFMT_LOG` environment variable of defmt. Without `defmt` and `log` feature, all functions are
+//! empty and optimized away.
+#![cfg_attr(not(any(feature = "defmt", feature = "log")), allow(unused_variables))]
+
+use crate::address::Address;
+use crate::error::Error;
+use crate::register::Register;
+use embedded_hal::i2c;
+
+/// Traces the values read starting at the given register (auto-increment toggles within the pair)
+#[inline(always)]
+pub(crate) fn read(address: Address, register: Register, values: &[u8]) {
+    #[cfg(any(feature = "defmt", feature = "log"))]
+    {
+        let mut register = register;
+
+        for value in values {
+            #[cfg(feature = "defmt")]
+            defmt::trace!("{=u8:#04x} read {} = {=u8:#010b}", address.value(), register, *value);
+            #[cfg(feature = "log")]
+            log::trace!("{:#04x} read {} = {:#010b}", address.value(), register, value);
+
+            register = register.pair();
+        }
+    }
+}
+
+/// Traces the value written to the given register
+#[inline(always)]
+pub(crate) fn write(address: Address, register: Register, value: u8) {
+    #[cfg(feature = "defmt")]
+    defmt::trace!("{=u8:#04x} write {} = {=u8:#010b}", address.value(), register, value);
+    #[cfg(feature = "log")]
+    log::trace!("{:#04x} write {} = {:#010b}", address.value(), register, value);
+}
+
+/// Traces a failed bus operation
+#[inline(always)]
+pub(crate) fn error<E: i2c::Error>(address: Address, error: &Error<E>) {
+    #[cfg(feature = "defmt")]
+    if let Error::Bus {
+        error,
+        operation,
+        register,
+    } = error
+    {
+        defmt::warn!(
+            "{=u8:#04x} {} of {} failed: {}",
+            address.value(),
+            operation,
+            register,
+            error.kind()
+        );
+    }
+
+    #[cfg(feature = "log")]
+    log::warn!("{:#04x} {}", address.value(), error);
+}